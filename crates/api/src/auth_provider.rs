@@ -0,0 +1,361 @@
+//! Where API keys are validated.
+//!
+//! [`AuthClient`](crate::state::AuthClient) caches responses from an
+//! [`AuthProvider`]: the TS auth daemon ([`DaemonAuthProvider`]), a local
+//! key store ([`LocalKeyStore`](crate::key_store::LocalKeyStore)) for
+//! self-hosted deployments, or [`MockAuthProvider`] for development.
+
+use axum::async_trait;
+use engine_core::{AuthErrorCode, AuthRequest, AuthResponse, Error, KeyType, ParsedApiKey};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::state::{Features, FeaturesResponse};
+
+/// Consecutive auth service failures before the circuit opens.
+const AUTH_BREAKER_THRESHOLD: u32 = 5;
+
+/// How long the circuit stays open before a trial request.
+const AUTH_BREAKER_OPEN_FOR: Duration = Duration::from_secs(10);
+
+/// Source of truth for API keys.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Short name for logs.
+    fn name(&self) -> &'static str;
+
+    /// Validate a key. Rejected keys are an `Ok` response with `valid: false`;
+    /// `Err` means the provider could not answer.
+    async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error>;
+
+    /// Feature flags for the key's project.
+    async fn features(&self, api_key: &ParsedApiKey) -> Result<Features, Error>;
+
+    /// Whether responses may be cached. Providers that answer from memory
+    /// return false so revocations apply immediately.
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// Accepts every well-formed key, for testing and development.
+pub struct MockAuthProvider;
+
+#[async_trait]
+impl AuthProvider for MockAuthProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+        debug!("Using mock auth validation");
+        Ok(AuthResponse {
+            valid: true,
+            project_id: Some(generate_mock_project_id(api_key)),
+            permissions: Some(vec!["ingest".into(), "read".into()]),
+            rate_limit: Some(1000),
+            tier: None,
+            allowed_origins: None,
+            key_type: KeyType::Browser,
+            error: None,
+            mau: None,
+            signing_secret: None,
+            require_signature: false,
+            ip_allowlist: None,
+            ip_denylist: None,
+        })
+    }
+
+    /// All features enabled except location.
+    async fn features(&self, _api_key: &ParsedApiKey) -> Result<Features, Error> {
+        Ok(Features::default())
+    }
+}
+
+/// Generate a deterministic mock project ID from the API key.
+/// This is for testing only - in production, the auth service provides this.
+fn generate_mock_project_id(api_key: &ParsedApiKey) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    api_key.as_str().hash(&mut hasher);
+    let hash = hasher.finish();
+    format!("proj-{:016x}", hash)
+}
+
+/// The TS auth daemon.
+///
+/// Calls `/internal/auth/validate` and `/features` through a circuit
+/// breaker.
+pub struct DaemonAuthProvider {
+    /// Auth service URL (e.g., "http://auth-service:8080")
+    base_url: String,
+    /// HTTP client
+    http_client: reqwest::Client,
+    /// Breaker around auth service calls
+    breaker: CircuitBreaker,
+}
+
+impl DaemonAuthProvider {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .expect("Failed to create HTTP client"),
+            breaker: CircuitBreaker::new("auth", AUTH_BREAKER_THRESHOLD, AUTH_BREAKER_OPEN_FOR),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for DaemonAuthProvider {
+    fn name(&self) -> &'static str {
+        "daemon"
+    }
+
+    /// Connection failures, 5xx responses and unparseable bodies count
+    /// against the circuit breaker.
+    async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+        if !self.breaker.allow() {
+            return Err(Error::auth(
+                AuthErrorCode::ServiceUnavailable,
+                "Auth service unavailable, retry later",
+            ));
+        }
+
+        let url = format!("{}/internal/auth/validate", self.base_url);
+        let request = AuthRequest::new(api_key.as_str());
+
+        debug!(url = %url, "Calling auth service");
+
+        let response = match self.http_client.post(&url).json(&request).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(error = %e, "Auth service request failed");
+                self.breaker.record_failure();
+                return Err(Error::auth(
+                    AuthErrorCode::ServiceUnavailable,
+                    format!("Auth service unavailable: {}", e),
+                ));
+            }
+        };
+
+        let status = response.status();
+        if status.is_server_error() {
+            let body = response.text().await.unwrap_or_default();
+            warn!(status = %status, body = %body, "Auth service returned error");
+            self.breaker.record_failure();
+            return Err(Error::auth(
+                AuthErrorCode::ServiceUnavailable,
+                format!("Auth service returned {}", status),
+            ));
+        }
+
+        let body = response.bytes().await.unwrap_or_default();
+        match serde_json::from_slice::<AuthResponse>(&body) {
+            Ok(auth_response) => {
+                self.breaker.record_success();
+                Ok(auth_response)
+            }
+            // The service is up but rejected the request
+            Err(_) if status.is_client_error() => {
+                self.breaker.record_success();
+                let body = String::from_utf8_lossy(&body);
+                warn!(status = %status, body = %body, "Auth service returned error");
+                Err(Error::internal(format!(
+                    "Auth service returned {}: {}",
+                    status, body
+                )))
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to parse auth response");
+                self.breaker.record_failure();
+                Err(Error::internal(format!("Invalid auth response: {}", e)))
+            }
+        }
+    }
+
+    /// Same breaker accounting as [`Self::validate`]; any error leaves the
+    /// fallback to [`AuthClient::features`](crate::state::AuthClient::features).
+    async fn features(&self, api_key: &ParsedApiKey) -> Result<Features, Error> {
+        if !self.breaker.allow() {
+            return Err(Error::auth(
+                AuthErrorCode::ServiceUnavailable,
+                "Auth service unavailable, retry later",
+            ));
+        }
+
+        let url = format!("{}/features", self.base_url);
+
+        let response = match self
+            .http_client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", api_key.as_str()))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(error = %e, "Features request failed");
+                self.breaker.record_failure();
+                return Err(Error::auth(
+                    AuthErrorCode::ServiceUnavailable,
+                    format!("Features service unavailable: {}", e),
+                ));
+            }
+        };
+
+        let status = response.status();
+        if status.is_server_error() {
+            warn!(status = %status, "Features endpoint returned error");
+            self.breaker.record_failure();
+            return Err(Error::auth(
+                AuthErrorCode::ServiceUnavailable,
+                format!("Features endpoint returned {}", status),
+            ));
+        }
+        if !status.is_success() {
+            // The service is up but rejected the request
+            self.breaker.record_success();
+            return Err(Error::internal(format!(
+                "Features endpoint returned {}",
+                status
+            )));
+        }
+
+        match response.json::<FeaturesResponse>().await {
+            Ok(features_response) => {
+                self.breaker.record_success();
+                Ok(features_response.features)
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to parse features response");
+                self.breaker.record_failure();
+                Err(Error::internal(format!("Invalid features response: {}", e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::serve;
+    use axum::http::StatusCode;
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A daemon answering every request with `status` and `body`, and a
+    /// count of the requests it received.
+    async fn stub(
+        status: StatusCode,
+        body: &'static str,
+    ) -> (DaemonAuthProvider, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let router = Router::new().fallback(move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                (status, body)
+            }
+        });
+        (DaemonAuthProvider::new(serve(router).await), hits)
+    }
+
+    fn key() -> ParsedApiKey {
+        ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap()
+    }
+
+    fn is_unavailable(result: &Result<impl Sized, Error>) -> bool {
+        matches!(
+            result,
+            Err(Error::Auth {
+                code: "AUTH_006",
+                ..
+            })
+        )
+    }
+
+    #[tokio::test]
+    async fn test_daemon_validate_responses() {
+        let (daemon, _) = stub(StatusCode::OK, r#"{"valid":true,"projectId":"proj"}"#).await;
+        let response = daemon.validate(&key()).await.unwrap();
+        assert!(response.valid);
+        assert_eq!(response.project_id.as_deref(), Some("proj"));
+
+        // Rejections come back as a parsed response, not an error
+        let body = r#"{"valid":false,"error":{"code":"AUTH_004","message":"revoked"}}"#;
+        let (daemon, _) = stub(StatusCode::UNAUTHORIZED, body).await;
+        assert!(!daemon.validate(&key()).await.unwrap().valid);
+
+        let (daemon, _) = stub(StatusCode::FORBIDDEN, "forbidden").await;
+        assert!(matches!(
+            daemon.validate(&key()).await,
+            Err(Error::Internal(_))
+        ));
+
+        let (daemon, _) = stub(StatusCode::OK, "not json").await;
+        assert!(matches!(
+            daemon.validate(&key()).await,
+            Err(Error::Internal(_))
+        ));
+
+        let (daemon, _) = stub(StatusCode::BAD_GATEWAY, "").await;
+        assert!(is_unavailable(&daemon.validate(&key()).await));
+    }
+
+    #[tokio::test]
+    async fn test_daemon_breaker_counts_only_service_failures() {
+        let (daemon, hits) = stub(StatusCode::SERVICE_UNAVAILABLE, "").await;
+        for _ in 0..AUTH_BREAKER_THRESHOLD {
+            assert!(is_unavailable(&daemon.validate(&key()).await));
+        }
+        // Open: fails fast for both calls without reaching the daemon
+        assert!(is_unavailable(&daemon.validate(&key()).await));
+        assert!(is_unavailable(&daemon.features(&key()).await));
+        assert_eq!(hits.load(Ordering::SeqCst), AUTH_BREAKER_THRESHOLD as usize);
+
+        // Client errors mean the service is up
+        let (daemon, hits) = stub(StatusCode::NOT_FOUND, "").await;
+        for _ in 0..=AUTH_BREAKER_THRESHOLD {
+            assert!(daemon.validate(&key()).await.is_err());
+        }
+        assert!(!daemon.breaker.is_open());
+        assert_eq!(
+            hits.load(Ordering::SeqCst),
+            AUTH_BREAKER_THRESHOLD as usize + 1
+        );
+
+        // An unparseable success body counts as a failure
+        let (daemon, _) = stub(StatusCode::OK, "<html>").await;
+        for _ in 0..AUTH_BREAKER_THRESHOLD {
+            assert!(daemon.validate(&key()).await.is_err());
+        }
+        assert!(daemon.breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_daemon_features_responses() {
+        let body = r#"{"features":{"location":true,"clicks":false},"projectId":"proj"}"#;
+        let (daemon, _) = stub(StatusCode::OK, body).await;
+        let features = daemon.features(&key()).await.unwrap();
+        assert!(features.location);
+        assert!(!features.clicks);
+        assert!(features.pageviews);
+
+        let (daemon, _) = stub(StatusCode::NOT_FOUND, "").await;
+        assert!(matches!(
+            daemon.features(&key()).await,
+            Err(Error::Internal(_))
+        ));
+
+        let (daemon, _) = stub(StatusCode::INTERNAL_SERVER_ERROR, "").await;
+        assert!(is_unavailable(&daemon.features(&key()).await));
+    }
+}
@@ -0,0 +1,130 @@
+//! Circuit breaker for calls to upstream services.
+//!
+//! After `failure_threshold` consecutive failures the breaker opens and
+//! calls fail fast for `open_for`. It then lets a single trial call through
+//! (half-open); success closes it again, failure re-opens it.
+
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+use telemetry::metrics;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// Trial call in flight; another is admitted after `open_for` in case
+    /// it was cancelled without reporting back
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+/// Consecutive-failure circuit breaker.
+pub struct CircuitBreaker {
+    name: &'static str,
+    failure_threshold: u32,
+    open_for: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            name,
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may be attempted now.
+    ///
+    /// Moves an expired open breaker to half-open and admits only that call.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } | BreakerState::HalfOpen { until } if now >= until => {
+                *state = BreakerState::HalfOpen {
+                    until: now + self.open_for,
+                };
+                true
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock();
+        if matches!(*state, BreakerState::HalfOpen { .. }) {
+            info!(breaker = self.name, "Circuit closed");
+        }
+        *state = BreakerState::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock();
+        let open = match *state {
+            BreakerState::Closed { failures } if failures + 1 < self.failure_threshold => {
+                *state = BreakerState::Closed {
+                    failures: failures + 1,
+                };
+                false
+            }
+            BreakerState::Closed { .. } | BreakerState::HalfOpen { .. } => true,
+            // Late failure from a call admitted before opening
+            BreakerState::Open { .. } => false,
+        };
+        if open {
+            warn!(
+                breaker = self.name,
+                open_for_secs = self.open_for.as_secs(),
+                "Circuit opened"
+            );
+            metrics().circuit_breaker_opened.inc();
+            *state = BreakerState::Open {
+                until: Instant::now() + self.open_for,
+            };
+        }
+    }
+
+    /// Whether calls are currently failing fast.
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.state.lock(), BreakerState::Closed { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold_and_recovers() {
+        let breaker = CircuitBreaker::new("test", 2, Duration::from_millis(20));
+
+        breaker.record_failure();
+        assert!(breaker.allow());
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        // One trial call, then fail fast until it reports back
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        breaker.record_failure();
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+    }
+}
@@ -2,10 +2,15 @@
 
 use axum::{
     async_trait,
-    extract::FromRequestParts,
-    http::{header, request::Parts},
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
 };
-use engine_core::{extract_api_key, ParsedApiKey};
+use engine_core::{
+    extract_api_key, Cidr, IpRules, KeyType, MauStatus, ParsedApiKey, RetentionTier, Scope,
+    SigningSecret,
+};
+use std::net::{IpAddr, SocketAddr};
+use tracing::debug;
 
 use crate::response::ApiError;
 use crate::state::AppState;
@@ -19,10 +24,43 @@ pub struct AuthContext {
     pub api_key: ParsedApiKey,
     /// Project ID from auth response
     pub project_id: String,
-    /// Rate limit (requests per minute)
+    /// Rate limit (events per second)
     pub rate_limit: u32,
     /// Allowed origins for CORS
     pub allowed_origins: Option<Vec<String>>,
+    /// Granted permissions (e.g. "ingest", "read", "admin")
+    pub permissions: Vec<String>,
+    /// Plan tier
+    pub tier: Option<RetentionTier>,
+    /// MAU status from the auth service
+    pub mau: Option<MauStatus>,
+    /// Browser or server key
+    pub key_type: KeyType,
+    /// Project secret for signed requests
+    pub signing_secret: Option<SigningSecret>,
+    /// Whether live server keys must sign their requests
+    pub require_signature: bool,
+    /// Project IP allowlist and denylist
+    pub ip_rules: IpRules,
+}
+
+impl AuthContext {
+    /// Check whether the key was granted `scope`.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        scope.granted_by(&self.permissions)
+    }
+
+    /// Reject keys without `scope` with `403 AUTH_005`.
+    pub fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden(format!(
+                "API key does not have {} scope",
+                scope
+            )))
+        }
+    }
 }
 
 #[async_trait]
@@ -48,44 +86,136 @@ impl FromRequestParts<AppState> for AuthContext {
 
         let project_id = auth_response.project_id()?.to_string();
 
+        let (ip_rules, invalid) = IpRules::parse(
+            auth_response.ip_allowlist.as_deref(),
+            auth_response.ip_denylist.as_deref(),
+        );
+        if !invalid.is_empty() {
+            debug!(project_id = %project_id, entries = ?invalid, "Ignoring invalid IP rule entries");
+        }
+
         Ok(AuthContext {
             api_key,
             project_id,
             rate_limit: auth_response.rate_limit_or_default(),
             allowed_origins: auth_response.allowed_origins.clone(),
+            permissions: auth_response.permissions.clone().unwrap_or_default(),
+            tier: auth_response.tier,
+            mau: auth_response.mau,
+            key_type: auth_response.key_type,
+            signing_secret: auth_response.signing_secret.clone(),
+            require_signature: auth_response.require_signature,
+            ip_rules,
         })
     }
 }
 
 /// Client IP address.
+///
+/// Forwarding headers are only believed when the peer is a trusted proxy
+/// (`AppState::trusted_proxies`); `None` when the server was started
+/// without connect info.
 #[derive(Debug, Clone)]
-pub struct ClientIp(pub Option<String>);
+pub struct ClientIp(pub Option<IpAddr>);
 
 #[async_trait]
-impl<S> FromRequestParts<S> for ClientIp
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<AppState> for ClientIp {
     type Rejection = std::convert::Infallible;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Try X-Forwarded-For first (for proxied requests)
-        if let Some(xff) = parts.headers.get("X-Forwarded-For") {
-            if let Ok(xff_str) = xff.to_str() {
-                // Take the first IP in the chain
-                if let Some(ip) = xff_str.split(',').next() {
-                    return Ok(ClientIp(Some(ip.trim().to_string())));
-                }
-            }
-        }
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
 
-        // Try X-Real-IP
-        if let Some(real_ip) = parts.headers.get("X-Real-IP") {
-            if let Ok(ip) = real_ip.to_str() {
-                return Ok(ClientIp(Some(ip.to_string())));
+        Ok(ClientIp(peer.map(|peer| {
+            resolve_client_ip(peer, &parts.headers, &state.trusted_proxies)
+        })))
+    }
+}
+
+/// Resolve the client behind `peer`.
+///
+/// `X-Forwarded-For` is walked from the right, skipping trusted proxies, so
+/// the result is the first hop no trusted proxy vouches for. Entries a
+/// client sent itself sit to the left of that hop and are ignored.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[Cidr]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|cidr| cidr.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let forwarded: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    if !forwarded.is_empty() {
+        let mut closest = peer;
+        for hop in forwarded.iter().rev() {
+            // A malformed hop means nothing further left can be trusted
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            closest = ip;
+            if !is_trusted(ip) {
+                break;
             }
         }
+        return closest;
+    }
+
+    headers
+        .get("X-Real-IP")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_core::ip::default_trusted_proxies;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn headers(xff: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", xff.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_resolve_client_ip() {
+        let trusted = default_trusted_proxies();
 
-        Ok(ClientIp(None))
+        // Untrusted peers cannot spoof their address
+        assert_eq!(
+            resolve_client_ip(ip("198.51.100.7"), &headers("203.0.113.9"), &trusted),
+            ip("198.51.100.7")
+        );
+        // Spoofed leftmost entry is skipped in favour of the hop the proxy saw
+        assert_eq!(
+            resolve_client_ip(
+                ip("10.0.0.2"),
+                &headers("1.1.1.1, 203.0.113.9, 10.0.0.3"),
+                &trusted
+            ),
+            ip("203.0.113.9")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), &HeaderMap::new(), &trusted),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), &headers("203.0.113.9"), &[]),
+            ip("10.0.0.2")
+        );
     }
 }
@@ -0,0 +1,346 @@
+//! Local key store, for running without the TS auth daemon.
+//!
+//! Tenants and API keys are read from a JSON file or from the
+//! `overwatch.tenants` / `overwatch.api_keys` tables and reloaded every
+//! `reload_interval_secs`. Keys are looked up by SHA-256 hash, so the store
+//! never holds raw keys. Lookups are answered from memory and not cached,
+//! so a revoked key stops working on the next reload.
+//!
+//! File format:
+//!
+//! ```json
+//! {
+//!   "tenants": [{
+//!     "id": "<uuid>", "name": "Acme", "tier": "paid",
+//!     "allowed_origins": ["https://*.acme.com"]
+//!   }],
+//!   "keys": [{ "key_hash": "<sha256 hex>", "tenant_id": "<uuid>", "name": "web" }]
+//! }
+//! ```
+
+use axum::async_trait;
+use chrono::Utc;
+use clickhouse_client::keys::{load_api_keys, load_tenants};
+use clickhouse_client::ClickHouseClient;
+use engine_core::{ApiKey, AuthResponse, Error, KeyStore, ParsedApiKey, Tenant};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+use crate::auth_provider::AuthProvider;
+use crate::state::Features;
+
+/// Where the key store is loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStoreSource {
+    /// JSON file at `path`
+    File,
+    /// `overwatch.tenants` and `overwatch.api_keys`
+    Clickhouse,
+}
+
+fn default_reload_interval_secs() -> u64 {
+    30
+}
+
+/// Local key store configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStoreConfig {
+    pub source: KeyStoreSource,
+    /// Key file, for `source = "file"`
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// How often to check for changes
+    #[serde(default = "default_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+impl KeyStoreConfig {
+    /// Load keys from a JSON file.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            source: KeyStoreSource::File,
+            path: Some(path.into()),
+            reload_interval_secs: default_reload_interval_secs(),
+        }
+    }
+}
+
+/// Contents of a key file.
+#[derive(Debug, Default, Deserialize)]
+struct KeyFile {
+    #[serde(default)]
+    tenants: Vec<Tenant>,
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+/// Key store backed by a file or ClickHouse.
+pub struct LocalKeyStore {
+    config: KeyStoreConfig,
+    clickhouse: Arc<ClickHouseClient>,
+    store: RwLock<Arc<KeyStore>>,
+    /// Modification time of the file last loaded
+    file_modified: Mutex<Option<SystemTime>>,
+}
+
+impl LocalKeyStore {
+    /// Load the store; fails if the initial load does.
+    pub async fn load(
+        config: KeyStoreConfig,
+        clickhouse: Arc<ClickHouseClient>,
+    ) -> Result<Self, Error> {
+        if config.source == KeyStoreSource::File && config.path.is_none() {
+            return Err(Error::validation(
+                "Key store source \"file\" requires a path",
+            ));
+        }
+
+        let store = Self {
+            config,
+            clickhouse,
+            store: RwLock::new(Arc::new(KeyStore::default())),
+            file_modified: Mutex::new(None),
+        };
+        store.reload().await?;
+        Ok(store)
+    }
+
+    /// Number of tenants and keys currently loaded.
+    pub fn counts(&self) -> (usize, usize) {
+        let store = self.store.read();
+        (store.tenant_count(), store.key_count())
+    }
+
+    /// Reload the store if its source changed.
+    ///
+    /// Returns whether it was replaced. On error the previous keys stay.
+    pub async fn reload(&self) -> Result<bool, Error> {
+        let loaded = match self.config.source {
+            KeyStoreSource::File => match self.read_file().await? {
+                Some(loaded) => loaded,
+                None => return Ok(false),
+            },
+            KeyStoreSource::Clickhouse => KeyStore::new(
+                load_tenants(&self.clickhouse).await?,
+                load_api_keys(&self.clickhouse).await?,
+            )?,
+        };
+
+        debug!(
+            tenants = loaded.tenant_count(),
+            keys = loaded.key_count(),
+            "Key store loaded"
+        );
+        *self.store.write() = Arc::new(loaded);
+        Ok(true)
+    }
+
+    /// Parse the key file, or `None` if it has not changed since the last load.
+    async fn read_file(&self) -> Result<Option<KeyStore>, Error> {
+        let path = self.config.path.as_ref().expect("checked in load");
+        let modified = tokio::fs::metadata(path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| Error::internal(format!("Key file {}: {}", path.display(), e)))?;
+        if *self.file_modified.lock() == Some(modified) {
+            return Ok(None);
+        }
+
+        let bytes = tokio::fs::read(path)
+            .await
+            .map_err(|e| Error::internal(format!("Key file {}: {}", path.display(), e)))?;
+        let file: KeyFile = serde_json::from_slice(&bytes)
+            .map_err(|e| Error::validation(format!("Key file {}: {}", path.display(), e)))?;
+        let loaded = KeyStore::new(file.tenants, file.keys)?;
+
+        *self.file_modified.lock() = Some(modified);
+        info!(path = %path.display(), "Key file changed, reloading");
+        Ok(Some(loaded))
+    }
+
+    /// Start the background task that picks up changes.
+    pub fn start_reload(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(self.config.reload_interval_secs.max(1)));
+            // The first tick completes immediately; the store is already loaded
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.reload().await {
+                    warn!(error = %e, "Key store reload failed, keeping previous keys");
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl AuthProvider for LocalKeyStore {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+        let store = self.store.read().clone();
+        Ok(store.authenticate(&api_key.hash(), Utc::now()))
+    }
+
+    /// The store has no per-project flags; everything but location is on.
+    async fn features(&self, _api_key: &ParsedApiKey) -> Result<Features, Error> {
+        Ok(Features::default())
+    }
+
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AuthClient;
+    use crate::test_util::{self, CountingProducer};
+    use clickhouse_client::ClickHouseConfig;
+
+    #[tokio::test]
+    async fn test_file_store_reloads_revocations() {
+        let raw = "owk_live_ABC123xyz789DEF456ghi012JKL345mn";
+        let key = ParsedApiKey::parse(raw).unwrap();
+        let tenant_id = uuid::Uuid::new_v4();
+        let write = |active: bool| {
+            serde_json::json!({
+                "tenants": [{ "id": tenant_id, "name": "Acme", "tier": "paid" }],
+                "keys": [{
+                    "key_hash": key.hash(),
+                    "tenant_id": tenant_id,
+                    "name": "web",
+                    "active": active,
+                }],
+            })
+            .to_string()
+        };
+
+        let path = std::env::temp_dir().join(format!("keys-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, write(true)).unwrap();
+        let clickhouse = Arc::new(ClickHouseClient::new(ClickHouseConfig::default()).unwrap());
+        let store = Arc::new(
+            LocalKeyStore::load(KeyStoreConfig::file(&path), clickhouse)
+                .await
+                .unwrap(),
+        );
+        let client = AuthClient::with_provider(store.clone());
+
+        let response = client.validate(&key).await.unwrap();
+        assert_eq!(response.project_id().unwrap(), tenant_id.to_string());
+
+        std::fs::write(&path, write(false)).unwrap();
+        // Modification times can be coarse; force the reload
+        *store.file_modified.lock() = None;
+        assert!(store.reload().await.unwrap());
+
+        let response = client.validate(&key).await.unwrap();
+        assert!(matches!(
+            response.project_id(),
+            Err(Error::Auth {
+                code: "AUTH_004",
+                ..
+            })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_browser_keys_cannot_read_by_default() {
+        let raw = "owk_live_ABC123xyz789DEF456ghi012JKL345mn";
+        let tenant_id = uuid::Uuid::new_v4();
+        let file = serde_json::json!({
+            "tenants": [{ "id": tenant_id, "name": "Acme" }],
+            "keys": [{
+                "key_hash": ParsedApiKey::parse(raw).unwrap().hash(),
+                "tenant_id": tenant_id,
+                "name": "web",
+            }],
+        });
+
+        let path = std::env::temp_dir().join(format!("keys-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, file.to_string()).unwrap();
+        let clickhouse = Arc::new(ClickHouseClient::new(ClickHouseConfig::default()).unwrap());
+        let store = LocalKeyStore::load(KeyStoreConfig::file(&path), clickhouse)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let state = test_util::state(Arc::new(CountingProducer::default()))
+            .with_auth_provider(Arc::new(store));
+        let base = test_util::serve(crate::routes::router(state)).await;
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/analytics/timeseries?from=0&to=1000", base))
+            .header("X-API-Key", raw)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "AUTH_005");
+    }
+
+    #[tokio::test]
+    async fn test_tenant_origins_restrict_browser_keys() {
+        let raw = "owk_live_ABC123xyz789DEF456ghi012JKL345mn";
+        let tenant_id = uuid::Uuid::new_v4();
+        let file = serde_json::json!({
+            "tenants": [{
+                "id": tenant_id,
+                "name": "Acme",
+                "allowed_origins": ["https://shop.example"],
+            }],
+            "keys": [{
+                "key_hash": ParsedApiKey::parse(raw).unwrap().hash(),
+                "tenant_id": tenant_id,
+                "name": "web",
+            }],
+        });
+
+        let path = std::env::temp_dir().join(format!("keys-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, file.to_string()).unwrap();
+        let clickhouse = Arc::new(ClickHouseClient::new(ClickHouseConfig::default()).unwrap());
+        let store = LocalKeyStore::load(KeyStoreConfig::file(&path), clickhouse)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let key = ParsedApiKey::parse(raw).unwrap();
+        let response = store.validate(&key).await.unwrap();
+        assert_eq!(
+            response.allowed_origins,
+            Some(vec!["https://shop.example".to_string()])
+        );
+
+        let state = test_util::state(Arc::new(CountingProducer::default()))
+            .with_auth_provider(Arc::new(store));
+        let base = test_util::serve(crate::routes::router(state)).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/overwatch-ingest", base))
+            .header("X-API-Key", raw)
+            .header("Origin", "https://evil.example")
+            .json(&serde_json::json!({ "events": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("https://evil.example is not allowed"));
+    }
+}
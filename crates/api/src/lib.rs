@@ -1,10 +1,18 @@
 //! HTTP API layer for the ingestion engine.
 
+pub mod auth_provider;
+pub mod circuit_breaker;
 pub mod extractors;
+pub mod key_store;
+pub mod mau;
 pub mod middleware;
 pub mod response;
 pub mod routes;
+pub mod signing;
 pub mod state;
 
+#[cfg(test)]
+mod test_util;
+
 pub use routes::router;
 pub use state::AppState;
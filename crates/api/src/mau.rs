@@ -0,0 +1,334 @@
+//! Per-project MAU tracking.
+//!
+//! Estimates each project's distinct users for the current month from
+//! ingested events (`user_id`, else `session_id`) and warns as the estimate
+//! approaches the plan limit. Over-limit events are counted per action and
+//! flushed to `overwatch.mau_overages` for billing.
+//!
+//! The local estimate only covers traffic seen by this replica since it
+//! started, so it is combined with the distinct users already stored in
+//! `overwatch.mau_users`, refreshed on every flush. The larger of the two
+//! wins: stored users cover restarts and other replicas, the local estimate
+//! covers events not yet written.
+
+use chrono::{Datelike, Utc};
+use clickhouse_client::mau::{record_overages, stored_users, MauOverage};
+use clickhouse_client::ClickHouseClient;
+use engine_core::{ClickHouseEvent, HyperLogLog, MauConfig, OverLimitAction};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use telemetry::metrics;
+use tracing::{error, warn};
+
+/// How often overage counts are written to ClickHouse.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Current month as `YYYY-MM-01`.
+fn current_month() -> String {
+    let now = Utc::now();
+    format!("{:04}-{:02}-01", now.year(), now.month())
+}
+
+/// One project's users this month.
+struct ProjectMonth {
+    month: String,
+    users: HyperLogLog,
+    /// Distinct users stored in ClickHouse at the last refresh
+    stored: u64,
+    warned_approaching: bool,
+    warned_over: bool,
+}
+
+impl ProjectMonth {
+    fn new(month: String) -> Self {
+        Self {
+            month,
+            users: HyperLogLog::new(),
+            stored: 0,
+            warned_approaching: false,
+            warned_over: false,
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        self.users.estimate().max(self.stored)
+    }
+}
+
+/// Over-limit counts not yet flushed.
+#[derive(Default)]
+struct PendingOverage {
+    events: u64,
+    sampled_out: u64,
+}
+
+/// MAU estimates and overage accounting.
+pub struct MauTracker {
+    config: MauConfig,
+    projects: Mutex<HashMap<String, ProjectMonth>>,
+    /// (project, month, action) -> counts
+    overages: Mutex<HashMap<(String, String, OverLimitAction), PendingOverage>>,
+}
+
+impl MauTracker {
+    pub fn new(config: MauConfig) -> Self {
+        Self {
+            config,
+            projects: Mutex::new(HashMap::new()),
+            overages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &MauConfig {
+        &self.config
+    }
+
+    /// Add a batch's users to the project's estimate and return it.
+    ///
+    /// Logs once per month when the estimate reaches `warn_ratio` of `limit`
+    /// and again when it passes the limit.
+    pub fn observe(&self, project_id: &str, events: &[ClickHouseEvent], limit: Option<u32>) -> u64 {
+        let month = current_month();
+        let mut projects = self.projects.lock();
+        let project = projects
+            .entry(project_id.to_string())
+            .or_insert_with(|| ProjectMonth::new(month.clone()));
+        if project.month != month {
+            *project = ProjectMonth::new(month);
+        }
+
+        for event in events {
+            project
+                .users
+                .insert(event.user_id.as_deref().unwrap_or(&event.session_id));
+        }
+        let estimate = project.estimate();
+
+        let Some(limit) = limit.filter(|l| *l > 0) else {
+            return estimate;
+        };
+        if estimate >= limit as u64 && !project.warned_over {
+            project.warned_over = true;
+            metrics().mau_limit_warnings.inc();
+            warn!(
+                project_id = %project_id,
+                estimate = estimate,
+                limit = limit,
+                "Estimated MAU is over the plan limit"
+            );
+        } else if estimate as f64 >= limit as f64 * self.config.warn_ratio
+            && !project.warned_approaching
+        {
+            project.warned_approaching = true;
+            metrics().mau_limit_warnings.inc();
+            warn!(
+                project_id = %project_id,
+                estimate = estimate,
+                limit = limit,
+                "Estimated MAU is approaching the plan limit"
+            );
+        }
+        estimate
+    }
+
+    /// Count events accepted while over the limit.
+    pub fn record_overage(
+        &self,
+        project_id: &str,
+        action: OverLimitAction,
+        events: usize,
+        sampled_out: usize,
+    ) {
+        let mut overages = self.overages.lock();
+        let pending = overages
+            .entry((project_id.to_string(), current_month(), action))
+            .or_default();
+        pending.events += events as u64;
+        pending.sampled_out += sampled_out as u64;
+    }
+
+    /// Drain pending overages, with this month's estimate where known.
+    fn take_overages(&self) -> Vec<MauOverage> {
+        let overages = std::mem::take(&mut *self.overages.lock());
+        let projects = self.projects.lock();
+        overages
+            .into_iter()
+            .map(|((project_id, month, action), pending)| {
+                let estimated_users = projects
+                    .get(&project_id)
+                    .filter(|p| p.month == month)
+                    .map(|p| p.estimate())
+                    .unwrap_or(0);
+                MauOverage {
+                    project_id,
+                    month,
+                    action: action.as_str().to_string(),
+                    events: pending.events,
+                    sampled_out: pending.sampled_out,
+                    estimated_users,
+                }
+            })
+            .collect()
+    }
+
+    /// Put overages back after a failed flush, adding to any recorded since.
+    fn restore_overages(&self, overages: Vec<MauOverage>) {
+        let mut pending = self.overages.lock();
+        for overage in overages {
+            let Some(action) = [
+                OverLimitAction::Reject,
+                OverLimitAction::Sample,
+                OverLimitAction::Flag,
+            ]
+            .into_iter()
+            .find(|a| a.as_str() == overage.action) else {
+                continue;
+            };
+            let entry = pending
+                .entry((overage.project_id, overage.month, action))
+                .or_default();
+            entry.events += overage.events;
+            entry.sampled_out += overage.sampled_out;
+        }
+    }
+
+    /// Set this month's stored user counts.
+    fn apply_stored(&self, month: &str, stored: impl IntoIterator<Item = (String, u64)>) {
+        let mut projects = self.projects.lock();
+        for (project_id, users) in stored {
+            let project = projects
+                .entry(project_id)
+                .or_insert_with(|| ProjectMonth::new(month.to_string()));
+            if project.month == month {
+                project.stored = users;
+            }
+        }
+    }
+
+    /// Drop estimates from previous months.
+    fn prune(&self) {
+        let month = current_month();
+        self.projects.lock().retain(|_, p| p.month == month);
+    }
+
+    /// Start the background task that writes overages to ClickHouse.
+    pub fn start_flush(
+        self: Arc<Self>,
+        clickhouse: Arc<ClickHouseClient>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.prune();
+
+                let month = current_month();
+                match stored_users(&clickhouse, &month).await {
+                    Ok(rows) => self.apply_stored(
+                        &month,
+                        rows.into_iter().map(|row| (row.project_id, row.users)),
+                    ),
+                    Err(e) => warn!("Failed to read stored MAU: {}", e),
+                }
+
+                let overages = self.take_overages();
+                if overages.is_empty() {
+                    continue;
+                }
+                if let Err(e) = record_overages(&clickhouse, &overages).await {
+                    error!(
+                        projects = overages.len(),
+                        "Failed to record MAU overages, retrying next flush: {}", e
+                    );
+                    self.restore_overages(overages);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(session_id: &str, user_id: Option<&str>) -> ClickHouseEvent {
+        ClickHouseEvent {
+            event_id: "e".to_string(),
+            project_id: "proj".to_string(),
+            session_id: session_id.to_string(),
+            user_id: user_id.map(str::to_string),
+            event_type: "pageview".to_string(),
+            custom_name: None,
+            timestamp: 0,
+            url: String::new(),
+            path: String::new(),
+            referrer: String::new(),
+            user_agent: String::new(),
+            device_type: String::new(),
+            browser: String::new(),
+            browser_version: String::new(),
+            os: String::new(),
+            country: String::new(),
+            region: None,
+            city: None,
+            data: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_observe_counts_users_then_sessions() {
+        let tracker = MauTracker::new(MauConfig::default());
+        let events = vec![
+            event("s1", Some("alice")),
+            event("s2", Some("alice")),
+            event("s3", None),
+        ];
+        assert_eq!(tracker.observe("proj", &events, Some(10)), 2);
+        assert_eq!(tracker.observe("other", &events[..1], None), 1);
+    }
+
+    #[test]
+    fn test_overages_are_drained_with_estimate() {
+        let tracker = MauTracker::new(MauConfig::default());
+        tracker.observe("proj", &[event("s1", None)], Some(1));
+        tracker.record_overage("proj", OverLimitAction::Sample, 10, 9);
+        tracker.record_overage("proj", OverLimitAction::Sample, 5, 4);
+
+        let overages = tracker.take_overages();
+        assert_eq!(overages.len(), 1);
+        assert_eq!(overages[0].action, "sample");
+        assert_eq!(overages[0].events, 15);
+        assert_eq!(overages[0].sampled_out, 13);
+        assert_eq!(overages[0].estimated_users, 1);
+        assert!(tracker.take_overages().is_empty());
+    }
+
+    #[test]
+    fn test_failed_flush_restores_overages() {
+        let tracker = MauTracker::new(MauConfig::default());
+        tracker.record_overage("proj", OverLimitAction::Flag, 10, 0);
+        let failed = tracker.take_overages();
+
+        tracker.record_overage("proj", OverLimitAction::Flag, 5, 0);
+        tracker.restore_overages(failed);
+
+        let overages = tracker.take_overages();
+        assert_eq!(overages.len(), 1);
+        assert_eq!(overages[0].action, "flag");
+        assert_eq!(overages[0].events, 15);
+    }
+
+    #[test]
+    fn test_stored_users_raise_estimate() {
+        let tracker = MauTracker::new(MauConfig::default());
+        let month = current_month();
+        tracker.apply_stored(&month, [("proj".to_string(), 40)]);
+        assert_eq!(tracker.observe("proj", &[event("s1", None)], None), 40);
+
+        // The local estimate wins once it passes the stored count
+        tracker.apply_stored(&month, [("proj".to_string(), 0)]);
+        assert_eq!(tracker.observe("proj", &[event("s2", None)], None), 2);
+    }
+}
@@ -0,0 +1,304 @@
+//! Per-project CORS.
+//!
+//! Browser requests (those with an `Origin` header) are checked against the
+//! project's `allowed_origins` from the auth response. Patterns are exact
+//! origins (`https://example.com`), subdomain wildcards
+//! (`https://*.example.com`, which does not match the apex) or `*`. A
+//! project without `allowed_origins` accepts any origin, and server keys are
+//! never checked.
+//!
+//! Preflights carry no credentials and are answered from the origin alone:
+//! they always succeed, and the actual request is checked. Keys are never
+//! read from the query string, where they would end up in access logs.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use engine_core::{extract_api_key, KeyType, ParsedApiKey};
+use tracing::debug;
+
+use crate::response::ApiError;
+use crate::state::AppState;
+
+/// Headers browsers may send.
+const ALLOW_HEADERS: &str = "authorization, content-type, x-api-key";
+
+/// Headers scripts may read from responses.
+const EXPOSE_HEADERS: &str =
+    "retry-after, x-ratelimit-limit, x-ratelimit-remaining, x-ratelimit-reset";
+
+/// How long browsers may cache a preflight (seconds).
+const PREFLIGHT_MAX_AGE: &str = "600";
+
+/// Whether `origin` matches one of `patterns`.
+pub fn origin_allowed(patterns: &[String], origin: &str) -> bool {
+    let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim().trim_end_matches('/').to_ascii_lowercase();
+        if pattern == "*" || pattern == origin {
+            return true;
+        }
+        // https://*.example.com matches any subdomain, with the same scheme and port
+        match pattern.split_once("://*.") {
+            Some((scheme, suffix)) => origin
+                .strip_prefix(scheme)
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(suffix))
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => false,
+        }
+    })
+}
+
+/// API key from the request headers.
+fn request_key(request: &Request) -> Option<ParsedApiKey> {
+    let headers = request.headers();
+    let auth_header = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok());
+    let api_key_header = headers.get("X-API-Key").and_then(|h| h.to_str().ok());
+    if auth_header.is_none() && api_key_header.is_none() {
+        return None;
+    }
+    extract_api_key(auth_header, api_key_header).ok()
+}
+
+/// Whether the request's key may be used from `origin`.
+///
+/// Unknown or invalid keys are let through; the handler rejects them.
+async fn check_origin(state: &AppState, key: Option<ParsedApiKey>, origin: &str) -> bool {
+    let Some(key) = key else {
+        return true;
+    };
+    let Ok(auth) = state.auth_client.validate(&key).await else {
+        return true;
+    };
+    if !auth.valid || auth.key_type == KeyType::Server {
+        return true;
+    }
+    match auth.allowed_origins.as_deref() {
+        Some(patterns) if !patterns.is_empty() => origin_allowed(patterns, origin),
+        _ => true,
+    }
+}
+
+/// Echo the allowed origin.
+fn allow_origin(headers: &mut HeaderMap, origin: HeaderValue) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+}
+
+/// Per-project CORS middleware.
+pub async fn cors_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Not a browser request
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let origin_str = origin.to_str().unwrap_or_default().to_string();
+
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    if preflight {
+        let mut response = axum::http::StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        allow_origin(headers, origin);
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, POST, OPTIONS"),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            HeaderValue::from_static(ALLOW_HEADERS),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from_static(PREFLIGHT_MAX_AGE),
+        );
+        return response;
+    }
+
+    let key = request_key(&request);
+    if !check_origin(&state, key, &origin_str).await {
+        debug!(origin = %origin_str, path = %request.uri().path(), "Origin not allowed");
+        return ApiError::forbidden(format!(
+            "Origin {} is not allowed for this project",
+            origin_str
+        ))
+        .into_response();
+    }
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    allow_origin(headers, origin);
+    headers.insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_static(EXPOSE_HEADERS),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_provider::{AuthProvider, MockAuthProvider};
+    use crate::state::Features;
+    use crate::test_util::{self, CountingProducer};
+    use axum::{middleware, routing::post, Router};
+    use engine_core::{AuthResponse, Error};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const BROWSER_KEY: &str = "owk_live_ABC123xyz789DEF456ghi012JKL345mn";
+    const SERVER_KEY: &str = "owk_test_ABC123xyz789DEF456ghi012JKL345mn";
+    const SHOP: &str = "https://shop.example";
+    const EVIL: &str = "https://evil.example";
+
+    /// Restricts every project to `SHOP`; test keys are server keys.
+    #[derive(Default)]
+    struct ShopProvider {
+        lookups: AtomicUsize,
+    }
+
+    #[axum::async_trait]
+    impl AuthProvider for ShopProvider {
+        fn name(&self) -> &'static str {
+            "shop"
+        }
+
+        async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            let mut auth = MockAuthProvider.validate(api_key).await?;
+            auth.allowed_origins = Some(vec![SHOP.to_string()]);
+            if api_key.is_test() {
+                auth.key_type = KeyType::Server;
+            }
+            Ok(auth)
+        }
+
+        async fn features(&self, _api_key: &ParsedApiKey) -> Result<Features, Error> {
+            Ok(Features::default())
+        }
+    }
+
+    async fn serve(provider: Arc<ShopProvider>) -> String {
+        let state =
+            test_util::state(Arc::new(CountingProducer::default())).with_auth_provider(provider);
+        let router = Router::new()
+            .route("/v1/ingest", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                cors_middleware,
+            ))
+            .with_state(state);
+        test_util::serve(router).await
+    }
+
+    fn preflight(base: &str, path: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(Method::OPTIONS, format!("{}{}", base, path))
+            .header(header::ORIGIN, EVIL)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+    }
+
+    fn ingest(base: &str, origin: &str, key: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .post(format!("{}/v1/ingest", base))
+            .header(header::ORIGIN, origin)
+            .header("X-API-Key", key)
+    }
+
+    #[tokio::test]
+    async fn test_preflight_ignores_keys() {
+        let provider = Arc::new(ShopProvider::default());
+        let base = serve(provider.clone()).await;
+
+        for path in ["/v1/ingest", &format!("/v1/ingest?key={}", BROWSER_KEY)] {
+            let response = preflight(&base, path).send().await.unwrap();
+            assert_eq!(response.status(), 204);
+            let headers = response.headers();
+            assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], EVIL);
+            assert_eq!(
+                headers[header::ACCESS_CONTROL_ALLOW_METHODS],
+                "GET, POST, OPTIONS"
+            );
+            assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_HEADERS], ALLOW_HEADERS);
+            assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], PREFLIGHT_MAX_AGE);
+            assert_eq!(headers[header::VARY], "origin");
+        }
+        assert_eq!(provider.lookups.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin_is_forbidden() {
+        let base = serve(Arc::new(ShopProvider::default())).await;
+
+        let response = ingest(&base, EVIL, BROWSER_KEY).send().await.unwrap();
+        assert_eq!(response.status(), 403);
+        assert!(response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "AUTH_005");
+    }
+
+    #[tokio::test]
+    async fn test_allowed_origin_gets_cors_headers() {
+        let base = serve(Arc::new(ShopProvider::default())).await;
+
+        let response = ingest(&base, SHOP, BROWSER_KEY).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], SHOP);
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+            EXPOSE_HEADERS
+        );
+        assert_eq!(headers[header::VARY], "origin");
+    }
+
+    #[tokio::test]
+    async fn test_server_keys_skip_origin_check() {
+        let base = serve(Arc::new(ShopProvider::default())).await;
+
+        let response = ingest(&base, EVIL, SERVER_KEY).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            EVIL
+        );
+    }
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exact_and_wildcard_origins() {
+        let allowed = patterns(&["https://example.com/", "https://*.shop.io"]);
+
+        assert!(origin_allowed(&allowed, "https://example.com"));
+        assert!(origin_allowed(&allowed, "https://EXAMPLE.com"));
+        assert!(!origin_allowed(&allowed, "http://example.com"));
+        assert!(!origin_allowed(&allowed, "https://example.com.evil.io"));
+
+        assert!(origin_allowed(&allowed, "https://a.shop.io"));
+        assert!(origin_allowed(&allowed, "https://a.b.shop.io"));
+        assert!(!origin_allowed(&allowed, "https://shop.io"));
+        assert!(!origin_allowed(&allowed, "https://evilshop.io"));
+        assert!(!origin_allowed(&allowed, "http://a.shop.io"));
+        assert!(!origin_allowed(&allowed, "https://a.shop.io:8443"));
+
+        assert!(origin_allowed(&patterns(&["*"]), "https://anything.dev"));
+    }
+}
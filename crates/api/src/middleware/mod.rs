@@ -1,7 +1,10 @@
 //! API middleware.
 
 pub mod auth;
+pub mod cors;
 pub mod rate_limit;
+pub mod rate_limit_cluster;
 
 pub use auth::*;
 pub use rate_limit::*;
+pub use rate_limit_cluster::{ClusterBackend, ClusterConfig};
@@ -1,5 +1,16 @@
 //! Rate limiting middleware.
+//!
+//! Token buckets keyed by project. Ingestion charges one token per event
+//! against the project's own limit (events per second, from the auth
+//! response), with a burst of [`BURST_SECONDS`] worth of events.
+//!
+//! Buckets live in a [`RateLimitBackend`]. [`InMemoryBackend`] keeps them in
+//! process memory, which is per replica; see
+//! [`rate_limit_cluster`](super::rate_limit_cluster) for a backend that shares
+//! usage between replicas.
 
+use axum::async_trait;
+use axum::http::{HeaderMap, HeaderValue};
 use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -13,13 +24,10 @@ const MAX_BUCKETS: usize = 10_000;
 /// Default max age for stale buckets (1 hour).
 const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
 
-/// Token bucket rate limiter with bounded memory.
-pub struct RateLimiter {
-    buckets: Mutex<HashMap<String, TokenBucket>>,
-    config: RateLimitConfig,
-}
+/// Seconds of sustained rate a bucket can hold.
+pub const BURST_SECONDS: u32 = 5;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct RateLimitConfig {
     /// Requests per second
     pub rate: u32,
@@ -36,6 +44,31 @@ impl Default for RateLimitConfig {
     }
 }
 
+/// Outcome of charging a bucket, reported in `X-RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    /// Sustained limit (tokens per second)
+    pub limit: u32,
+    /// Whole tokens left after this charge
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_secs: u64,
+    /// Seconds until this charge would succeed (0 when allowed)
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitStatus {
+    /// `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`.
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Limit", HeaderValue::from(self.limit));
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from(self.remaining));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from(self.reset_secs));
+        headers
+    }
+}
+
 struct TokenBucket {
     tokens: f64,
     last_update: Instant,
@@ -49,37 +82,104 @@ impl TokenBucket {
         }
     }
 
-    fn try_acquire(&mut self, rate: u32, burst: u32) -> bool {
+    /// Add the tokens earned since the last update.
+    fn refill(&mut self, rate: u32, burst: u32) {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_update).as_secs_f64();
         self.last_update = now;
-
-        // Replenish tokens
         self.tokens = (self.tokens + elapsed * rate as f64).min(burst as f64);
+    }
 
-        // Try to consume a token
-        if self.tokens >= 1.0 {
-            self.tokens -= 1.0;
-            true
+    /// Charge `cost` tokens.
+    ///
+    /// A charge larger than the burst is allowed once the bucket is full and
+    /// leaves it in debt, so oversized batches are slowed rather than
+    /// rejected forever.
+    fn try_acquire(&mut self, cost: u32, rate: u32, burst: u32) -> RateLimitStatus {
+        let rate = rate.max(1);
+        let burst = burst.max(1);
+        self.refill(rate, burst);
+
+        // Try to consume the tokens
+        let required = cost.min(burst) as f64;
+        let allowed = self.tokens >= required;
+        if allowed {
+            self.tokens -= cost as f64;
+        }
+
+        let retry_after_secs = if allowed {
+            0
         } else {
-            false
+            ((required - self.tokens) / rate as f64).ceil().max(1.0) as u64
+        };
+
+        RateLimitStatus {
+            allowed,
+            limit: rate,
+            remaining: self.tokens.max(0.0).floor() as u32,
+            reset_secs: ((burst as f64 - self.tokens) / rate as f64).ceil().max(0.0) as u64,
+            retry_after_secs,
         }
     }
+
+    /// Charge `cost` tokens unconditionally, going into debt if needed.
+    fn debit(&mut self, cost: u64, rate: u32, burst: u32) {
+        self.refill(rate.max(1), burst.max(1));
+        self.tokens -= cost as f64;
+    }
 }
 
-impl RateLimiter {
-    pub fn new(config: RateLimitConfig) -> Self {
+/// Storage for token buckets.
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    /// Charge `cost` tokens from the key's bucket.
+    async fn acquire(&self, key: &str, cost: u32, rate: u32, burst: u32) -> RateLimitStatus;
+
+    /// Drop buckets not used within `max_age`.
+    async fn cleanup(&self, max_age: Duration);
+
+    /// Number of buckets held by this instance.
+    fn bucket_count(&self) -> usize;
+}
+
+/// Buckets in process memory, bounded to [`MAX_BUCKETS`].
+pub struct InMemoryBackend {
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl Default for InMemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
         Self {
             buckets: Mutex::new(HashMap::with_capacity(1000)),
-            config,
         }
     }
 
-    /// Check if request is allowed for the given key.
+    /// Charge `cost` tokens from the key's bucket.
     /// Automatically evicts stale entries when at capacity.
-    pub fn check(&self, key: &str) -> bool {
+    pub fn charge(&self, key: &str, cost: u32, rate: u32, burst: u32) -> RateLimitStatus {
+        let mut buckets = self.buckets.lock();
+        Self::bucket(&mut buckets, key, burst).try_acquire(cost, rate, burst)
+    }
+
+    /// Record usage that was already admitted elsewhere (e.g. by another
+    /// replica), so it counts against this bucket too.
+    pub fn debit(&self, key: &str, cost: u64, rate: u32, burst: u32) {
         let mut buckets = self.buckets.lock();
+        Self::bucket(&mut buckets, key, burst).debit(cost, rate, burst);
+    }
 
+    /// The key's bucket, created full if missing.
+    fn bucket<'a>(
+        buckets: &'a mut HashMap<String, TokenBucket>,
+        key: &str,
+        burst: u32,
+    ) -> &'a mut TokenBucket {
         // If at capacity and key doesn't exist, evict stale entries first
         if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
             let now = Instant::now();
@@ -118,15 +218,13 @@ impl RateLimiter {
             }
         }
 
-        let bucket = buckets
+        buckets
             .entry(key.to_string())
-            .or_insert_with(|| TokenBucket::new(self.config.burst));
-
-        bucket.try_acquire(self.config.rate, self.config.burst)
+            .or_insert_with(|| TokenBucket::new(burst))
     }
 
     /// Clean up stale buckets older than max_age.
-    pub fn cleanup(&self, max_age: Duration) {
+    pub fn remove_stale(&self, max_age: Duration) {
         let mut buckets = self.buckets.lock();
         let now = Instant::now();
         let before = buckets.len();
@@ -143,16 +241,133 @@ impl RateLimiter {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.buckets.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for InMemoryBackend {
+    async fn acquire(&self, key: &str, cost: u32, rate: u32, burst: u32) -> RateLimitStatus {
+        self.charge(key, cost, rate, burst)
+    }
+
+    async fn cleanup(&self, max_age: Duration) {
+        self.remove_stale(max_age);
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Token bucket rate limiter over a pluggable backend.
+pub struct RateLimiter {
+    backend: Arc<dyn RateLimitBackend>,
+    config: RateLimitConfig,
+}
+
+impl RateLimiter {
+    /// Limiter with per-process buckets.
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self::with_backend(config, Arc::new(InMemoryBackend::new()))
+    }
+
+    pub fn with_backend(config: RateLimitConfig, backend: Arc<dyn RateLimitBackend>) -> Self {
+        Self { backend, config }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Check if request is allowed for the given key, using the global config.
+    pub async fn check(&self, key: &str) -> bool {
+        self.backend
+            .acquire(key, 1, self.config.rate, self.config.burst)
+            .await
+            .allowed
+    }
+
+    /// Charge `events` tokens against a project's own limit (events per second).
+    ///
+    /// Empty requests still cost one token.
+    pub async fn check_events(&self, key: &str, rate: u32, events: usize) -> RateLimitStatus {
+        let cost = u32::try_from(events).unwrap_or(u32::MAX).max(1);
+        self.backend
+            .acquire(key, cost, rate, rate.saturating_mul(BURST_SECONDS))
+            .await
+    }
+
+    /// Clean up stale buckets older than max_age.
+    pub async fn cleanup(&self, max_age: Duration) {
+        self.backend.cleanup(max_age).await;
+    }
+
     /// Clean up stale buckets using default max age.
-    pub fn cleanup_stale(&self) {
-        self.cleanup(DEFAULT_MAX_AGE);
+    pub async fn cleanup_stale(&self) {
+        self.cleanup(DEFAULT_MAX_AGE).await;
     }
 
     /// Get the number of active buckets.
     pub fn bucket_count(&self) -> usize {
-        self.buckets.lock().len()
+        self.backend.bucket_count()
     }
 }
 
 /// Shared rate limiter state.
 pub type SharedRateLimiter = Arc<RateLimiter>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_events_charges_per_event() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+
+        // 10/s with a 50 event burst
+        let status = limiter.check_events("proj", 10, 30).await;
+        assert!(status.allowed);
+        assert_eq!(status.limit, 10);
+        assert_eq!(status.remaining, 20);
+        assert_eq!(status.reset_secs, 3);
+
+        let status = limiter.check_events("proj", 10, 30).await;
+        assert!(!status.allowed);
+        assert_eq!(status.remaining, 20);
+        assert_eq!(status.retry_after_secs, 1);
+
+        // Other projects have their own bucket
+        assert!(limiter.check_events("other", 10, 50).await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch_goes_into_debt() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+
+        // Larger than the burst, but the bucket is full
+        let status = limiter.check_events("proj", 10, 80).await;
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.reset_secs, 8);
+
+        // The debt has to be paid back before the next event
+        let status = limiter.check_events("proj", 10, 1).await;
+        assert!(!status.allowed);
+        assert_eq!(status.retry_after_secs, 4);
+    }
+
+    #[tokio::test]
+    async fn test_headers() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+        let headers = limiter.check_events("proj", 100, 1).await.headers();
+        assert_eq!(headers["X-RateLimit-Limit"], "100");
+        assert_eq!(headers["X-RateLimit-Remaining"], "499");
+        assert_eq!(headers["X-RateLimit-Reset"], "1");
+    }
+}
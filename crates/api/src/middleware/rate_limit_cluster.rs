@@ -0,0 +1,367 @@
+//! Cluster-wide rate limiting.
+//!
+//! Every replica keeps its own token buckets, but also reports the events it
+//! admitted to its peers every `sync_interval_ms`. Peers debit that usage
+//! from their own buckets, so each bucket tracks the project's consumption
+//! across the whole cluster and a project gets its limit once, not once per
+//! replica.
+//!
+//! Usage admitted between two syncs is not yet visible to the other
+//! replicas, so a project can briefly exceed its limit by up to one sync
+//! interval of traffic per replica.
+//!
+//! Reports also carry the nonces of signed requests accepted since the last
+//! sync, so replays are refused cluster-wide ([`crate::signing`]).
+
+use super::rate_limit::{InMemoryBackend, RateLimitBackend, RateLimitStatus};
+use crate::signing::SeenNonce;
+use axum::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+/// Header carrying the shared cluster secret on sync requests.
+pub const CLUSTER_SECRET_HEADER: &str = "X-Overwatch-Cluster-Secret";
+
+/// Path peers accept usage reports on.
+pub const SYNC_PATH: &str = "/internal/rate-limit/sync";
+
+/// Shortest cluster secret accepted at startup.
+pub const MIN_SECRET_LEN: usize = 16;
+
+fn default_peer_port() -> u16 {
+    8080
+}
+
+fn default_sync_interval_ms() -> u64 {
+    500
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// Peer discovery and sync settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Static peer base URLs (e.g. `http://10.0.0.2:8080`)
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// Hostname resolving to every replica (e.g. `<app>.internal` on fly.io)
+    #[serde(default)]
+    pub peer_dns: Option<String>,
+    /// Port peers found through `peer_dns` listen on
+    #[serde(default = "default_peer_port")]
+    pub peer_port: u16,
+    /// Shared secret sent in [`CLUSTER_SECRET_HEADER`]
+    pub secret: String,
+    /// How often usage is pushed to peers
+    #[serde(default = "default_sync_interval_ms")]
+    pub sync_interval_ms: u64,
+    /// Per-peer request timeout
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl ClusterConfig {
+    /// No peers and default intervals.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            peers: Vec::new(),
+            peer_dns: None,
+            peer_port: default_peer_port(),
+            secret: secret.into(),
+            sync_interval_ms: default_sync_interval_ms(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+
+    /// Reject secrets too short to keep the sync endpoint private.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.secret.len() < MIN_SECRET_LEN {
+            return Err(format!(
+                "Cluster secret must be at least {} characters",
+                MIN_SECRET_LEN
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Events one project consumed on a replica since its last report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectUsage {
+    pub events: u64,
+    /// Bucket rate and burst, to create the bucket on peers that have not
+    /// seen the project yet
+    pub rate: u32,
+    pub burst: u32,
+}
+
+impl ProjectUsage {
+    /// Most events a replica can admit for the project in one sync
+    /// interval: a full bucket plus a (rounded up) second of refill.
+    fn max_per_sync(&self, sync_interval_ms: u64) -> u64 {
+        let refill_secs = sync_interval_ms.div_ceil(1000).max(1);
+        u64::from(self.burst) + u64::from(self.rate) * refill_secs
+    }
+}
+
+/// Body of a sync request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub instance_id: String,
+    pub usage: HashMap<String, ProjectUsage>,
+    /// Signed request nonces accepted since the last report
+    #[serde(default)]
+    pub nonces: Vec<SeenNonce>,
+}
+
+/// Rate limit backend that shares usage with peer replicas.
+pub struct ClusterBackend {
+    local: InMemoryBackend,
+    instance_id: String,
+    config: ClusterConfig,
+    /// Usage admitted here and not yet reported
+    pending: Mutex<HashMap<String, ProjectUsage>>,
+    /// Nonces accepted here and not yet reported
+    pending_nonces: Mutex<Vec<SeenNonce>>,
+    http: reqwest::Client,
+}
+
+impl ClusterBackend {
+    pub fn new(config: ClusterConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .unwrap_or_default();
+        Self {
+            local: InMemoryBackend::new(),
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            config,
+            pending: Mutex::new(HashMap::new()),
+            pending_nonces: Mutex::new(Vec::new()),
+            http,
+        }
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Compare a sync request's secret without short-circuiting. An empty
+    /// secret never matches.
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        let expected = self.config.secret.as_bytes();
+        let given = secret.as_bytes();
+        !expected.is_empty()
+            && expected.len() == given.len()
+            && expected
+                .iter()
+                .zip(given)
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    /// Debit a peer's usage from the local buckets.
+    ///
+    /// Returns the number of projects applied; reports from this replica
+    /// (e.g. when `peer_dns` resolves to ourselves) are ignored. Usage is
+    /// capped at what one replica could admit in a sync interval, so a
+    /// single report cannot lock a project out for long.
+    pub fn apply_remote(&self, report: &UsageReport) -> usize {
+        if report.instance_id == self.instance_id {
+            return 0;
+        }
+        for (project_id, usage) in &report.usage {
+            let events = usage
+                .events
+                .min(usage.max_per_sync(self.config.sync_interval_ms));
+            self.local
+                .debit(project_id, events, usage.rate, usage.burst);
+        }
+        report.usage.len()
+    }
+
+    /// Report a signed request nonce to peers on the next sync.
+    pub fn share_nonce(&self, nonce: SeenNonce) {
+        self.pending_nonces.lock().push(nonce);
+    }
+
+    /// Take the usage and nonces accepted since the last report.
+    pub(crate) fn take_pending(&self) -> Option<UsageReport> {
+        let usage = std::mem::take(&mut *self.pending.lock());
+        let nonces = std::mem::take(&mut *self.pending_nonces.lock());
+        if usage.is_empty() && nonces.is_empty() {
+            return None;
+        }
+        Some(UsageReport {
+            instance_id: self.instance_id.clone(),
+            usage,
+            nonces,
+        })
+    }
+
+    /// Static peers plus every address `peer_dns` resolves to.
+    async fn peers(&self) -> Vec<String> {
+        let mut peers = self.config.peers.clone();
+        if let Some(host) = &self.config.peer_dns {
+            match tokio::net::lookup_host((host.as_str(), self.config.peer_port)).await {
+                Ok(addrs) => peers.extend(addrs.map(|addr| format!("http://{}", addr))),
+                Err(e) => warn!(host = %host, error = %e, "Failed to resolve rate limit peers"),
+            }
+        }
+        peers
+    }
+
+    /// Push pending usage to every peer.
+    ///
+    /// Failed pushes are not retried; the peer under-counts that interval.
+    pub async fn sync(&self) {
+        let Some(report) = self.take_pending() else {
+            return;
+        };
+        let report = Arc::new(report);
+
+        let mut requests = JoinSet::new();
+        for peer in self.peers().await {
+            let http = self.http.clone();
+            let secret = self.config.secret.clone();
+            let report = report.clone();
+            requests.spawn(async move {
+                let url = format!("{}{}", peer.trim_end_matches('/'), SYNC_PATH);
+                let result = http
+                    .post(&url)
+                    .header(CLUSTER_SECRET_HEADER, secret)
+                    .json(&*report)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    warn!(peer = %peer, error = %e, "Failed to sync rate limit usage");
+                }
+            });
+        }
+        while requests.join_next().await.is_some() {}
+
+        debug!(projects = report.usage.len(), "Synced rate limit usage");
+    }
+
+    /// Start the background sync task.
+    pub fn start_sync(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(self.config.sync_interval_ms.max(50)));
+            loop {
+                interval.tick().await;
+                self.sync().await;
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for ClusterBackend {
+    async fn acquire(&self, key: &str, cost: u32, rate: u32, burst: u32) -> RateLimitStatus {
+        let status = self.local.charge(key, cost, rate, burst);
+        if status.allowed {
+            let mut pending = self.pending.lock();
+            let usage = pending.entry(key.to_string()).or_insert(ProjectUsage {
+                events: 0,
+                rate,
+                burst,
+            });
+            usage.events += cost as u64;
+            usage.rate = rate;
+            usage.burst = burst;
+        }
+        status
+    }
+
+    async fn cleanup(&self, max_age: Duration) {
+        self.local.remove_stale(max_age);
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.local.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::rate_limit::{RateLimitConfig, RateLimiter};
+
+    const SECRET: &str = "s3cret-s3cret-s3cret";
+
+    fn config() -> ClusterConfig {
+        ClusterConfig::new(SECRET)
+    }
+
+    #[tokio::test]
+    async fn test_limit_is_shared_between_replicas() {
+        let a = Arc::new(ClusterBackend::new(config()));
+        let b = Arc::new(ClusterBackend::new(config()));
+        let limiter_a = RateLimiter::with_backend(RateLimitConfig::default(), a.clone());
+        let limiter_b = RateLimiter::with_backend(RateLimitConfig::default(), b.clone());
+
+        // 10/s with a 50 event burst, split across two replicas
+        assert!(limiter_a.check_events("proj", 10, 30).await.allowed);
+        b.apply_remote(&a.take_pending().unwrap());
+
+        let status = limiter_b.check_events("proj", 10, 30).await;
+        assert!(!status.allowed);
+        assert_eq!(status.remaining, 20);
+        assert!(limiter_b.check_events("proj", 10, 20).await.allowed);
+
+        // Nothing left to report on A, and its own report is ignored
+        assert!(a.take_pending().is_none());
+        let report = b.take_pending().unwrap();
+        assert_eq!(report.usage["proj"].events, 20);
+        assert_eq!(b.apply_remote(&report), 0);
+    }
+
+    #[test]
+    fn test_verify_secret() {
+        let backend = ClusterBackend::new(config());
+        assert!(backend.verify_secret(SECRET));
+        assert!(!backend.verify_secret("s3cret"));
+        assert!(!backend.verify_secret(""));
+
+        let empty = ClusterConfig::new("");
+        assert!(empty.validate().is_err());
+        assert!(ClusterConfig::new("short").validate().is_err());
+        assert!(config().validate().is_ok());
+        assert!(!ClusterBackend::new(empty).verify_secret(""));
+    }
+
+    #[tokio::test]
+    async fn test_remote_usage_is_capped() {
+        let backend = Arc::new(ClusterBackend::new(config()));
+        let limiter = RateLimiter::with_backend(RateLimitConfig::default(), backend.clone());
+        let mut usage = HashMap::new();
+        usage.insert(
+            "proj".to_string(),
+            ProjectUsage {
+                events: u64::MAX,
+                rate: 10,
+                burst: 50,
+            },
+        );
+        backend.apply_remote(&UsageReport {
+            instance_id: "peer".to_string(),
+            usage,
+            nonces: Vec::new(),
+        });
+
+        // Debited at most burst + one second of refill, so the bucket
+        // recovers within seconds
+        let status = limiter.check_events("proj", 10, 1).await;
+        assert!(!status.allowed);
+        assert!(status.retry_after_secs <= 2);
+    }
+}
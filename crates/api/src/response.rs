@@ -1,7 +1,7 @@
 //! Standardized API responses.
 
 use axum::{
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -13,6 +13,9 @@ pub struct IngestResponse {
     pub success: bool,
     pub received: usize,
     pub timestamp: i64,
+    /// Events not stored: disabled features or MAU sampling
+    #[serde(default)]
+    pub dropped: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub errors: Option<Vec<String>>,
 }
@@ -23,15 +26,23 @@ impl IngestResponse {
             success: true,
             received,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            dropped: 0,
             errors: None,
         }
     }
 
+    /// Report events dropped by feature flags or sampling.
+    pub fn with_dropped(mut self, dropped: usize) -> Self {
+        self.dropped = dropped;
+        self
+    }
+
     pub fn partial(received: usize, errors: Vec<String>) -> Self {
         Self {
             success: true,
             received,
             timestamp: chrono::Utc::now().timestamp_millis(),
+            dropped: 0,
             errors: if errors.is_empty() {
                 None
             } else {
@@ -79,6 +90,8 @@ pub struct ApiError {
     pub status: StatusCode,
     pub response: ErrorResponse,
     pub retry_after: Option<u64>,
+    /// Extra response headers (e.g. `X-RateLimit-*`), boxed to keep errors small
+    pub headers: Option<Box<HeaderMap>>,
 }
 
 impl ApiError {
@@ -87,9 +100,18 @@ impl ApiError {
             status,
             response: ErrorResponse::new(msg, code),
             retry_after: None,
+            headers: None,
         }
     }
 
+    /// Adds response headers.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers
+            .get_or_insert_with(Default::default)
+            .extend(headers);
+        self
+    }
+
     pub fn bad_request(msg: impl Into<String>) -> Self {
         Self::with_code(StatusCode::BAD_REQUEST, "VALID_001", msg)
     }
@@ -107,6 +129,16 @@ impl ApiError {
             status: StatusCode::TOO_MANY_REQUESTS,
             response: ErrorResponse::new(msg, "RATE_001"),
             retry_after,
+            headers: None,
+        }
+    }
+
+    pub fn unavailable(msg: impl Into<String>, retry_after: Option<u64>) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            response: ErrorResponse::new(msg, "DB_002"),
+            retry_after,
+            headers: None,
         }
     }
 
@@ -119,6 +151,7 @@ impl ApiError {
             status: StatusCode::BAD_REQUEST,
             response: ErrorResponse::new("Validation failed", code).with_details(errors),
             retry_after: None,
+            headers: None,
         }
     }
 }
@@ -127,7 +160,11 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let mut response = (self.status, Json(self.response)).into_response();
 
-        // Add Retry-After header for rate limit responses
+        if let Some(headers) = self.headers {
+            response.headers_mut().extend(*headers);
+        }
+
+        // Add Retry-After header for rate limit and backlog responses
         if let Some(retry_after) = self.retry_after {
             if let Ok(value) = retry_after.to_string().parse() {
                 response.headers_mut().insert("Retry-After", value);
@@ -152,14 +189,28 @@ impl From<engine_core::Error> for ApiError {
             engine_core::Error::ValidationWithCode { code, message, .. } => {
                 ApiError::validation(*code, vec![message.clone()])
             }
-            engine_core::Error::Database { code, message, .. } => {
-                ApiError::with_code(StatusCode::INTERNAL_SERVER_ERROR, *code, message)
+            engine_core::Error::Database {
+                code,
+                message,
+                http_status,
+            } => {
+                let status =
+                    StatusCode::from_u16(*http_status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                ApiError::with_code(status, *code, message)
             }
             engine_core::Error::RateLimit {
+                code,
                 message,
+                http_status,
                 retry_after,
-                ..
-            } => ApiError::rate_limited(message, *retry_after),
+            } => {
+                let status =
+                    StatusCode::from_u16(*http_status).unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+                ApiError {
+                    retry_after: *retry_after,
+                    ..ApiError::with_code(status, *code, message)
+                }
+            }
             engine_core::Error::Validation(msg) => ApiError::bad_request(msg),
             engine_core::Error::Unauthorized(msg) => ApiError::unauthorized("AUTH_003", msg),
             engine_core::Error::RateLimited(msg) => ApiError::rate_limited(msg, None),
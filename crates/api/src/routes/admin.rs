@@ -0,0 +1,154 @@
+//! Operator endpoints.
+//!
+//! All routes require an API key with the `admin` scope. Data-subject
+//! routes only touch the key's own project.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use clickhouse_client::forwarding::{self, DeadLetter, Delivery};
+use clickhouse_client::gdpr::{self, ErasureRequest};
+use clickhouse_client::mau::{self, MauOverage};
+use engine_core::Scope;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::extractors::AuthContext;
+use crate::response::ApiError;
+use crate::state::AppState;
+
+fn default_limit() -> u64 {
+    100
+}
+
+/// `?limit=`
+#[derive(Debug, Deserialize)]
+pub struct LimitParams {
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+/// Log and hide a data-subject query failure.
+fn subject_failed(e: engine_core::Error) -> ApiError {
+    match e {
+        engine_core::Error::Validation(_) => e.into(),
+        _ => {
+            error!("Data-subject request failed: {}", e);
+            ApiError::internal("Failed to process data-subject request")
+        }
+    }
+}
+
+/// GET /admin/subjects/:user_id/export - All stored rows for a user as JSONL.
+pub async fn subject_export_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let body = gdpr::export_subject(&state.clickhouse, &auth.project_id, &user_id)
+        .await
+        .map_err(subject_failed)?;
+
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body))
+}
+
+/// POST /admin/subjects/:user_id/erase - Start erasing a user's data.
+///
+/// Returns 202 with the pending request; progress is reported by
+/// `GET /admin/erasure-requests`.
+pub async fn subject_erase_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(user_id): Path<String>,
+) -> Result<(StatusCode, Json<ErasureRequest>), ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let request = gdpr::create_erasure_request(&state.clickhouse, &auth.project_id, &user_id)
+        .await
+        .map_err(subject_failed)?;
+
+    let clickhouse = state.clickhouse.clone();
+    let pending = request.clone();
+    tokio::spawn(async move {
+        let request_id = pending.request_id.clone();
+        if let Err(e) = gdpr::erase_subject(&clickhouse, pending).await {
+            warn!(request_id = %request_id, error = %e, "Erasure request failed");
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(request)))
+}
+
+/// GET /admin/erasure-requests - Erasure requests for the project, newest first.
+pub async fn erasure_requests_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<Vec<ErasureRequest>>, ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let requests = gdpr::erasure_requests(&state.clickhouse, &auth.project_id)
+        .await
+        .map_err(subject_failed)?;
+
+    Ok(Json(requests))
+}
+
+/// Log and hide a forwarding status query failure.
+fn forwarding_failed(e: engine_core::Error) -> ApiError {
+    error!("Forwarding status query failed: {}", e);
+    ApiError::internal("Failed to load forwarding status")
+}
+
+/// GET /admin/forwarding/deliveries - Recent forwarded batches for the project.
+pub async fn forwarding_deliveries_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<LimitParams>,
+) -> Result<Json<Vec<Delivery>>, ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let deliveries =
+        forwarding::recent_deliveries(&state.clickhouse, &auth.project_id, params.limit.min(1000))
+            .await
+            .map_err(forwarding_failed)?;
+
+    Ok(Json(deliveries))
+}
+
+/// GET /admin/forwarding/dead-letters - Batches that exhausted their retries.
+pub async fn forwarding_dead_letters_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<LimitParams>,
+) -> Result<Json<Vec<DeadLetter>>, ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let dead_letters =
+        forwarding::dead_letters(&state.clickhouse, &auth.project_id, params.limit.min(1000))
+            .await
+            .map_err(forwarding_failed)?;
+
+    Ok(Json(dead_letters))
+}
+
+/// GET /admin/mau/overages - Events accepted while over the MAU limit, per month.
+pub async fn mau_overages_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<Vec<MauOverage>>, ApiError> {
+    auth.require(Scope::Admin)?;
+
+    let overages = mau::project_overages(&state.clickhouse, &auth.project_id)
+        .await
+        .map_err(|e| {
+            error!("MAU overage query failed: {}", e);
+            ApiError::internal("Failed to load MAU overages")
+        })?;
+
+    Ok(Json(overages))
+}
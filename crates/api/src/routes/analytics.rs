@@ -0,0 +1,312 @@
+//! Analytics read endpoints for dashboards.
+//!
+//! All routes require an API key with the `read` scope and only ever
+//! return data for the key's project. Times are Unix milliseconds.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use clickhouse_client::analytics::{
+    self, ActorKey, Breakdown, BreakdownCount, FunnelStep, FunnelStepResult, Interval, PageCount,
+    ReferrerCount, RetentionCohort, TimeRange, TimeSeriesPoint, VisitorStats, WebVital,
+    WebVitalPercentiles,
+};
+use clickhouse_client::properties::{self, PropertyKeyInfo};
+use engine_core::Scope;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::extractors::AuthContext;
+use crate::response::ApiError;
+use crate::state::AppState;
+
+fn default_limit() -> u32 {
+    10
+}
+
+fn default_interval() -> Interval {
+    Interval::Day
+}
+
+fn default_actor_key() -> ActorKey {
+    ActorKey::User
+}
+
+fn default_funnel_window() -> u64 {
+    24 * 3600
+}
+
+/// `?from=&to=`
+#[derive(Debug, Deserialize)]
+pub struct RangeParams {
+    pub from: i64,
+    pub to: i64,
+}
+
+/// `?from=&to=&limit=`
+#[derive(Debug, Deserialize)]
+pub struct TopParams {
+    pub from: i64,
+    pub to: i64,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+/// `?from=&to=&interval=&event_type=`
+#[derive(Debug, Deserialize)]
+pub struct TimeSeriesParams {
+    pub from: i64,
+    pub to: i64,
+    #[serde(default = "default_interval")]
+    pub interval: Interval,
+    pub event_type: Option<String>,
+}
+
+/// `?from=&to=&metric=&limit=`
+#[derive(Debug, Deserialize)]
+pub struct WebVitalsParams {
+    pub from: i64,
+    pub to: i64,
+    pub metric: WebVital,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+/// Funnel request body.
+#[derive(Debug, Deserialize)]
+pub struct FunnelRequest {
+    pub from: i64,
+    pub to: i64,
+    pub steps: Vec<FunnelStep>,
+    /// Conversion window in seconds (default 24h)
+    #[serde(default = "default_funnel_window")]
+    pub window_secs: u64,
+    #[serde(default = "default_actor_key")]
+    pub key: ActorKey,
+}
+
+/// `?from=&to=&key=`
+#[derive(Debug, Deserialize)]
+pub struct RetentionParams {
+    pub from: i64,
+    pub to: i64,
+    #[serde(default = "default_actor_key")]
+    pub key: ActorKey,
+}
+
+/// `?event_name=`
+#[derive(Debug, Deserialize)]
+pub struct PropertiesParams {
+    pub event_name: Option<String>,
+}
+
+/// Pass validation errors through; hide query failures behind DB_001.
+fn query_failed(e: engine_core::Error) -> ApiError {
+    match e {
+        engine_core::Error::Validation(_) => e.into(),
+        _ => {
+            error!("Analytics query failed: {}", e);
+            ApiError::internal("Failed to run analytics query")
+        }
+    }
+}
+
+/// GET /analytics/timeseries - Event counts per interval and event type.
+pub async fn timeseries_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<TimeSeriesParams>,
+) -> Result<Json<Vec<TimeSeriesPoint>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let points = analytics::event_time_series(
+        &state.clickhouse,
+        &auth.project_id,
+        range,
+        params.interval,
+        params.event_type.as_deref(),
+    )
+    .await
+    .map_err(query_failed)?;
+
+    Ok(Json(points))
+}
+
+/// GET /analytics/pages - Top pages by pageviews.
+pub async fn top_pages_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<TopParams>,
+) -> Result<Json<Vec<PageCount>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let pages = analytics::top_pages(&state.clickhouse, &auth.project_id, range, params.limit)
+        .await
+        .map_err(query_failed)?;
+
+    Ok(Json(pages))
+}
+
+/// GET /analytics/referrers - Top referring domains.
+pub async fn top_referrers_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<TopParams>,
+) -> Result<Json<Vec<ReferrerCount>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let referrers =
+        analytics::top_referrers(&state.clickhouse, &auth.project_id, range, params.limit)
+            .await
+            .map_err(query_failed)?;
+
+    Ok(Json(referrers))
+}
+
+/// GET /analytics/visitors - Unique visitors, sessions and users.
+pub async fn visitors_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<RangeParams>,
+) -> Result<Json<VisitorStats>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let stats = analytics::unique_visitors(&state.clickhouse, &auth.project_id, range)
+        .await
+        .map_err(query_failed)?;
+
+    Ok(Json(stats))
+}
+
+/// GET /analytics/breakdown/:dimension - Device, browser, OS or country breakdown.
+pub async fn breakdown_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(dimension): Path<Breakdown>,
+    Query(params): Query<TopParams>,
+) -> Result<Json<Vec<BreakdownCount>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let rows = analytics::breakdown(
+        &state.clickhouse,
+        &auth.project_id,
+        range,
+        dimension,
+        params.limit,
+    )
+    .await
+    .map_err(query_failed)?;
+
+    Ok(Json(rows))
+}
+
+/// GET /analytics/web-vitals - Web-vitals percentiles per path.
+pub async fn web_vitals_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<WebVitalsParams>,
+) -> Result<Json<Vec<WebVitalPercentiles>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let vitals = analytics::web_vitals(
+        &state.clickhouse,
+        &auth.project_id,
+        range,
+        params.metric,
+        params.limit,
+    )
+    .await
+    .map_err(query_failed)?;
+
+    Ok(Json(vitals))
+}
+
+/// POST /analytics/funnel - Ordered funnel conversion per step.
+pub async fn funnel_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Json(request): Json<FunnelRequest>,
+) -> Result<Json<Vec<FunnelStepResult>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: request.from,
+        to: request.to,
+    };
+    let steps = analytics::funnel(
+        &state.clickhouse,
+        &auth.project_id,
+        range,
+        &request.steps,
+        request.window_secs,
+        request.key,
+    )
+    .await
+    .map_err(query_failed)?;
+
+    Ok(Json(steps))
+}
+
+/// GET /analytics/retention - Weekly retention cohorts.
+pub async fn retention_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<RetentionParams>,
+) -> Result<Json<Vec<RetentionCohort>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let range = TimeRange {
+        from: params.from,
+        to: params.to,
+    };
+    let cohorts =
+        analytics::retention_cohorts(&state.clickhouse, &auth.project_id, range, params.key)
+            .await
+            .map_err(query_failed)?;
+
+    Ok(Json(cohorts))
+}
+
+/// GET /analytics/properties - Property keys seen for the project.
+pub async fn properties_handler(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(params): Query<PropertiesParams>,
+) -> Result<Json<Vec<PropertyKeyInfo>>, ApiError> {
+    auth.require(Scope::Read)?;
+
+    let keys = properties::property_catalog(
+        &state.clickhouse,
+        &auth.project_id,
+        params.event_name.as_deref(),
+    )
+    .await
+    .map_err(query_failed)?;
+
+    Ok(Json(keys))
+}
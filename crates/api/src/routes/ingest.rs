@@ -7,10 +7,16 @@
 //!
 //! Transforms to ClickHouse format and sends to Redpanda.
 
-use axum::{body::Bytes, extract::State, Json};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, HeaderValue},
+    Json,
+};
 use engine_core::{
     limits::{MAX_BATCH_EVENTS, MAX_BATCH_SIZE_BYTES},
-    transform_batch, SDKPayload, ValidationErrorCode,
+    transform_batch_with, KeyType, OverLimitAction, RateLimitErrorCode, SDKPayload, Scope,
+    ValidationErrorCode,
 };
 use std::time::Instant;
 use telemetry::metrics;
@@ -24,27 +30,66 @@ use crate::state::AppState;
 ///
 /// Accepts SDK events in camelCase format, validates, transforms to
 /// ClickHouse format (snake_case), and sends to Redpanda for processing.
+///
+/// Events whose feature is disabled for the project are dropped (and counted
+/// in `dropped`); location fields are stripped unless `location` is enabled.
+///
+/// Projects over their MAU limit are rejected (`402 RATE_002`), sampled by
+/// session, or accepted and flagged for billing, depending on their plan.
+///
+/// Each event costs one token from the project's rate limit; responses carry
+/// `X-RateLimit-Limit/Remaining/Reset` once the batch has been charged.
+///
+/// Requests may be signed with the project's signing secret (see
+/// [`crate::signing`]); projects can require it for live server keys.
+///
+/// Projects may restrict server keys to CIDR allowlists and block ranges
+/// with denylists; both are checked against the resolved [`ClientIp`].
+///
+/// Requires the `ingest` scope. Batches sent with `owk_test_` keys go to the
+/// sandbox producer, with their own rate limit and no MAU accounting, so
+/// test traffic never reaches production tables.
 pub async fn ingest_handler(
     State(state): State<AppState>,
     auth: AuthContext,
-    ClientIp(_client_ip): ClientIp,
+    ClientIp(client_ip): ClientIp,
+    request_headers: HeaderMap,
     body: Bytes,
-) -> Result<Json<IngestResponse>, ApiError> {
+) -> Result<(HeaderMap, Json<IngestResponse>), ApiError> {
     let start = Instant::now();
 
+    auth.require(Scope::Ingest)?;
+    if let Err(e) = auth
+        .ip_rules
+        .check(client_ip, auth.key_type == KeyType::Server)
+    {
+        metrics().ip_rejected_requests.inc();
+        debug!(project_id = %auth.project_id, client_ip = ?client_ip, "Client IP rejected");
+        return Err(e.into());
+    }
+    let signed = state
+        .signatures
+        .verify(&auth, &request_headers, &body)
+        .await?;
+    let sandboxed = auth.api_key.is_test();
+
     metrics().batches_received.inc();
 
-    // Rate limit check by project_id
-    if !state.rate_limiter.check(&auth.project_id) {
-        metrics().rate_limited_requests.inc();
-        warn!(
-            project_id = %auth.project_id,
-            "Rate limit exceeded"
-        );
-        return Err(ApiError::rate_limited(
-            format!("Rate limit exceeded for project {}", auth.project_id),
-            Some(1), // Retry after 1 second
-        ));
+    // Shed load while the pipeline is too far behind
+    if let Some(max_lag) = state.max_consumer_lag {
+        let lag = metrics().consumer_lag.get();
+        if lag > max_lag {
+            warn!(
+                project_id = %auth.project_id,
+                lag = lag,
+                max_lag = max_lag,
+                "Consumer backlog too deep, rejecting batch"
+            );
+            return Err(ApiError::unavailable(
+                format!("Ingestion backlog of {} events, retry later", lag),
+                Some(5),
+            ));
+        }
     }
 
     // Check payload size before parsing
@@ -85,15 +130,108 @@ pub async fn ingest_handler(
         ));
     }
 
-    // Transform SDK events to ClickHouse format
-    let (ch_events, transform_errors) =
-        transform_batch(payload.events, &auth.project_id).map_err(|e| {
+    // Over-limit handling, from the auth service's MAU view
+    let over_limit = auth
+        .mau
+        .filter(|mau| mau.is_over_limit && !sandboxed)
+        .map(|_| state.mau.config().action(auth.tier));
+    if over_limit == Some(OverLimitAction::Reject) {
+        metrics().mau_rejected_batches.inc();
+        warn!(project_id = %auth.project_id, "MAU limit exceeded, rejecting batch");
+        return Err(engine_core::Error::rate_limit(
+            RateLimitErrorCode::MauExceeded,
+            format!(
+                "Project {} is over its monthly active user limit",
+                auth.project_id
+            ),
+            None,
+        )
+        .into());
+    }
+
+    // Rate limit by project_id, one token per event; test keys get their own bucket
+    let bucket = if sandboxed {
+        format!("{}:sandbox", auth.project_id)
+    } else {
+        auth.project_id.clone()
+    };
+    let rate_limit = state
+        .rate_limiter
+        .check_events(&bucket, auth.rate_limit, total_events)
+        .await;
+    if !rate_limit.allowed {
+        metrics().rate_limited_requests.inc();
+        warn!(
+            project_id = %auth.project_id,
+            events = total_events,
+            limit = rate_limit.limit,
+            retry_after = rate_limit.retry_after_secs,
+            "Rate limit exceeded"
+        );
+        return Err(ApiError::rate_limited(
+            format!("Rate limit exceeded for project {}", auth.project_id),
+            Some(rate_limit.retry_after_secs),
+        )
+        .with_headers(rate_limit.headers()));
+    }
+
+    // Transform SDK events to ClickHouse format, applying the project's features
+    let features = state
+        .auth_client
+        .features(&auth.api_key, &auth.project_id)
+        .await;
+    let (mut ch_events, transform_errors, filtered) =
+        transform_batch_with(payload.events, &auth.project_id, &features).map_err(|e| {
             error!("Transform failed: {}", e);
             ApiError::internal("Failed to process events")
         })?;
 
+    if !sandboxed {
+        state
+            .mau
+            .observe(&auth.project_id, &ch_events, auth.mau.map(|mau| mau.limit));
+    }
+
+    let mut sampled_out = 0;
+    match over_limit {
+        Some(OverLimitAction::Sample) => {
+            let received = ch_events.len();
+            let config = state.mau.config();
+            ch_events.retain(|event| config.sample_keeps(&event.session_id));
+            sampled_out = received - ch_events.len();
+            metrics().events_mau_sampled_out.inc_by(sampled_out as u64);
+            state.mau.record_overage(
+                &auth.project_id,
+                OverLimitAction::Sample,
+                received,
+                sampled_out,
+            );
+        }
+        Some(action) => {
+            state
+                .mau
+                .record_overage(&auth.project_id, action, ch_events.len(), 0);
+        }
+        None => {}
+    }
+
     let accepted = ch_events.len();
     let rejected = transform_errors.len();
+    let dropped = filtered.dropped + sampled_out;
+
+    if filtered.dropped > 0 {
+        debug!(
+            project_id = %auth.project_id,
+            dropped = filtered.dropped,
+            "Dropped events for disabled features"
+        );
+        metrics()
+            .events_dropped_disabled
+            .inc_by(filtered.dropped as u64);
+    }
+    metrics()
+        .events_location_stripped
+        .inc_by(filtered.location_stripped as u64);
 
     if rejected > 0 {
         warn!(
@@ -107,10 +245,17 @@ pub async fn ingest_handler(
 
     metrics().events_validated.inc_by(accepted as u64);
 
-    // Send to Redpanda
-    if !ch_events.is_empty() {
-        let send_result = state
-            .producer
+    // Send to Redpanda; without a sandbox, test events are validated but not stored
+    let producer = if sandboxed {
+        state.sandbox.as_ref()
+    } else {
+        Some(&state.producer)
+    };
+    if let Some(producer) = producer.filter(|_| !ch_events.is_empty()) {
+        if sandboxed {
+            metrics().events_sandboxed.inc_by(ch_events.len() as u64);
+        }
+        let send_result = producer
             .send_clickhouse_events(ch_events)
             .await
             .map_err(|e| {
@@ -134,18 +279,91 @@ pub async fn ingest_handler(
         project_id = %auth.project_id,
         accepted = accepted,
         rejected = rejected,
+        dropped = dropped,
+        sandboxed = sandboxed,
+        signed = signed,
         latency_ms = latency_ms,
         "Batch processed"
     );
 
+    let mut headers = rate_limit.headers();
+    if let Some(action) = over_limit {
+        headers.insert(
+            "X-Overwatch-Mau-Over-Limit",
+            HeaderValue::from_static(action.as_str()),
+        );
+    }
+
     // Return response with partial errors if any
     if rejected > 0 {
         let error_msgs: Vec<String> = transform_errors
             .into_iter()
             .map(|e| e.to_string())
             .collect();
-        Ok(Json(IngestResponse::partial(accepted, error_msgs)))
+        Ok((
+            headers,
+            Json(IngestResponse::partial(accepted, error_msgs).with_dropped(dropped)),
+        ))
     } else {
-        Ok(Json(IngestResponse::success(accepted)))
+        Ok((
+            headers,
+            Json(IngestResponse::success(accepted).with_dropped(dropped)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, CountingProducer};
+    use engine_core::{IpRules, ParsedApiKey};
+    use std::sync::Arc;
+
+    fn test_key_context() -> AuthContext {
+        AuthContext {
+            api_key: ParsedApiKey::parse("owk_test_ABC123xyz789DEF456ghi012JKL345mn").unwrap(),
+            project_id: "proj-sandbox".to_string(),
+            rate_limit: 1000,
+            allowed_origins: None,
+            permissions: vec!["ingest".to_string()],
+            tier: None,
+            mau: None,
+            key_type: KeyType::Browser,
+            signing_secret: None,
+            require_signature: false,
+            ip_rules: IpRules::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_test_key_without_sandbox_is_accepted_but_not_stored() {
+        let producer = Arc::new(CountingProducer::default());
+        let state = test_util::state(producer.clone());
+        let body = serde_json::json!([{
+            "id": "3f1c2a9e-8b7d-4c6e-9a5f-1e2d3c4b5a69",
+            "type": "pageview",
+            "timestamp": chrono::Utc::now().timestamp_millis(),
+            "sessionId": "5a4b3c2d-1e0f-4a9b-8c7d-6e5f4a3b2c1d",
+            "url": "https://example.com/",
+            "userAgent": "Mozilla/5.0 (Test)",
+        }]);
+
+        let result = ingest_handler(
+            State(state),
+            test_key_context(),
+            ClientIp(None),
+            HeaderMap::new(),
+            Bytes::from(body.to_string()),
+        )
+        .await;
+
+        match result {
+            Ok((_, Json(response))) => {
+                assert!(response.success);
+                assert_eq!(response.received, 1);
+            }
+            Err(e) => panic!("test key batch rejected: {}", e.response.code),
+        }
+        assert_eq!(producer.count(), 0);
     }
 }
@@ -0,0 +1,91 @@
+//! Replica-to-replica endpoints.
+//!
+//! Authenticated with the cluster secret rather than an API key.
+
+use axum::{extract::State, http::HeaderMap, http::StatusCode, Json};
+use tracing::warn;
+
+use crate::middleware::rate_limit_cluster::{UsageReport, CLUSTER_SECRET_HEADER};
+use crate::response::ApiError;
+use crate::state::AppState;
+
+/// POST /internal/rate-limit/sync - Apply a peer's rate limit usage and
+/// remember its signed request nonces.
+pub async fn rate_limit_sync_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(report): Json<UsageReport>,
+) -> Result<StatusCode, ApiError> {
+    let Some(cluster) = state.rate_limit_cluster.as_ref() else {
+        return Err(ApiError::with_code(
+            StatusCode::NOT_FOUND,
+            "VALID_001",
+            "Cluster rate limiting is not enabled",
+        ));
+    };
+
+    let secret = headers
+        .get(CLUSTER_SECRET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !cluster.verify_secret(secret) {
+        warn!(instance_id = %report.instance_id, "Rejected rate limit sync with bad secret");
+        return Err(ApiError::unauthorized("AUTH_003", "Invalid cluster secret"));
+    }
+
+    cluster.apply_remote(&report);
+    // Our own nonces, when `peer_dns` resolves to us, are already known
+    state.signatures.remember_remote(&report.nonces);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::rate_limit_cluster::{ClusterBackend, ClusterConfig, ProjectUsage};
+    use crate::test_util::{self, CountingProducer};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn report() -> UsageReport {
+        let mut usage = HashMap::new();
+        usage.insert(
+            "proj".to_string(),
+            ProjectUsage {
+                events: 5,
+                rate: 10,
+                burst: 50,
+            },
+        );
+        UsageReport {
+            instance_id: "peer".to_string(),
+            usage,
+            nonces: Vec::new(),
+        }
+    }
+
+    async fn sync(secret: &str, header: Option<&str>) -> StatusCode {
+        let cluster = Arc::new(ClusterBackend::new(ClusterConfig::new(secret)));
+        let state = test_util::state(Arc::new(CountingProducer::default()))
+            .with_rate_limit_cluster(cluster);
+        let mut headers = HeaderMap::new();
+        if let Some(header) = header {
+            headers.insert(CLUSTER_SECRET_HEADER, header.parse().unwrap());
+        }
+        match rate_limit_sync_handler(State(state), headers, Json(report())).await {
+            Ok(status) => status,
+            Err(e) => e.status,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_requires_cluster_secret() {
+        let secret = "0123456789abcdef0123";
+        assert_eq!(sync(secret, Some(secret)).await, StatusCode::NO_CONTENT);
+        assert_eq!(sync(secret, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(sync(secret, Some("")).await, StatusCode::UNAUTHORIZED);
+        // A misconfigured empty secret does not open the endpoint
+        assert_eq!(sync("", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(sync("", Some("")).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,34 +1,100 @@
 //! API routes.
 
+pub mod admin;
+pub mod analytics;
 pub mod health;
 pub mod ingest;
+pub mod internal;
 
 use axum::{
+    extract::Request,
+    http::{header, HeaderName},
+    middleware,
     routing::{get, post},
     Router,
 };
 use tower_http::{
-    compression::CompressionLayer,
-    cors::{Any, CorsLayer},
+    compression::CompressionLayer, sensitive_headers::SetSensitiveRequestHeadersLayer,
     trace::TraceLayer,
 };
+use tracing::Span;
 
+use crate::middleware::cors::cors_middleware;
 use crate::state::AppState;
 
+/// Request headers that carry credentials and must never be logged.
+fn sensitive_headers() -> [HeaderName; 3] {
+    [
+        header::AUTHORIZATION,
+        HeaderName::from_static("x-api-key"),
+        // CLUSTER_SECRET_HEADER
+        HeaderName::from_static("x-overwatch-cluster-secret"),
+    ]
+}
+
+/// Request span without the query string, which may carry a `key`.
+fn make_span(request: &Request) -> Span {
+    tracing::debug_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        version = ?request.version(),
+    )
+}
+
 /// Creates the API router.
 pub fn router(state: AppState) -> Router {
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
     Router::new()
         .route("/overwatch-ingest", post(ingest::ingest_handler))
+        .route("/analytics/timeseries", get(analytics::timeseries_handler))
+        .route("/analytics/pages", get(analytics::top_pages_handler))
+        .route(
+            "/analytics/referrers",
+            get(analytics::top_referrers_handler),
+        )
+        .route("/analytics/visitors", get(analytics::visitors_handler))
+        .route(
+            "/analytics/breakdown/:dimension",
+            get(analytics::breakdown_handler),
+        )
+        .route("/analytics/web-vitals", get(analytics::web_vitals_handler))
+        .route("/analytics/funnel", post(analytics::funnel_handler))
+        .route("/analytics/retention", get(analytics::retention_handler))
+        .route("/analytics/properties", get(analytics::properties_handler))
+        .route(
+            "/admin/subjects/:user_id/export",
+            get(admin::subject_export_handler),
+        )
+        .route(
+            "/admin/subjects/:user_id/erase",
+            post(admin::subject_erase_handler),
+        )
+        .route(
+            "/admin/erasure-requests",
+            get(admin::erasure_requests_handler),
+        )
+        .route(
+            "/admin/forwarding/deliveries",
+            get(admin::forwarding_deliveries_handler),
+        )
+        .route(
+            "/admin/forwarding/dead-letters",
+            get(admin::forwarding_dead_letters_handler),
+        )
+        .route("/admin/mau/overages", get(admin::mau_overages_handler))
+        .route(
+            "/internal/rate-limit/sync",
+            post(internal::rate_limit_sync_handler),
+        )
         .route("/health", get(health::health_handler))
         .route("/health/ready", get(health::ready_handler))
         .route("/health/live", get(health::live_handler))
         .layer(CompressionLayer::new())
-        .layer(TraceLayer::new_for_http())
-        .layer(cors)
+        .layer(TraceLayer::new_for_http().make_span_with(make_span))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            cors_middleware,
+        ))
+        .layer(SetSensitiveRequestHeadersLayer::new(sensitive_headers()))
         .with_state(state)
 }
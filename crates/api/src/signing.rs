@@ -0,0 +1,500 @@
+//! Signed requests for server-to-server ingestion.
+//!
+//! Backend SDKs sign each request with the project's signing secret:
+//!
+//! - `X-Overwatch-Timestamp`: Unix seconds
+//! - `X-Overwatch-Nonce`: 16-128 characters of `[A-Za-z0-9_-]`, unique per request
+//! - `X-Overwatch-Signature`: `sha256=<hex>` HMAC-SHA256 of
+//!   `<timestamp>.<nonce>.<body>`
+//!
+//! Requests more than [`REPLAY_WINDOW_SECS`] from the server's clock are
+//! rejected, and each nonce is accepted once per project within the window.
+//! Nonces are kept until their timestamp leaves the window; when the store
+//! is full, signed requests are refused with `AUTH_006` rather than
+//! forgetting nonces early. With cluster rate limiting configured, accepted
+//! nonces ride along on the peer sync, so other replicas refuse them too once
+//! the next sync lands; a replay sent to another replica within one sync
+//! interval can still get through. Without it, nonces are tracked per
+//! replica and a replay to a different replica is only bounded by the
+//! timestamp check.
+
+use axum::http::HeaderMap;
+use engine_core::{AuthErrorCode, Error, KeyType};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use telemetry::metrics;
+use tracing::{debug, warn};
+
+use crate::extractors::AuthContext;
+use crate::middleware::rate_limit_cluster::ClusterBackend;
+use crate::response::ApiError;
+
+pub const SIGNATURE_HEADER: &str = "X-Overwatch-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Overwatch-Timestamp";
+pub const NONCE_HEADER: &str = "X-Overwatch-Nonce";
+
+/// Maximum distance between a request's timestamp and the server clock.
+pub const REPLAY_WINDOW_SECS: i64 = 300;
+
+/// Width of a nonce bucket, by request timestamp.
+const NONCE_BUCKET_SECS: i64 = 60;
+
+/// Maximum nonces remembered at once, across projects.
+const MAX_TRACKED_NONCES: usize = 1_000_000;
+
+/// Maximum nonces remembered for one project.
+const MAX_PROJECT_NONCES: usize = 200_000;
+
+fn mac(secret: &str, timestamp: i64, nonce: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(nonce.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// `sha256=<hex>` signature of `<timestamp>.<nonce>.<body>`.
+pub fn sign_request(secret: &str, timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    let signature = mac(secret, timestamp, nonce, body).finalize().into_bytes();
+    format!("sha256={}", hex::encode(signature))
+}
+
+fn valid_nonce(nonce: &str) -> bool {
+    (16..=128).contains(&nonce.len())
+        && nonce
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn invalid(message: &str) -> ApiError {
+    metrics().signature_failures.inc();
+    Error::auth(AuthErrorCode::InvalidSignature, message).into()
+}
+
+fn expired(message: &str) -> ApiError {
+    metrics().signature_failures.inc();
+    Error::auth(AuthErrorCode::SignatureExpired, message).into()
+}
+
+/// A nonce accepted by one replica, shared with its peers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeenNonce {
+    pub project_id: String,
+    pub nonce: String,
+    /// Signed request timestamp (Unix seconds)
+    pub timestamp: i64,
+}
+
+/// Outcome of remembering a nonce.
+#[derive(Debug, PartialEq, Eq)]
+enum NonceCheck {
+    Fresh,
+    Reused,
+    /// The store is at capacity; the nonce was not remembered
+    Full,
+}
+
+/// Nonces of accepted requests, per project and timestamp bucket.
+///
+/// A bucket is dropped only once every timestamp in it is outside the
+/// replay window, so a nonce is remembered for as long as its request could
+/// pass the timestamp check. A replay carries the same timestamp (it is
+/// signed), so only that bucket needs checking.
+struct NonceStore {
+    projects: HashMap<String, BTreeMap<i64, HashSet<String>>>,
+    len: usize,
+    max_nonces: usize,
+    max_project_nonces: usize,
+}
+
+impl NonceStore {
+    fn new(max_nonces: usize, max_project_nonces: usize) -> Self {
+        Self {
+            projects: HashMap::new(),
+            len: 0,
+            max_nonces,
+            max_project_nonces,
+        }
+    }
+
+    /// First bucket that can still hold timestamps inside the window.
+    fn cutoff(now: i64) -> i64 {
+        (now - REPLAY_WINDOW_SECS).div_euclid(NONCE_BUCKET_SECS)
+    }
+
+    /// Drop expired buckets of every project.
+    fn prune(&mut self, now: i64) {
+        let cutoff = Self::cutoff(now);
+        let mut removed = 0;
+        self.projects.retain(|_, buckets| {
+            removed += prune_buckets(buckets, cutoff);
+            !buckets.is_empty()
+        });
+        self.len -= removed;
+    }
+
+    fn insert(&mut self, project_id: &str, nonce: &str, timestamp: i64, now: i64) -> NonceCheck {
+        if self.len >= self.max_nonces {
+            self.prune(now);
+        }
+
+        let buckets = self.projects.entry(project_id.to_string()).or_default();
+        let removed = prune_buckets(buckets, Self::cutoff(now));
+        self.len -= removed;
+
+        let bucket = timestamp.div_euclid(NONCE_BUCKET_SECS);
+        if buckets
+            .get(&bucket)
+            .is_some_and(|nonces| nonces.contains(nonce))
+        {
+            return NonceCheck::Reused;
+        }
+        let project_len: usize = buckets.values().map(HashSet::len).sum();
+        if self.len >= self.max_nonces || project_len >= self.max_project_nonces {
+            return NonceCheck::Full;
+        }
+
+        buckets.entry(bucket).or_default().insert(nonce.to_string());
+        self.len += 1;
+        NonceCheck::Fresh
+    }
+}
+
+/// Remove buckets before `cutoff`, returning the nonces removed.
+fn prune_buckets(buckets: &mut BTreeMap<i64, HashSet<String>>, cutoff: i64) -> usize {
+    let kept = buckets.split_off(&cutoff);
+    let removed = buckets.values().map(HashSet::len).sum();
+    *buckets = kept;
+    removed
+}
+
+/// Checks request signatures and remembers nonces.
+pub struct SignatureVerifier {
+    nonces: Mutex<NonceStore>,
+    /// Peer channel accepted nonces are shared over
+    cluster: Option<Arc<ClusterBackend>>,
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SignatureVerifier {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_TRACKED_NONCES, MAX_PROJECT_NONCES)
+    }
+
+    fn with_capacity(max_nonces: usize, max_project_nonces: usize) -> Self {
+        Self {
+            nonces: Mutex::new(NonceStore::new(max_nonces, max_project_nonces)),
+            cluster: None,
+        }
+    }
+
+    /// Share accepted nonces with peer replicas through `cluster`.
+    pub fn with_cluster(mut self, cluster: Arc<ClusterBackend>) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// Remember nonces accepted by a peer. Nonces that are malformed or
+    /// outside the window are ignored. Returns how many were new.
+    pub fn remember_remote(&self, nonces: &[SeenNonce]) -> usize {
+        self.remember_remote_at(nonces, chrono::Utc::now().timestamp())
+    }
+
+    fn remember_remote_at(&self, nonces: &[SeenNonce], now: i64) -> usize {
+        let mut store = self.nonces.lock();
+        nonces
+            .iter()
+            .filter(|seen| {
+                valid_nonce(&seen.nonce) && (now - seen.timestamp).abs() <= REPLAY_WINDOW_SECS
+            })
+            .filter(|seen| {
+                store.insert(&seen.project_id, &seen.nonce, seen.timestamp, now)
+                    == NonceCheck::Fresh
+            })
+            .count()
+    }
+
+    /// Verify the request's signature if it has one, or must have one.
+    ///
+    /// Returns whether the request was signed. Missing, malformed or wrong
+    /// signatures are `AUTH_007`; stale timestamps and reused nonces are
+    /// `AUTH_008`; a full nonce store is `AUTH_006`.
+    pub async fn verify(
+        &self,
+        auth: &AuthContext,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<bool, ApiError> {
+        self.verify_at(auth, headers, body, chrono::Utc::now().timestamp())
+            .await
+    }
+
+    async fn verify_at(
+        &self,
+        auth: &AuthContext,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: i64,
+    ) -> Result<bool, ApiError> {
+        let Some(signature) = header(headers, SIGNATURE_HEADER) else {
+            if auth.require_signature && auth.api_key.is_live() && auth.key_type == KeyType::Server
+            {
+                return Err(invalid("This project requires signed requests"));
+            }
+            return Ok(false);
+        };
+
+        let Some(secret) = &auth.signing_secret else {
+            return Err(invalid("Project has no signing secret"));
+        };
+        let timestamp = header(headers, TIMESTAMP_HEADER)
+            .and_then(|value| value.parse::<i64>().ok())
+            .ok_or_else(|| invalid("Missing or invalid X-Overwatch-Timestamp"))?;
+        let nonce = header(headers, NONCE_HEADER)
+            .filter(|nonce| valid_nonce(nonce))
+            .ok_or_else(|| invalid("Missing or invalid X-Overwatch-Nonce"))?;
+        let expected = signature
+            .strip_prefix("sha256=")
+            .and_then(|hex_signature| hex::decode(hex_signature).ok())
+            .ok_or_else(|| invalid("Malformed X-Overwatch-Signature"))?;
+
+        mac(secret.expose(), timestamp, nonce, body)
+            .verify_slice(&expected)
+            .map_err(|_| invalid("Request signature does not match"))?;
+
+        if (now - timestamp).abs() > REPLAY_WINDOW_SECS {
+            return Err(expired(&format!(
+                "Request timestamp is outside the {} second window",
+                REPLAY_WINDOW_SECS
+            )));
+        }
+
+        // Only nonces of correctly signed requests are remembered
+        let check = self
+            .nonces
+            .lock()
+            .insert(&auth.project_id, nonce, timestamp, now);
+        match check {
+            NonceCheck::Fresh => {
+                if let Some(cluster) = &self.cluster {
+                    cluster.share_nonce(SeenNonce {
+                        project_id: auth.project_id.clone(),
+                        nonce: nonce.to_string(),
+                        timestamp,
+                    });
+                }
+                Ok(true)
+            }
+            NonceCheck::Reused => {
+                debug!(project_id = %auth.project_id, "Signed request nonce reused");
+                Err(expired("Request nonce has already been used"))
+            }
+            NonceCheck::Full => {
+                warn!(project_id = %auth.project_id, "Nonce store full, refusing signed request");
+                Err(Error::auth(
+                    AuthErrorCode::ServiceUnavailable,
+                    "Too many signed requests, retry later",
+                )
+                .into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_core::{ParsedApiKey, SigningSecret};
+
+    const SECRET: &str = "whsec_test";
+    const NOW: i64 = 1_700_000_000;
+
+    fn context(require_signature: bool) -> AuthContext {
+        AuthContext {
+            api_key: ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap(),
+            project_id: "proj".to_string(),
+            rate_limit: 1000,
+            allowed_origins: None,
+            permissions: vec!["ingest".to_string()],
+            tier: None,
+            mau: None,
+            key_type: KeyType::Server,
+            signing_secret: Some(SigningSecret::new(SECRET)),
+            require_signature,
+            ip_rules: Default::default(),
+        }
+    }
+
+    fn signed(timestamp: i64, nonce: &str, body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            SIGNATURE_HEADER,
+            sign_request(SECRET, timestamp, nonce, body)
+                .parse()
+                .unwrap(),
+        );
+        headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse().unwrap());
+        headers.insert(NONCE_HEADER, nonce.parse().unwrap());
+        headers
+    }
+
+    fn code(result: Result<bool, ApiError>) -> String {
+        result.err().map(|e| e.response.code).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_nonces_are_shared_with_peers() {
+        use crate::middleware::rate_limit_cluster::ClusterConfig;
+
+        let cluster = Arc::new(ClusterBackend::new(ClusterConfig::new("0123456789abcdef")));
+        let first = SignatureVerifier::new().with_cluster(cluster.clone());
+        let second = SignatureVerifier::new();
+        let auth = context(false);
+        let body = br#"{"events":[]}"#;
+        let headers = signed(NOW, "nonce-0123456789ab", body);
+
+        assert!(matches!(
+            first.verify_at(&auth, &headers, body, NOW).await,
+            Ok(true)
+        ));
+        let report = cluster.take_pending().unwrap();
+        assert_eq!(
+            report.nonces,
+            vec![SeenNonce {
+                project_id: "proj".to_string(),
+                nonce: "nonce-0123456789ab".to_string(),
+                timestamp: NOW,
+            }]
+        );
+
+        // The peer refuses the replay once the report arrives
+        assert_eq!(second.remember_remote_at(&report.nonces, NOW), 1);
+        assert_eq!(
+            code(second.verify_at(&auth, &headers, body, NOW).await),
+            "AUTH_008"
+        );
+
+        // Stale or malformed nonces are not stored
+        let mut stale = report.nonces[0].clone();
+        stale.timestamp = NOW - REPLAY_WINDOW_SECS - 1;
+        let mut malformed = report.nonces[0].clone();
+        malformed.nonce = "short".to_string();
+        assert_eq!(second.remember_remote_at(&[stale, malformed], NOW), 0);
+    }
+
+    #[tokio::test]
+    async fn test_signature_replay_and_expiry() {
+        let verifier = SignatureVerifier::new();
+        let auth = context(false);
+        let body = br#"{"events":[]}"#;
+        let headers = signed(NOW - 10, "nonce-0123456789ab", body);
+
+        assert!(matches!(
+            verifier.verify_at(&auth, &headers, body, NOW).await,
+            Ok(true)
+        ));
+        // Same nonce again
+        assert_eq!(
+            code(verifier.verify_at(&auth, &headers, body, NOW).await),
+            "AUTH_008"
+        );
+
+        let headers = signed(NOW, "nonce-tampered-body", body);
+        assert_eq!(
+            code(verifier.verify_at(&auth, &headers, b"{}", NOW).await),
+            "AUTH_007"
+        );
+
+        let headers = signed(NOW - REPLAY_WINDOW_SECS - 1, "nonce-too-old-00000", body);
+        assert_eq!(
+            code(verifier.verify_at(&auth, &headers, body, NOW).await),
+            "AUTH_008"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_requests_when_required() {
+        let verifier = SignatureVerifier::new();
+        let unsigned = HeaderMap::new();
+
+        assert!(matches!(
+            verifier
+                .verify_at(&context(false), &unsigned, b"{}", NOW)
+                .await,
+            Ok(false)
+        ));
+        assert_eq!(
+            code(
+                verifier
+                    .verify_at(&context(true), &unsigned, b"{}", NOW)
+                    .await
+            ),
+            "AUTH_007"
+        );
+
+        // Browser keys cannot keep a secret, so they are never required to sign
+        let mut browser = context(true);
+        browser.key_type = KeyType::Browser;
+        assert!(matches!(
+            verifier.verify_at(&browser, &unsigned, b"{}", NOW).await,
+            Ok(false)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_nonces_kept_for_window_and_capped() {
+        let verifier = SignatureVerifier::with_capacity(2, 2);
+        let auth = context(false);
+        let body = b"{}";
+
+        let first = signed(NOW, "nonce-first-0000000", body);
+        assert!(matches!(
+            verifier.verify_at(&auth, &first, body, NOW).await,
+            Ok(true)
+        ));
+        assert!(matches!(
+            verifier
+                .verify_at(&auth, &signed(NOW, "nonce-second-000000", body), body, NOW)
+                .await,
+            Ok(true)
+        ));
+
+        // At capacity: refused rather than forgetting a live nonce
+        let third = signed(NOW, "nonce-third-0000000", body);
+        assert_eq!(
+            code(verifier.verify_at(&auth, &third, body, NOW).await),
+            "AUTH_006"
+        );
+        // Still caught at the far edge of the window
+        let edge = NOW + REPLAY_WINDOW_SECS;
+        assert_eq!(
+            code(verifier.verify_at(&auth, &first, body, edge).await),
+            "AUTH_008"
+        );
+
+        // Once the first requests leave the window their nonces are dropped
+        let later = NOW + REPLAY_WINDOW_SECS + NONCE_BUCKET_SECS;
+        let third = signed(later, "nonce-third-0000000", body);
+        assert!(matches!(
+            verifier.verify_at(&auth, &third, body, later).await,
+            Ok(true)
+        ));
+        assert_eq!(verifier.nonces.lock().len, 1);
+    }
+}
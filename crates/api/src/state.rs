@@ -1,184 +1,256 @@
 //! Application state shared across handlers.
 
+use crate::auth_provider::{AuthProvider, DaemonAuthProvider, MockAuthProvider};
+use crate::mau::MauTracker;
 use crate::middleware::rate_limit::{RateLimitConfig, RateLimiter, SharedRateLimiter};
+use crate::middleware::rate_limit_cluster::ClusterBackend;
+use crate::signing::SignatureVerifier;
 use clickhouse_client::ClickHouseClient;
-use engine_core::{AuthRequest, AuthResponse, Error, ParsedApiKey};
-use moka::future::Cache;
+use engine_core::ip::default_trusted_proxies;
+use engine_core::{AuthErrorCode, AuthResponse, Cidr, Error, EventFilter, MauConfig, ParsedApiKey};
+use moka::{future::Cache, Expiry};
 use redpanda::EventProducer;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{debug, warn};
+use telemetry::metrics;
+use tracing::warn;
 
 /// Cache TTL for auth responses (30 seconds).
 const AUTH_CACHE_TTL: Duration = Duration::from_secs(30);
 
+/// Cache TTL for invalid-key responses, so newly created keys work quickly.
+const AUTH_NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// How long the last good response may be served while the auth service is down.
+const AUTH_STALE_GRACE: Duration = Duration::from_secs(300);
+
 /// Maximum cache entries.
 const AUTH_CACHE_MAX_CAPACITY: u64 = 10_000;
 
+/// Valid responses live for [`AUTH_CACHE_TTL`], invalid ones for
+/// [`AUTH_NEGATIVE_CACHE_TTL`].
+struct AuthExpiry;
+
+impl Expiry<String, AuthResponse> for AuthExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &AuthResponse,
+        _created_at: std::time::Instant,
+    ) -> Option<Duration> {
+        Some(if value.valid {
+            AUTH_CACHE_TTL
+        } else {
+            AUTH_NEGATIVE_CACHE_TTL
+        })
+    }
+}
+
+/// Cached feature flags, and whether they are the defaults served because
+/// the provider could not answer.
+#[derive(Clone)]
+struct CachedFeatures {
+    features: Features,
+    fallback: bool,
+}
+
+/// Fetched features live for [`AUTH_CACHE_TTL`], fallbacks for
+/// [`AUTH_NEGATIVE_CACHE_TTL`].
+struct FeaturesExpiry;
+
+impl Expiry<String, CachedFeatures> for FeaturesExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &CachedFeatures,
+        _created_at: std::time::Instant,
+    ) -> Option<Duration> {
+        Some(if value.fallback {
+            AUTH_NEGATIVE_CACHE_TTL
+        } else {
+            AUTH_CACHE_TTL
+        })
+    }
+}
+
 /// Auth service client.
 ///
-/// Calls the TS auth daemon's `/internal/auth/validate` endpoint.
-/// Caches responses for 30 seconds to reduce load on auth service.
+/// Validates keys with an [`AuthProvider`], by default the TS auth daemon.
+/// Caches valid responses for 30 seconds and invalid ones for 5, and
+/// coalesces concurrent lookups of the same key into one request. While
+/// the provider is unavailable (`AUTH_006`), keys it recently accepted, and
+/// has not since rejected, keep working from their last good response for
+/// up to 5 minutes.
 #[derive(Clone)]
 pub struct AuthClient {
-    /// Auth service URL (e.g., "http://auth-service:8080")
-    base_url: String,
-    /// HTTP client
-    http_client: reqwest::Client,
+    /// Where keys are validated
+    provider: Arc<dyn AuthProvider>,
     /// Auth response cache (API key hash -> AuthResponse)
     cache: Cache<String, AuthResponse>,
-    /// Whether to use mock mode (for testing)
-    mock_mode: bool,
+    /// Last valid response per key hash, served during outages
+    last_good: Cache<String, AuthResponse>,
+    /// Feature flag cache (project ID -> Features)
+    features_cache: Cache<String, CachedFeatures>,
+    /// Last fetched features per project, served during outages
+    last_good_features: Cache<String, Features>,
 }
 
 impl AuthClient {
-    /// Creates a new auth client.
+    /// Creates a client for the auth daemon at `base_url`, or a mock client
+    /// if it is empty or `"mock"`.
     pub fn new(base_url: impl Into<String>) -> Self {
         let base_url = base_url.into();
-        let mock_mode = base_url.is_empty() || base_url == "mock";
+        if base_url.is_empty() || base_url == "mock" {
+            Self::with_provider(Arc::new(MockAuthProvider))
+        } else {
+            Self::with_provider(Arc::new(DaemonAuthProvider::new(base_url)))
+        }
+    }
 
+    /// Creates a client validating keys with `provider`.
+    pub fn with_provider(provider: Arc<dyn AuthProvider>) -> Self {
         Self {
-            base_url,
-            http_client: reqwest::Client::builder()
-                .timeout(Duration::from_secs(5))
-                .build()
-                .expect("Failed to create HTTP client"),
+            provider,
             cache: Cache::builder()
                 .max_capacity(AUTH_CACHE_MAX_CAPACITY)
-                .time_to_live(AUTH_CACHE_TTL)
+                .expire_after(AuthExpiry)
+                .build(),
+            last_good: Cache::builder()
+                .max_capacity(AUTH_CACHE_MAX_CAPACITY)
+                .time_to_live(AUTH_STALE_GRACE)
+                .build(),
+            features_cache: Cache::builder()
+                .max_capacity(AUTH_CACHE_MAX_CAPACITY)
+                .expire_after(FeaturesExpiry)
+                .build(),
+            last_good_features: Cache::builder()
+                .max_capacity(AUTH_CACHE_MAX_CAPACITY)
+                .time_to_live(AUTH_STALE_GRACE)
                 .build(),
-            mock_mode,
         }
     }
 
+    /// Name of the provider, for logs.
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.name()
+    }
+
     /// Validate an API key with the auth service.
     ///
-    /// Returns cached response if available, otherwise calls the auth service.
+    /// Returns cached response if available, otherwise calls the provider.
+    /// If the provider is unavailable, the key's last good response is
+    /// served within the grace period. Other errors are returned as is.
     pub async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
-        let cache_key = api_key.as_str().to_string();
-
-        // Check cache first
-        if let Some(cached) = self.cache.get(&cache_key).await {
-            debug!("Auth cache hit");
-            return Ok(cached);
+        if !self.provider.cacheable() {
+            return self.provider.validate(api_key).await;
         }
 
-        // Get response (mock or real)
-        let response = if self.mock_mode {
-            self.mock_validate(api_key)
-        } else {
-            self.remote_validate(api_key).await?
-        };
-
-        // Cache the response
-        self.cache.insert(cache_key, response.clone()).await;
-
-        Ok(response)
-    }
-
-    /// Call the remote auth service.
-    async fn remote_validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
-        let url = format!("{}/internal/auth/validate", self.base_url);
-        let request = AuthRequest::write(api_key.as_str());
-
-        debug!(url = %url, "Calling auth service");
+        let cache_key = api_key.hash();
 
-        let response = self
-            .http_client
-            .post(&url)
-            .json(&request)
-            .send()
+        // Concurrent misses for the same key share one lookup; errors are not cached
+        let error = match self
+            .cache
+            .try_get_with(cache_key.clone(), self.lookup(api_key))
             .await
-            .map_err(|e| {
-                warn!(error = %e, "Auth service request failed");
-                Error::internal(format!("Auth service unavailable: {}", e))
-            })?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            warn!(status = %status, body = %body, "Auth service returned error");
-            return Err(Error::internal(format!(
-                "Auth service returned {}: {}",
-                status, body
-            )));
-        }
+        {
+            Ok(response) => return Ok(response),
+            Err(error) => error,
+        };
 
-        let auth_response: AuthResponse = response.json().await.map_err(|e| {
-            warn!(error = %e, "Failed to parse auth response");
-            Error::internal(format!("Invalid auth response: {}", e))
-        })?;
+        let unavailable = matches!(
+            &*error,
+            Error::Auth { code, .. } if *code == AuthErrorCode::ServiceUnavailable.code()
+        );
+        if unavailable {
+            if let Some(stale) = self.last_good.get(&cache_key).await {
+                metrics().auth_stale_responses.inc();
+                warn!(error = %error, "Auth lookup failed, serving last good response");
+                return Ok(stale);
+            }
+        }
 
-        Ok(auth_response)
+        Err(match &*error {
+            Error::Auth {
+                code,
+                message,
+                http_status,
+            } => Error::Auth {
+                code,
+                message: message.clone(),
+                http_status: *http_status,
+            },
+            other => Error::internal(other.to_string()),
+        })
     }
 
-    /// Mock validation for testing/development.
-    fn mock_validate(&self, api_key: &ParsedApiKey) -> AuthResponse {
-        debug!("Using mock auth validation");
-        AuthResponse {
-            valid: true,
-            project_id: Some(generate_mock_project_id(api_key)),
-            permissions: Some(vec!["read".into(), "write".into()]),
-            rate_limit: Some(1000),
-            allowed_origins: None,
-            error: None,
-            mau: None,
+    /// Fetch a response from the provider and remember it if valid, or
+    /// forget the last good one if the key is now rejected.
+    async fn lookup(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+        let response = self.provider.validate(api_key).await?;
+        if response.valid {
+            self.last_good
+                .insert(api_key.hash(), response.clone())
+                .await;
+        } else {
+            self.last_good.invalidate(&api_key.hash()).await;
         }
+        Ok(response)
     }
 
-    /// Invalidate cached auth response for an API key.
+    /// Invalidate cached auth responses for an API key.
     pub async fn invalidate(&self, api_key: &ParsedApiKey) {
-        self.cache.invalidate(&api_key.as_str().to_string()).await;
+        let cache_key = api_key.hash();
+        self.cache.invalidate(&cache_key).await;
+        self.last_good.invalidate(&cache_key).await;
     }
 
-    /// Fetch feature flags for a project.
+    /// Feature flags for the key's project, cached for the same TTL as auth
+    /// responses.
     ///
-    /// In mock mode, returns all features enabled except location.
-    pub async fn fetch_features(&self, api_key: &ParsedApiKey) -> Result<Features, Error> {
-        if self.mock_mode {
-            return Ok(Features::default());
+    /// If the provider cannot answer, the project's last fetched features
+    /// are served within the grace period, otherwise the defaults, so
+    /// ingestion keeps working without location data. Either fallback is
+    /// cached for the negative TTL to keep load off a failing service.
+    pub async fn features(&self, api_key: &ParsedApiKey, project_id: &str) -> Features {
+        if let Some(cached) = self.features_cache.get(project_id).await {
+            return cached.features;
         }
 
-        let url = format!("{}/features", self.base_url);
-
-        let response = self
-            .http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", api_key.as_str()))
-            .send()
-            .await
-            .map_err(|e| {
-                warn!(error = %e, "Features request failed");
-                Error::internal(format!("Features service unavailable: {}", e))
-            })?;
-
-        if !response.status().is_success() {
-            // Fall back to default features on error
-            warn!("Features endpoint returned error, using defaults");
-            return Ok(Features::default());
-        }
-
-        let features_response: FeaturesResponse = response.json().await.map_err(|e| {
-            warn!(error = %e, "Failed to parse features response");
-            Error::internal(format!("Invalid features response: {}", e))
-        })?;
+        let cached = match self.provider.features(api_key).await {
+            Ok(features) => {
+                self.last_good_features
+                    .insert(project_id.to_string(), features.clone())
+                    .await;
+                CachedFeatures {
+                    features,
+                    fallback: false,
+                }
+            }
+            Err(e) => {
+                let features = match self.last_good_features.get(project_id).await {
+                    Some(stale) => {
+                        warn!(project_id = %project_id, error = %e, "Serving last good features");
+                        stale
+                    }
+                    None => {
+                        warn!(project_id = %project_id, error = %e, "Using default features");
+                        Features::default()
+                    }
+                };
+                CachedFeatures {
+                    features,
+                    fallback: true,
+                }
+            }
+        };
 
-        Ok(features_response.features)
+        self.features_cache
+            .insert(project_id.to_string(), cached.clone())
+            .await;
+        cached.features
     }
 }
 
-/// Generate a deterministic mock project ID from the API key.
-/// This is for testing only - in production, the auth service provides this.
-fn generate_mock_project_id(api_key: &ParsedApiKey) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-
-    let mut hasher = DefaultHasher::new();
-    api_key.as_str().hash(&mut hasher);
-    let hash = hasher.finish();
-    format!("proj-{:016x}", hash)
-}
-
 /// Feature flags response from the auth service.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -188,7 +260,7 @@ pub struct FeaturesResponse {
 }
 
 /// Feature flags for a project.
-#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Features {
     // Core analytics features
@@ -232,6 +304,38 @@ fn default_true() -> bool {
     true
 }
 
+/// Everything enabled except location, matching a missing field.
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            pageviews: true,
+            clicks: true,
+            scrolling: true,
+            mouse_move: true,
+            forms: true,
+            performance: true,
+            errors: true,
+            visibility: true,
+            resources: true,
+            location: false,
+            exit_intent: true,
+            idle_detection: true,
+            engagement_scoring: true,
+            triggers: true,
+        }
+    }
+}
+
+impl EventFilter for Features {
+    fn is_event_enabled(&self, event_type: &str) -> bool {
+        Features::is_event_enabled(self, event_type)
+    }
+
+    fn keep_location(&self) -> bool {
+        self.location
+    }
+}
+
 impl Features {
     /// Check if a given event type is enabled.
     pub fn is_event_enabled(&self, event_type: &str) -> bool {
@@ -271,12 +375,24 @@ impl Features {
 pub struct AppState {
     /// Event producer (Redpanda in production, mock in tests)
     pub producer: Arc<dyn EventProducer>,
+    /// Producer for test-key events; they are not stored when unset
+    pub sandbox: Option<Arc<dyn EventProducer>>,
     /// ClickHouse client
     pub clickhouse: Arc<ClickHouseClient>,
     /// Auth service client
     pub auth_client: AuthClient,
     /// Rate limiter
     pub rate_limiter: SharedRateLimiter,
+    /// Cluster rate limit backend, when usage is shared with peers
+    pub rate_limit_cluster: Option<Arc<ClusterBackend>>,
+    /// Reject ingestion with 503 once consumer lag exceeds this many events
+    pub max_consumer_lag: Option<u64>,
+    /// MAU estimates and over-limit handling
+    pub mau: Arc<MauTracker>,
+    /// Request signature checks and seen nonces
+    pub signatures: Arc<SignatureVerifier>,
+    /// Proxies whose `X-Forwarded-For` entries are believed
+    pub trusted_proxies: Arc<Vec<Cidr>>,
 }
 
 impl AppState {
@@ -287,9 +403,15 @@ impl AppState {
     ) -> Self {
         Self {
             producer,
+            sandbox: None,
             clickhouse,
             auth_client: AuthClient::new(auth_url),
             rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::default())),
+            rate_limit_cluster: None,
+            max_consumer_lag: None,
+            mau: Arc::new(MauTracker::new(MauConfig::default())),
+            signatures: Arc::new(SignatureVerifier::new()),
+            trusted_proxies: Arc::new(default_trusted_proxies()),
         }
     }
 
@@ -302,12 +424,58 @@ impl AppState {
     ) -> Self {
         Self {
             producer,
+            sandbox: None,
             clickhouse,
             auth_client: AuthClient::new(auth_url),
             rate_limiter: Arc::new(RateLimiter::new(rate_config)),
+            rate_limit_cluster: None,
+            max_consumer_lag: None,
+            mau: Arc::new(MauTracker::new(MauConfig::default())),
+            signatures: Arc::new(SignatureVerifier::new()),
+            trusted_proxies: Arc::new(default_trusted_proxies()),
         }
     }
 
+    /// Share rate limit usage and signed request nonces with peer replicas
+    /// through `cluster`.
+    pub fn with_rate_limit_cluster(mut self, cluster: Arc<ClusterBackend>) -> Self {
+        let config = self.rate_limiter.config().clone();
+        self.rate_limiter = Arc::new(RateLimiter::with_backend(config, cluster.clone()));
+        self.signatures = Arc::new(SignatureVerifier::new().with_cluster(cluster.clone()));
+        self.rate_limit_cluster = Some(cluster);
+        self
+    }
+
+    /// Shed ingestion load once the broker backlog passes `max_lag` events.
+    pub fn with_max_consumer_lag(mut self, max_lag: Option<u64>) -> Self {
+        self.max_consumer_lag = max_lag;
+        self
+    }
+
+    /// Send events from `owk_test_` keys to `producer`.
+    pub fn with_sandbox(mut self, producer: Arc<dyn EventProducer>) -> Self {
+        self.sandbox = Some(producer);
+        self
+    }
+
+    /// Validate keys with `provider` instead of the auth daemon.
+    pub fn with_auth_provider(mut self, provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_client = AuthClient::with_provider(provider);
+        self
+    }
+
+    /// Resolve client IPs through `proxies` instead of the private ranges.
+    pub fn with_trusted_proxies(mut self, proxies: Vec<Cidr>) -> Self {
+        self.trusted_proxies = Arc::new(proxies);
+        self
+    }
+
+    /// Use per-plan MAU over-limit behavior.
+    pub fn with_mau(mut self, config: MauConfig) -> Self {
+        self.mau = Arc::new(MauTracker::new(config));
+        self
+    }
+
     /// Start the rate limiter cleanup background task.
     /// Returns a handle that can be used to cancel the task.
     pub fn start_rate_limiter_cleanup(&self) -> tokio::task::JoinHandle<()> {
@@ -316,8 +484,163 @@ impl AppState {
             let mut interval = tokio::time::interval(Duration::from_secs(300)); // 5 minutes
             loop {
                 interval.tick().await;
-                rate_limiter.cleanup_stale();
+                rate_limiter.cleanup_stale().await;
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_outage_serves_last_good_response() {
+        // Nothing listens on port 1
+        let client = AuthClient::new("http://127.0.0.1:1");
+        let known = ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap();
+        let unknown = ParsedApiKey::parse("owk_live_ZZZ123xyz789DEF456ghi012JKL345mn").unwrap();
+
+        let mut response = MockAuthProvider.validate(&known).await.unwrap();
+        response.project_id = Some("proj-known".into());
+        client.last_good.insert(known.hash(), response).await;
+
+        let served = client.validate(&known).await.unwrap();
+        assert_eq!(served.project_id.as_deref(), Some("proj-known"));
+
+        let err = client.validate(&unknown).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Auth {
+                code: "AUTH_006",
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_features_fallback_is_cached() {
+        let client = AuthClient::new("http://127.0.0.1:1");
+        let key = ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap();
+
+        let features = client.features(&key, "proj-new").await;
+        assert!(!features.location);
+        let cached = client.features_cache.get("proj-new").await.unwrap();
+        assert!(cached.fallback);
+
+        // A project with known features keeps them during the outage
+        let known = Features {
+            location: true,
+            ..Features::default()
+        };
+        client
+            .last_good_features
+            .insert("proj-known".to_string(), known)
+            .await;
+        assert!(client.features(&key, "proj-known").await.location);
+    }
+
+    /// Provider that answers with queued results.
+    struct ScriptedProvider {
+        results: parking_lot::Mutex<std::collections::VecDeque<Result<AuthResponse, Error>>>,
+    }
+
+    #[axum::async_trait]
+    impl AuthProvider for ScriptedProvider {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        async fn validate(&self, _api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+            self.results.lock().pop_front().expect("unexpected lookup")
+        }
+
+        async fn features(&self, _api_key: &ParsedApiKey) -> Result<Features, Error> {
+            Ok(Features::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_responses_only_for_unavailable_provider() {
+        let key = ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap();
+        let valid = MockAuthProvider.validate(&key).await.unwrap();
+        let unavailable = || Error::auth(AuthErrorCode::ServiceUnavailable, "down");
+        let provider = Arc::new(ScriptedProvider {
+            results: parking_lot::Mutex::new(
+                [
+                    Ok(valid.clone()),
+                    Err(Error::internal("Auth service returned 400")),
+                    Err(unavailable()),
+                    Ok(AuthResponse::invalid(AuthErrorCode::Revoked, "revoked")),
+                    Err(unavailable()),
+                ]
+                .into(),
+            ),
+        });
+        let client = AuthClient::with_provider(provider);
+        let lookup = || async {
+            client.cache.invalidate(&key.hash()).await;
+            client.validate(&key).await
+        };
+
+        assert!(lookup().await.unwrap().valid);
+        // A rejected request is not an outage
+        assert!(matches!(lookup().await, Err(Error::Internal(_))));
+        assert!(lookup().await.unwrap().valid);
+        // Once revoked, the last good response is gone
+        assert!(!lookup().await.unwrap().valid);
+        assert!(matches!(
+            lookup().await,
+            Err(Error::Auth {
+                code: "AUTH_006",
+                ..
+            })
+        ));
+    }
+
+    /// Provider that counts lookups.
+    struct CountingProvider {
+        cacheable: bool,
+        lookups: std::sync::atomic::AtomicUsize,
+    }
+
+    #[axum::async_trait]
+    impl AuthProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn validate(&self, api_key: &ParsedApiKey) -> Result<AuthResponse, Error> {
+            self.lookups
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            MockAuthProvider.validate(api_key).await
+        }
+
+        async fn features(&self, _api_key: &ParsedApiKey) -> Result<Features, Error> {
+            Ok(Features::default())
+        }
+
+        fn cacheable(&self) -> bool {
+            self.cacheable
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uncacheable_provider_skips_cache() {
+        let key = ParsedApiKey::parse("owk_live_ABC123xyz789DEF456ghi012JKL345mn").unwrap();
+        for (cacheable, expected) in [(true, 1), (false, 3)] {
+            let provider = Arc::new(CountingProvider {
+                cacheable,
+                lookups: Default::default(),
+            });
+            let client = AuthClient::with_provider(provider.clone());
+            for _ in 0..3 {
+                assert!(client.validate(&key).await.unwrap().valid);
+            }
+            assert_eq!(
+                provider.lookups.load(std::sync::atomic::Ordering::SeqCst),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Fixtures shared by unit tests.
+
+use axum::{async_trait, Router};
+use clickhouse_client::{ClickHouseClient, ClickHouseConfig};
+use engine_core::ClickHouseEvent;
+use redpanda::{EventProducer, SendResult};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::state::AppState;
+
+/// Producer that counts the events sent to it.
+#[derive(Default)]
+pub struct CountingProducer {
+    events: AtomicUsize,
+}
+
+impl CountingProducer {
+    pub fn count(&self) -> usize {
+        self.events.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl EventProducer for CountingProducer {
+    async fn send_clickhouse_events(
+        &self,
+        events: Vec<ClickHouseEvent>,
+    ) -> engine_core::Result<SendResult> {
+        self.events.fetch_add(events.len(), Ordering::SeqCst);
+        Ok(SendResult {
+            events_sent: events.len(),
+            errors: vec![],
+        })
+    }
+
+    fn is_healthy(&self) -> bool {
+        true
+    }
+}
+
+/// State with mock auth and an unreachable ClickHouse.
+pub fn state(producer: Arc<CountingProducer>) -> AppState {
+    let clickhouse = Arc::new(ClickHouseClient::new(ClickHouseConfig::default()).unwrap());
+    AppState::new(producer, clickhouse, "mock")
+}
+
+/// Serve `router` on a local port and return its base URL.
+pub async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", addr)
+}
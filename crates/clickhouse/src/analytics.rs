@@ -0,0 +1,694 @@
+//! Analytics read queries for dashboards.
+//!
+//! Every query is scoped to a single project and a `[from, to)` time range.
+//! User input only ever reaches ClickHouse through `.bind()`; the few SQL
+//! fragments that vary (bucket function, breakdown column, vital key, actor
+//! key) come from closed enums, and funnel step conditions are fixed
+//! templates whose values are bound - never built from request strings.
+
+use crate::client::ClickHouseClient;
+use clickhouse::Row;
+use engine_core::Result;
+use serde::{Deserialize, Serialize};
+
+/// Maximum rows returned by top-N queries.
+pub const MAX_QUERY_LIMIT: u32 = 1000;
+
+/// Maximum steps in a funnel.
+pub const MAX_FUNNEL_STEPS: usize = 10;
+
+/// Maximum funnel conversion window (30 days).
+pub const MAX_FUNNEL_WINDOW_SECS: u64 = 30 * 24 * 3600;
+
+/// Time range filter in Unix milliseconds (`from` inclusive, `to` exclusive).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub from: i64,
+    pub to: i64,
+}
+
+impl TimeRange {
+    /// Validates that the range is non-empty.
+    pub fn validate(&self) -> Result<()> {
+        if self.from >= self.to {
+            return Err(engine_core::Error::validation(
+                "time range 'from' must be before 'to'",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Time-series bucket size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Interval {
+    /// Bucket start expression, as a DateTime.
+    fn bucket_expr(&self) -> &'static str {
+        match self {
+            Self::Minute => "toStartOfMinute(timestamp)",
+            Self::Hour => "toStartOfHour(timestamp)",
+            Self::Day => "toDateTime(toStartOfDay(timestamp))",
+            Self::Week => "toDateTime(toMonday(timestamp))",
+            Self::Month => "toDateTime(toStartOfMonth(timestamp))",
+        }
+    }
+}
+
+/// Dimension for breakdown queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Breakdown {
+    Device,
+    Browser,
+    Os,
+    Country,
+}
+
+impl Breakdown {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Device => "device_type",
+            Self::Browser => "browser",
+            Self::Os => "os",
+            Self::Country => "country",
+        }
+    }
+}
+
+/// Web vital stored on `performance` events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebVital {
+    Lcp,
+    Fcp,
+    Fid,
+    Inp,
+    Cls,
+    Ttfb,
+}
+
+impl WebVital {
+    /// Key of the metric inside the event `data` JSON.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Lcp => "lcp",
+            Self::Fcp => "fcp",
+            Self::Fid => "fid",
+            Self::Inp => "inp",
+            Self::Cls => "cls",
+            Self::Ttfb => "ttfb",
+        }
+    }
+}
+
+/// Identity used to follow visitors across events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActorKey {
+    /// Identified users only (`user_id IS NOT NULL`)
+    User,
+    /// Every session, anonymous or not
+    Session,
+}
+
+impl ActorKey {
+    fn expr(&self) -> &'static str {
+        match self {
+            Self::User => "assumeNotNull(user_id)",
+            Self::Session => "session_id",
+        }
+    }
+
+    fn filter(&self) -> &'static str {
+        match self {
+            Self::User => "AND user_id IS NOT NULL",
+            Self::Session => "",
+        }
+    }
+}
+
+/// One funnel step: an event type, optionally narrowed by path or custom name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunnelStep {
+    pub event_type: String,
+    pub path: Option<String>,
+    pub custom_name: Option<String>,
+}
+
+impl FunnelStep {
+    /// Condition with `?` placeholders, bound by [`FunnelStep::bind`].
+    fn condition(&self) -> String {
+        let mut cond = String::from("(event_type = ?");
+        if self.path.is_some() {
+            cond.push_str(" AND path = ?");
+        }
+        if self.custom_name.is_some() {
+            cond.push_str(" AND custom_name = ?");
+        }
+        cond.push(')');
+        cond
+    }
+
+    fn bind(&self, mut query: clickhouse::query::Query) -> clickhouse::query::Query {
+        query = query.bind(self.event_type.as_str());
+        if let Some(path) = &self.path {
+            query = query.bind(path.as_str());
+        }
+        if let Some(custom_name) = &self.custom_name {
+            query = query.bind(custom_name.as_str());
+        }
+        query
+    }
+}
+
+/// Conversion through one funnel step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunnelStepResult {
+    /// 1-based step number
+    pub step: usize,
+    /// Actors who reached this step within the window
+    pub actors: u64,
+    /// Share of actors who entered the funnel (step 1)
+    pub conversion_rate: f64,
+    /// Share of actors from the previous step
+    pub step_conversion_rate: f64,
+}
+
+/// Actors active in each week after their cohort week.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionCohort {
+    /// Cohort week start (Monday, Unix ms)
+    pub cohort: i64,
+    /// Actors whose first active week in range is `cohort`
+    pub size: u64,
+    /// `retained[n]` = actors active `n` weeks after the cohort week
+    pub retained: Vec<u64>,
+}
+
+/// Event count for one bucket and event type.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct TimeSeriesPoint {
+    /// Bucket start (Unix ms)
+    pub bucket: i64,
+    pub event_type: String,
+    pub count: u64,
+}
+
+/// Pageviews per path.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct PageCount {
+    pub path: String,
+    pub views: u64,
+    pub visitors: u64,
+}
+
+/// Visits per referring domain.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct ReferrerCount {
+    pub referrer: String,
+    pub visits: u64,
+    pub visitors: u64,
+}
+
+/// Unique visitor totals.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct VisitorStats {
+    /// Distinct `user_id`, falling back to `session_id` for anonymous traffic
+    pub visitors: u64,
+    pub sessions: u64,
+    /// Distinct identified users
+    pub users: u64,
+}
+
+/// Event and visitor counts for one breakdown value.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct BreakdownCount {
+    pub value: String,
+    pub events: u64,
+    pub visitors: u64,
+}
+
+/// Percentiles of one web vital for a path.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct WebVitalPercentiles {
+    pub path: String,
+    pub samples: u64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+/// Shared project + time filter; binds `project_id, from, to` in that order.
+const SCOPE: &str = "project_id = ? \
+    AND timestamp >= fromUnixTimestamp64Milli(?) \
+    AND timestamp < fromUnixTimestamp64Milli(?)";
+
+fn query_error(e: clickhouse::error::Error) -> engine_core::Error {
+    engine_core::Error::internal(format!("Query error: {}", e))
+}
+
+/// Event counts per bucket and event type, optionally for a single type.
+pub async fn event_time_series(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    interval: Interval,
+    event_type: Option<&str>,
+) -> Result<Vec<TimeSeriesPoint>> {
+    range.validate()?;
+
+    let type_filter = if event_type.is_some() {
+        "AND event_type = ?"
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT toInt64(toUnixTimestamp({bucket})) * 1000 AS bucket, \
+                toString(event_type) AS event_type, count() AS count \
+         FROM overwatch.events \
+         WHERE {SCOPE} {type_filter} \
+         GROUP BY bucket, event_type \
+         ORDER BY bucket, event_type",
+        bucket = interval.bucket_expr(),
+    );
+
+    let mut query = client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to);
+    if let Some(event_type) = event_type {
+        query = query.bind(event_type);
+    }
+
+    query.fetch_all().await.map_err(query_error)
+}
+
+/// Most viewed paths.
+pub async fn top_pages(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    limit: u32,
+) -> Result<Vec<PageCount>> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT path, count() AS views, \
+                uniq(ifNull(user_id, session_id)) AS visitors \
+         FROM overwatch.events \
+         WHERE {SCOPE} AND event_type = 'pageview' \
+         GROUP BY path \
+         ORDER BY views DESC \
+         LIMIT ?"
+    );
+
+    client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .bind(limit.min(MAX_QUERY_LIMIT))
+        .fetch_all()
+        .await
+        .map_err(query_error)
+}
+
+/// Top referring domains, excluding direct traffic.
+pub async fn top_referrers(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    limit: u32,
+) -> Result<Vec<ReferrerCount>> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT domainWithoutWWW(referrer) AS referrer, count() AS visits, \
+                uniq(ifNull(user_id, session_id)) AS visitors \
+         FROM overwatch.events \
+         WHERE {SCOPE} AND event_type = 'pageview' AND referrer != '' \
+         GROUP BY referrer \
+         HAVING referrer != '' \
+         ORDER BY visits DESC \
+         LIMIT ?"
+    );
+
+    client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .bind(limit.min(MAX_QUERY_LIMIT))
+        .fetch_all()
+        .await
+        .map_err(query_error)
+}
+
+/// Unique visitors, sessions and identified users.
+pub async fn unique_visitors(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+) -> Result<VisitorStats> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT uniq(ifNull(user_id, session_id)) AS visitors, \
+                uniq(session_id) AS sessions, \
+                uniqIf(user_id, isNotNull(user_id)) AS users \
+         FROM overwatch.events \
+         WHERE {SCOPE}"
+    );
+
+    client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_one()
+        .await
+        .map_err(query_error)
+}
+
+/// Event and visitor counts grouped by a client dimension.
+pub async fn breakdown(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    dimension: Breakdown,
+    limit: u32,
+) -> Result<Vec<BreakdownCount>> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT toString({column}) AS value, count() AS events, \
+                uniq(ifNull(user_id, session_id)) AS visitors \
+         FROM overwatch.events \
+         WHERE {SCOPE} \
+         GROUP BY value \
+         ORDER BY events DESC \
+         LIMIT ?",
+        column = dimension.column(),
+    );
+
+    client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .bind(limit.min(MAX_QUERY_LIMIT))
+        .fetch_all()
+        .await
+        .map_err(query_error)
+}
+
+/// p50/p75/p95 of a web vital per path, busiest paths first.
+pub async fn web_vitals(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    vital: WebVital,
+    limit: u32,
+) -> Result<Vec<WebVitalPercentiles>> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT path, count() AS samples, \
+                quantile(0.5)(value) AS p50, \
+                quantile(0.75)(value) AS p75, \
+                quantile(0.95)(value) AS p95 \
+         FROM ( \
+             SELECT path, JSONExtractFloat(data, '{key}') AS value \
+             FROM overwatch.events \
+             WHERE {SCOPE} AND event_type = 'performance' AND JSONHas(data, '{key}') \
+         ) \
+         GROUP BY path \
+         ORDER BY samples DESC \
+         LIMIT ?",
+        key = vital.key(),
+    );
+
+    client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .bind(limit.min(MAX_QUERY_LIMIT))
+        .fetch_all()
+        .await
+        .map_err(query_error)
+}
+
+#[derive(Debug, Row, Deserialize)]
+struct FunnelLevelRow {
+    level: u8,
+    actors: u64,
+}
+
+/// Ordered funnel conversion using `windowFunnel`.
+///
+/// An actor reaches step N if they hit steps 1..N in order, all within
+/// `window_secs` of step 1.
+pub async fn funnel(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    steps: &[FunnelStep],
+    window_secs: u64,
+    key: ActorKey,
+) -> Result<Vec<FunnelStepResult>> {
+    range.validate()?;
+    if steps.len() < 2 || steps.len() > MAX_FUNNEL_STEPS {
+        return Err(engine_core::Error::validation(format!(
+            "funnel must have between 2 and {} steps",
+            MAX_FUNNEL_STEPS
+        )));
+    }
+    if window_secs == 0 || window_secs > MAX_FUNNEL_WINDOW_SECS {
+        return Err(engine_core::Error::validation(format!(
+            "funnel window must be between 1 and {} seconds",
+            MAX_FUNNEL_WINDOW_SECS
+        )));
+    }
+
+    let conditions: Vec<String> = steps.iter().map(FunnelStep::condition).collect();
+    let sql = format!(
+        "SELECT level, count() AS actors \
+         FROM ( \
+             SELECT {actor} AS actor, \
+                    windowFunnel(?)(toDateTime(timestamp), {conditions}) AS level \
+             FROM overwatch.events \
+             WHERE {SCOPE} {actor_filter} \
+             GROUP BY actor \
+         ) \
+         WHERE level > 0 \
+         GROUP BY level \
+         ORDER BY level",
+        actor = key.expr(),
+        conditions = conditions.join(", "),
+        actor_filter = key.filter(),
+    );
+
+    let mut query = client.inner().query(&sql).bind(window_secs);
+    for step in steps {
+        query = step.bind(query);
+    }
+    let rows: Vec<FunnelLevelRow> = query
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all()
+        .await
+        .map_err(query_error)?;
+
+    let levels: Vec<(u8, u64)> = rows.into_iter().map(|r| (r.level, r.actors)).collect();
+    Ok(funnel_conversions(&levels, steps.len()))
+}
+
+/// Turn `(max level reached, actors)` counts into per-step conversion.
+fn funnel_conversions(levels: &[(u8, u64)], step_count: usize) -> Vec<FunnelStepResult> {
+    let reached: Vec<u64> = (1..=step_count)
+        .map(|step| {
+            levels
+                .iter()
+                .filter(|(level, _)| *level as usize >= step)
+                .map(|(_, actors)| actors)
+                .sum()
+        })
+        .collect();
+
+    let entered = reached.first().copied().unwrap_or(0);
+    reached
+        .iter()
+        .enumerate()
+        .map(|(i, &actors)| {
+            let previous = if i == 0 { entered } else { reached[i - 1] };
+            FunnelStepResult {
+                step: i + 1,
+                actors,
+                conversion_rate: ratio(actors, entered),
+                step_conversion_rate: ratio(actors, previous),
+            }
+        })
+        .collect()
+}
+
+fn ratio(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+#[derive(Debug, Row, Deserialize)]
+struct RetentionCellRow {
+    cohort: i64,
+    week: u32,
+    actors: u64,
+}
+
+/// Weekly retention cohorts.
+///
+/// Actors are grouped by their first active week (Monday-based) within the
+/// range; each cohort then counts how many were active N weeks later.
+pub async fn retention_cohorts(
+    client: &ClickHouseClient,
+    project_id: &str,
+    range: TimeRange,
+    key: ActorKey,
+) -> Result<Vec<RetentionCohort>> {
+    range.validate()?;
+
+    let sql = format!(
+        "SELECT toInt64(toUnixTimestamp(toDateTime(cohort_week))) * 1000 AS cohort, \
+                week, count() AS actors \
+         FROM ( \
+             SELECT {actor} AS actor, \
+                    groupUniqArray(toMonday(timestamp)) AS weeks, \
+                    arrayMin(weeks) AS cohort_week \
+             FROM overwatch.events \
+             WHERE {SCOPE} {actor_filter} \
+             GROUP BY actor \
+         ) \
+         ARRAY JOIN arrayMap(w -> toUInt32(intDiv(dateDiff('day', cohort_week, w), 7)), weeks) AS week \
+         GROUP BY cohort, week \
+         ORDER BY cohort, week",
+        actor = key.expr(),
+        actor_filter = key.filter(),
+    );
+
+    let rows: Vec<RetentionCellRow> = client
+        .inner()
+        .query(&sql)
+        .bind(project_id)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all()
+        .await
+        .map_err(query_error)?;
+
+    let cells: Vec<(i64, u32, u64)> = rows
+        .into_iter()
+        .map(|r| (r.cohort, r.week, r.actors))
+        .collect();
+    Ok(build_cohorts(&cells))
+}
+
+/// Fold `(cohort, week, actors)` cells (sorted by cohort) into cohort rows.
+fn build_cohorts(cells: &[(i64, u32, u64)]) -> Vec<RetentionCohort> {
+    let mut cohorts: Vec<RetentionCohort> = Vec::new();
+
+    for &(cohort, week, actors) in cells {
+        if cohorts.last().map(|c| c.cohort) != Some(cohort) {
+            cohorts.push(RetentionCohort {
+                cohort,
+                size: 0,
+                retained: Vec::new(),
+            });
+        }
+        let entry = cohorts.last_mut().expect("cohort just pushed");
+        let week = week as usize;
+        if entry.retained.len() <= week {
+            entry.retained.resize(week + 1, 0);
+        }
+        entry.retained[week] = actors;
+        if week == 0 {
+            entry.size = actors;
+        }
+    }
+
+    cohorts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_range_validate() {
+        assert!(TimeRange { from: 1, to: 2 }.validate().is_ok());
+        assert!(TimeRange { from: 2, to: 2 }.validate().is_err());
+        assert!(TimeRange { from: 3, to: 2 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_enums_deserialize_lowercase() {
+        let interval: Interval = serde_json::from_str("\"hour\"").unwrap();
+        assert_eq!(interval, Interval::Hour);
+        let dim: Breakdown = serde_json::from_str("\"country\"").unwrap();
+        assert_eq!(dim.column(), "country");
+        let vital: WebVital = serde_json::from_str("\"ttfb\"").unwrap();
+        assert_eq!(vital.key(), "ttfb");
+        assert!(serde_json::from_str::<Breakdown>("\"user_agent\"").is_err());
+    }
+
+    #[test]
+    fn test_funnel_step_condition() {
+        let step = FunnelStep {
+            event_type: "pageview".into(),
+            path: Some("/pricing".into()),
+            custom_name: None,
+        };
+        assert_eq!(step.condition(), "(event_type = ? AND path = ?)");
+    }
+
+    #[test]
+    fn test_funnel_conversions() {
+        // 50 stopped at step 1, 30 at step 2, 20 completed all three
+        let results = funnel_conversions(&[(1, 50), (2, 30), (3, 20)], 3);
+        let actors: Vec<u64> = results.iter().map(|r| r.actors).collect();
+        assert_eq!(actors, vec![100, 50, 20]);
+        assert_eq!(results[1].conversion_rate, 0.5);
+        assert_eq!(results[2].step_conversion_rate, 0.4);
+
+        let empty = funnel_conversions(&[], 2);
+        assert_eq!(empty[0].actors, 0);
+        assert_eq!(empty[1].conversion_rate, 0.0);
+    }
+
+    #[test]
+    fn test_build_cohorts() {
+        let cohorts = build_cohorts(&[(1000, 0, 10), (1000, 2, 3), (2000, 0, 5)]);
+        assert_eq!(cohorts.len(), 2);
+        assert_eq!(cohorts[0].size, 10);
+        assert_eq!(cohorts[0].retained, vec![10, 0, 3]);
+        assert_eq!(cohorts[1].retained, vec![5]);
+    }
+}
@@ -20,6 +20,50 @@ pub struct ClickHouseConfig {
     /// Query timeout in seconds
     #[serde(default = "default_timeout_secs")]
     pub timeout_secs: u64,
+    /// Maximum rows per INSERT before the streaming inserter cuts a new one
+    #[serde(default = "default_insert_max_rows")]
+    pub insert_max_rows: usize,
+    /// Maximum RowBinary bytes per INSERT before the streaming inserter cuts a new one
+    #[serde(default = "default_insert_max_bytes")]
+    pub insert_max_bytes: usize,
+    /// Use server-side async inserts (`async_insert=1, wait_for_async_insert=1`)
+    #[serde(default)]
+    pub async_insert: bool,
+    /// Typed columns on `overwatch.events` computed from `data` at insert time
+    #[serde(default = "default_materialized_columns")]
+    pub materialized_columns: Vec<MaterializedColumn>,
+}
+
+/// Type of a column materialized from the `data` JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaterializedType {
+    /// `Nullable(Float64)`, NULL when the key is absent
+    Float64,
+    /// `String`, empty when the key is absent
+    String,
+}
+
+/// A typed `overwatch.events` column extracted from `data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterializedColumn {
+    /// Column name (e.g. `scroll_depth`)
+    pub name: String,
+    /// JSON key in `data`; dots address nested objects (e.g. `metrics.lcp`)
+    pub json_key: String,
+    /// Column type
+    #[serde(rename = "type")]
+    pub column_type: MaterializedType,
+}
+
+impl MaterializedColumn {
+    pub fn new(name: &str, json_key: &str, column_type: MaterializedType) -> Self {
+        Self {
+            name: name.to_string(),
+            json_key: json_key.to_string(),
+            column_type,
+        }
+    }
 }
 
 fn default_database() -> String {
@@ -34,6 +78,27 @@ fn default_timeout_secs() -> u64 {
     30
 }
 
+fn default_insert_max_rows() -> usize {
+    100_000
+}
+
+fn default_insert_max_bytes() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+fn default_materialized_columns() -> Vec<MaterializedColumn> {
+    vec![
+        MaterializedColumn::new("scroll_depth", "scrollDepth", MaterializedType::Float64),
+        MaterializedColumn::new("lcp", "lcp", MaterializedType::Float64),
+        MaterializedColumn::new("trigger_id", "triggerId", MaterializedType::String),
+        MaterializedColumn::new(
+            "engagement_score",
+            "engagementScore",
+            MaterializedType::Float64,
+        ),
+    ]
+}
+
 impl Default for ClickHouseConfig {
     fn default() -> Self {
         Self {
@@ -43,6 +108,10 @@ impl Default for ClickHouseConfig {
             password: None,
             pool_size: default_pool_size(),
             timeout_secs: default_timeout_secs(),
+            insert_max_rows: default_insert_max_rows(),
+            insert_max_bytes: default_insert_max_bytes(),
+            async_insert: false,
+            materialized_columns: default_materialized_columns(),
         }
     }
 }
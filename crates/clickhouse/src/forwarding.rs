@@ -0,0 +1,242 @@
+//! Delivery log and dead-letter queue for customer event forwarding.
+//!
+//! Every batch sent to a forwarding destination is recorded in
+//! `overwatch.forwarding_deliveries`. Batches that exhaust their retries are
+//! also stored with their payload in `overwatch.forwarding_dlq`, so they can
+//! be inspected and replayed per destination. Each destination's position
+//! in the event stream is kept in `overwatch.forwarding_offsets`.
+
+use crate::client::ClickHouseClient;
+use clickhouse::Row;
+use engine_core::Result;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a forwarded batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Delivered,
+    DeadLettered,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delivered => "delivered",
+            Self::DeadLettered => "dead_lettered",
+        }
+    }
+}
+
+/// One forwarded batch, as stored in `overwatch.forwarding_deliveries`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct Delivery {
+    pub delivery_id: String,
+    pub project_id: String,
+    pub destination: String,
+    /// `delivered` or `dead_lettered`
+    pub status: String,
+    pub events: u32,
+    pub attempts: u32,
+    /// Last HTTP status, 0 if no response was received
+    pub http_status: u16,
+    /// Last error, empty when delivered
+    pub error: String,
+    pub duration_ms: u64,
+    /// Creation time (Unix ms)
+    pub created_at: i64,
+}
+
+/// A batch that exhausted its retries, as stored in `overwatch.forwarding_dlq`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub delivery_id: String,
+    pub project_id: String,
+    pub destination: String,
+    pub url: String,
+    /// Request body that was sent
+    pub payload: String,
+    pub events: u32,
+    pub attempts: u32,
+    pub error: String,
+    /// Failure time (Unix ms)
+    pub failed_at: i64,
+}
+
+/// Record a forwarded batch.
+pub async fn record_delivery(client: &ClickHouseClient, delivery: &Delivery) -> Result<()> {
+    client
+        .inner()
+        .query(
+            "INSERT INTO overwatch.forwarding_deliveries \
+             (delivery_id, project_id, destination, status, events, attempts, http_status, \
+              error, duration_ms, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, fromUnixTimestamp64Milli(?))",
+        )
+        .bind(delivery.delivery_id.as_str())
+        .bind(delivery.project_id.as_str())
+        .bind(delivery.destination.as_str())
+        .bind(delivery.status.as_str())
+        .bind(delivery.events)
+        .bind(delivery.attempts)
+        .bind(delivery.http_status)
+        .bind(delivery.error.as_str())
+        .bind(delivery.duration_ms)
+        .bind(delivery.created_at)
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Delivery insert error: {}", e)))
+}
+
+/// Store a batch in the dead-letter queue.
+pub async fn record_dead_letter(client: &ClickHouseClient, dead_letter: &DeadLetter) -> Result<()> {
+    client
+        .inner()
+        .query(
+            "INSERT INTO overwatch.forwarding_dlq \
+             (delivery_id, project_id, destination, url, payload, events, attempts, error, \
+              failed_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, fromUnixTimestamp64Milli(?))",
+        )
+        .bind(dead_letter.delivery_id.as_str())
+        .bind(dead_letter.project_id.as_str())
+        .bind(dead_letter.destination.as_str())
+        .bind(dead_letter.url.as_str())
+        .bind(dead_letter.payload.as_str())
+        .bind(dead_letter.events)
+        .bind(dead_letter.attempts)
+        .bind(dead_letter.error.as_str())
+        .bind(dead_letter.failed_at)
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Dead letter insert error: {}", e)))
+}
+
+/// Next offset to read for a destination, if one has been saved.
+pub async fn load_offset(
+    client: &ClickHouseClient,
+    group_id: &str,
+    project_id: &str,
+    destination: &str,
+    partition: i32,
+) -> Result<Option<i64>> {
+    client
+        .inner()
+        .query(
+            "SELECT next_offset FROM overwatch.forwarding_offsets FINAL \
+             WHERE group_id = ? AND project_id = ? AND destination = ? AND partition = ?",
+        )
+        .bind(group_id)
+        .bind(project_id)
+        .bind(destination)
+        .bind(partition)
+        .fetch_optional()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+/// Save the next offset to read for a destination.
+pub async fn save_offset(
+    client: &ClickHouseClient,
+    group_id: &str,
+    project_id: &str,
+    destination: &str,
+    partition: i32,
+    next_offset: i64,
+) -> Result<()> {
+    client
+        .inner()
+        .query(
+            "INSERT INTO overwatch.forwarding_offsets \
+             (group_id, project_id, destination, partition, next_offset) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(group_id)
+        .bind(project_id)
+        .bind(destination)
+        .bind(partition)
+        .bind(next_offset)
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Offset insert error: {}", e)))
+}
+
+/// A project's most recent deliveries, newest first.
+pub async fn recent_deliveries(
+    client: &ClickHouseClient,
+    project_id: &str,
+    limit: u64,
+) -> Result<Vec<Delivery>> {
+    client
+        .inner()
+        .query(
+            "SELECT delivery_id, project_id, toString(destination) AS destination, \
+                    toString(status) AS status, events, attempts, http_status, error, \
+                    duration_ms, toUnixTimestamp64Milli(created_at) AS created_at \
+             FROM overwatch.forwarding_deliveries \
+             WHERE project_id = ? \
+             ORDER BY created_at DESC \
+             LIMIT ?",
+        )
+        .bind(project_id)
+        .bind(limit)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+/// A project's dead-lettered batches, newest first.
+pub async fn dead_letters(
+    client: &ClickHouseClient,
+    project_id: &str,
+    limit: u64,
+) -> Result<Vec<DeadLetter>> {
+    client
+        .inner()
+        .query(
+            "SELECT delivery_id, project_id, toString(destination) AS destination, url, payload, \
+                    events, attempts, error, toUnixTimestamp64Milli(failed_at) AS failed_at \
+             FROM overwatch.forwarding_dlq \
+             WHERE project_id = ? \
+             ORDER BY failed_at DESC \
+             LIMIT ?",
+        )
+        .bind(project_id)
+        .bind(limit)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clickhouse::test::{handlers, Mock};
+
+    #[tokio::test]
+    async fn test_offset_round_trip() {
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        mock.add(handlers::provide(Vec::<i64>::new()));
+        let loaded = load_offset(&client, "forwarder", "proj", "crm", 0).await;
+        assert_eq!(loaded.unwrap(), None);
+
+        let recorded = mock.add(handlers::record_ddl());
+        save_offset(&client, "forwarder", "proj", "crm", 0, 42)
+            .await
+            .unwrap();
+        assert!(recorded
+            .query()
+            .await
+            .starts_with("INSERT INTO overwatch.forwarding_offsets"));
+
+        mock.add(handlers::provide(vec![42i64]));
+        let loaded = load_offset(&client, "forwarder", "proj", "crm", 0).await;
+        assert_eq!(loaded.unwrap(), Some(42));
+    }
+}
@@ -0,0 +1,590 @@
+//! Data-subject export and erasure by user ID.
+//!
+//! A subject is a `user_id` within a project, plus every `session_id` that
+//! user appeared in. Sessions are resolved from `events` and `sessions`, so
+//! anonymous events recorded earlier in an identified session are included.
+//! The per-type tables carry no `user_id` and are matched by session only.
+//!
+//! Erasure uses lightweight deletes (`DELETE FROM`), which hide rows
+//! immediately and remove them on the next merge. Forwarding dead letters
+//! whose payload mentions the user or one of their sessions are deleted
+//! whole. Each erasure is tracked in `overwatch.erasure_requests` (a
+//! `ReplacingMergeTree` keyed on the request ID, so every status change
+//! inserts a new version of the row). The resolved session IDs are stored on
+//! the request before anything is deleted: once `events` and `sessions` rows
+//! are gone the sessions can no longer be resolved, and a retry still has to
+//! reach the per-type tables. Erasure is idempotent, so requests interrupted
+//! by a restart are simply run again ([`resume_erasures`]).
+//!
+//! Parquet archives written before retention drops are not rewritten. The
+//! project's archive files are counted on the request (`archived_files`) so
+//! they can be handled out of band; see `archive_manifest` for locations.
+
+use crate::client::ClickHouseClient;
+use clickhouse::Row;
+use engine_core::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Tables with a `user_id` column.
+const USER_TABLES: &[&str] = &["overwatch.events", "overwatch.sessions"];
+
+/// Per-type tables, matched by `session_id`.
+const SESSION_TABLES: &[&str] = &[
+    "overwatch.pageviews",
+    "overwatch.clicks",
+    "overwatch.scroll_events",
+    "overwatch.mouse_moves",
+    "overwatch.form_events",
+    "overwatch.errors",
+    "overwatch.performance_metrics",
+    "overwatch.visibility_events",
+    "overwatch.resource_loads",
+    "overwatch.geographic",
+    "overwatch.custom_events",
+];
+
+/// Erasure request lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+impl ErasureStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// One erasure request, as stored in `overwatch.erasure_requests`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct ErasureRequest {
+    pub request_id: String,
+    pub project_id: String,
+    pub user_id: String,
+    /// `pending`, `completed` or `failed`
+    pub status: String,
+    /// Linked sessions found for the user
+    pub sessions: u64,
+    /// Their IDs, recorded before any rows are deleted
+    pub session_ids: Vec<String>,
+    /// Rows matched across all tables (and dead letters) at erasure time
+    pub rows_erased: u64,
+    /// Parquet archive files of the project, which erasure does not rewrite
+    pub archived_files: u64,
+    /// Failure reason, empty unless `status` is `failed`
+    pub error: String,
+    /// Request time (Unix ms)
+    pub requested_at: i64,
+    /// Completion or failure time (Unix ms)
+    pub completed_at: Option<i64>,
+}
+
+/// `erasure_requests` columns in [`ErasureRequest`] order.
+const REQUEST_COLUMNS: &str = "request_id, project_id, user_id, toString(status) AS status, \
+     sessions, session_ids, rows_erased, archived_files, error, \
+     toUnixTimestamp64Milli(requested_at) AS requested_at, \
+     toUnixTimestamp64Milli(completed_at) AS completed_at";
+
+/// Row filter for a subject in `table`, binding user ID then sessions.
+fn subject_filter(table: &str) -> &'static str {
+    if USER_TABLES.contains(&table) {
+        "project_id = ? AND (user_id = ? OR has(?, session_id))"
+    } else {
+        "project_id = ? AND has(?, session_id)"
+    }
+}
+
+/// Bind a subject filter's arguments.
+fn bind_subject(
+    query: clickhouse::query::Query,
+    table: &str,
+    project_id: &str,
+    user_id: &str,
+    sessions: &[String],
+) -> clickhouse::query::Query {
+    let query = query.bind(project_id);
+    if USER_TABLES.contains(&table) {
+        query.bind(user_id).bind(sessions)
+    } else {
+        query.bind(sessions)
+    }
+}
+
+/// Sessions in which the user appeared.
+pub async fn linked_sessions(
+    client: &ClickHouseClient,
+    project_id: &str,
+    user_id: &str,
+) -> Result<Vec<String>> {
+    client
+        .inner()
+        .query(
+            "SELECT DISTINCT session_id FROM ( \
+                 SELECT session_id FROM overwatch.events WHERE project_id = ? AND user_id = ? \
+                 UNION ALL \
+                 SELECT session_id FROM overwatch.sessions WHERE project_id = ? AND user_id = ? \
+             ) ORDER BY session_id",
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+/// Export everything stored for a subject as JSONL.
+///
+/// Each line is `{"table": "...", "row": {...}}` with the row's stored
+/// columns as written by ClickHouse's `JSONEachRow` format.
+pub async fn export_subject(
+    client: &ClickHouseClient,
+    project_id: &str,
+    user_id: &str,
+) -> Result<String> {
+    let sessions = linked_sessions(client, project_id, user_id).await?;
+
+    let mut output = String::new();
+    for table in USER_TABLES.iter().chain(SESSION_TABLES) {
+        let sql = format!(
+            "SELECT formatRow('JSONEachRow', *) FROM {} WHERE {}",
+            table,
+            subject_filter(table)
+        );
+        let rows: Vec<String> = bind_subject(
+            client.inner().query(&sql),
+            table,
+            project_id,
+            user_id,
+            &sessions,
+        )
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+        for row in rows {
+            let row: serde_json::Value = serde_json::from_str(row.trim_end())
+                .map_err(|e| engine_core::Error::internal(format!("Export error: {}", e)))?;
+            let line = serde_json::json!({ "table": table, "row": row });
+            output.push_str(&line.to_string());
+            output.push('\n');
+        }
+    }
+
+    Ok(output)
+}
+
+/// Record a new pending erasure request.
+pub async fn create_erasure_request(
+    client: &ClickHouseClient,
+    project_id: &str,
+    user_id: &str,
+) -> Result<ErasureRequest> {
+    if user_id.is_empty() {
+        return Err(engine_core::Error::validation("user_id must not be empty"));
+    }
+
+    let request = ErasureRequest {
+        request_id: uuid::Uuid::new_v4().to_string(),
+        project_id: project_id.to_string(),
+        user_id: user_id.to_string(),
+        status: ErasureStatus::Pending.as_str().to_string(),
+        sessions: 0,
+        session_ids: Vec::new(),
+        rows_erased: 0,
+        archived_files: 0,
+        error: String::new(),
+        requested_at: chrono::Utc::now().timestamp_millis(),
+        completed_at: None,
+    };
+    record_request(client, &request).await?;
+    Ok(request)
+}
+
+/// Erase a subject across all tables and record the outcome.
+///
+/// Returns the final state of the request; a failure is recorded on the
+/// request and also returned as an error.
+pub async fn erase_subject(
+    client: &ClickHouseClient,
+    mut request: ErasureRequest,
+) -> Result<ErasureRequest> {
+    let result = erase_rows(client, &mut request).await;
+    request.completed_at = Some(chrono::Utc::now().timestamp_millis());
+
+    match result {
+        Ok(erased) => {
+            request.status = ErasureStatus::Completed.as_str().to_string();
+            request.rows_erased = erased.rows;
+            request.archived_files = erased.archived_files;
+            request.error = String::new();
+            record_request(client, &request).await?;
+            info!(
+                request_id = %request.request_id,
+                project_id = %request.project_id,
+                sessions = request.sessions,
+                rows = erased.rows,
+                archived_files = erased.archived_files,
+                "Erasure request completed"
+            );
+            Ok(request)
+        }
+        Err(e) => {
+            request.status = ErasureStatus::Failed.as_str().to_string();
+            request.error = e.to_string();
+            if let Err(record_err) = record_request(client, &request).await {
+                error!(
+                    request_id = %request.request_id,
+                    error = %record_err,
+                    "Failed to record erasure failure"
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+/// What one erasure found.
+struct Erased {
+    rows: u64,
+    archived_files: u64,
+}
+
+/// Payload fragments identifying a subject in serialized events.
+fn payload_needles(user_id: &str, sessions: &[String]) -> Vec<String> {
+    let quoted = |value: &str| serde_json::Value::from(value).to_string();
+    std::iter::once(format!("\"user_id\":{}", quoted(user_id)))
+        .chain(
+            sessions
+                .iter()
+                .map(|session| format!("\"session_id\":{}", quoted(session))),
+        )
+        .collect()
+}
+
+/// Delete a subject's rows and dead letters, and count archive files.
+///
+/// Sessions resolved now are merged with those recorded by earlier attempts
+/// and stored on the request before the first delete.
+async fn erase_rows(client: &ClickHouseClient, request: &mut ErasureRequest) -> Result<Erased> {
+    let mut sessions = linked_sessions(client, &request.project_id, &request.user_id).await?;
+    sessions.extend(request.session_ids.iter().cloned());
+    sessions.sort();
+    sessions.dedup();
+
+    request.status = ErasureStatus::Pending.as_str().to_string();
+    request.sessions = sessions.len() as u64;
+    request.session_ids = sessions.clone();
+    record_request(client, request).await?;
+
+    let (project_id, user_id) = (request.project_id.as_str(), request.user_id.as_str());
+    let mut rows = 0u64;
+
+    for table in USER_TABLES.iter().chain(SESSION_TABLES) {
+        let filter = subject_filter(table);
+
+        let count: u64 = bind_subject(
+            client
+                .inner()
+                .query(&format!("SELECT count() FROM {} WHERE {}", table, filter)),
+            table,
+            project_id,
+            user_id,
+            &sessions,
+        )
+        .fetch_one()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+        if count == 0 {
+            continue;
+        }
+
+        bind_subject(
+            client
+                .inner()
+                .query(&format!("DELETE FROM {} WHERE {}", table, filter)),
+            table,
+            project_id,
+            user_id,
+            &sessions,
+        )
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Delete error: {}", e)))?;
+
+        rows += count;
+    }
+
+    rows += erase_dead_letters(client, project_id, &payload_needles(user_id, &sessions)).await?;
+
+    let archived_files: u64 = client
+        .inner()
+        .query("SELECT count() FROM overwatch.archive_manifest WHERE project_id = ?")
+        .bind(project_id)
+        .fetch_one()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    Ok(Erased {
+        rows,
+        archived_files,
+    })
+}
+
+/// Delete forwarding dead letters whose payload contains any of `needles`.
+async fn erase_dead_letters(
+    client: &ClickHouseClient,
+    project_id: &str,
+    needles: &[String],
+) -> Result<u64> {
+    let filter = "project_id = ? AND arrayExists(n -> position(payload, n) > 0, ?)";
+
+    let count: u64 = client
+        .inner()
+        .query(&format!(
+            "SELECT count() FROM overwatch.forwarding_dlq WHERE {}",
+            filter
+        ))
+        .bind(project_id)
+        .bind(needles)
+        .fetch_one()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+    if count == 0 {
+        return Ok(0);
+    }
+
+    client
+        .inner()
+        .query(&format!(
+            "DELETE FROM overwatch.forwarding_dlq WHERE {}",
+            filter
+        ))
+        .bind(project_id)
+        .bind(needles)
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Delete error: {}", e)))?;
+    Ok(count)
+}
+
+/// Run erasures left `pending` or `failed` for longer than `stale_after`,
+/// e.g. by a restart mid-erasure. Returns how many completed.
+pub async fn resume_erasures(client: &ClickHouseClient, stale_after: Duration) -> Result<usize> {
+    let requests: Vec<ErasureRequest> = client
+        .inner()
+        .query(&format!(
+            "SELECT {} FROM overwatch.erasure_requests FINAL \
+             WHERE status IN ('pending', 'failed') \
+               AND updated_at < now64(3) - toIntervalSecond(?) \
+             ORDER BY requested_at",
+            REQUEST_COLUMNS
+        ))
+        .bind(stale_after.as_secs())
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    let mut completed = 0;
+    for request in requests {
+        let request_id = request.request_id.clone();
+        info!(request_id = %request_id, status = %request.status, "Resuming erasure request");
+        match erase_subject(client, request).await {
+            Ok(_) => completed += 1,
+            Err(e) => warn!(request_id = %request_id, error = %e, "Erasure request failed again"),
+        }
+    }
+    Ok(completed)
+}
+
+/// Insert a version of the request row.
+async fn record_request(client: &ClickHouseClient, request: &ErasureRequest) -> Result<()> {
+    client
+        .inner()
+        .query(
+            "INSERT INTO overwatch.erasure_requests \
+             (request_id, project_id, user_id, status, sessions, session_ids, rows_erased, \
+              archived_files, error, requested_at, completed_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, fromUnixTimestamp64Milli(?), \
+                     fromUnixTimestamp64Milli(?), now64(3))",
+        )
+        .bind(request.request_id.as_str())
+        .bind(request.project_id.as_str())
+        .bind(request.user_id.as_str())
+        .bind(request.status.as_str())
+        .bind(request.sessions)
+        .bind(&request.session_ids)
+        .bind(request.rows_erased)
+        .bind(request.archived_files)
+        .bind(request.error.as_str())
+        .bind(request.requested_at)
+        .bind(request.completed_at)
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Erasure request insert error: {}", e)))
+}
+
+/// List a project's erasure requests, newest first.
+pub async fn erasure_requests(
+    client: &ClickHouseClient,
+    project_id: &str,
+) -> Result<Vec<ErasureRequest>> {
+    client
+        .inner()
+        .query(&format!(
+            "SELECT {} FROM overwatch.erasure_requests FINAL \
+             WHERE project_id = ? \
+             ORDER BY requested_at DESC",
+            REQUEST_COLUMNS
+        ))
+        .bind(project_id)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_filter() {
+        assert!(subject_filter("overwatch.events").contains("user_id = ?"));
+        assert!(subject_filter("overwatch.sessions").contains("user_id = ?"));
+        assert!(!subject_filter("overwatch.clicks").contains("user_id"));
+        assert!(SESSION_TABLES
+            .iter()
+            .all(|t| subject_filter(t).matches('?').count() == 2));
+    }
+
+    #[test]
+    fn test_payload_needles() {
+        let needles = payload_needles("u\"1", &["s1".to_string()]);
+        assert_eq!(needles, vec![r#""user_id":"u\"1""#, r#""session_id":"s1""#]);
+
+        // Matches events as the forwarding worker serializes them
+        let payload = serde_json::json!({ "events": [{ "session_id": "s1", "user_id": null }] });
+        assert!(payload.to_string().contains(&needles[1]));
+    }
+
+    fn mock_client(mock: &clickhouse::test::Mock) -> ClickHouseClient {
+        ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn failed_request(session_ids: &[&str]) -> ErasureRequest {
+        ErasureRequest {
+            request_id: "req-1".to_string(),
+            project_id: "proj".to_string(),
+            user_id: "user-1".to_string(),
+            status: ErasureStatus::Failed.as_str().to_string(),
+            sessions: session_ids.len() as u64,
+            session_ids: session_ids.iter().map(|s| s.to_string()).collect(),
+            rows_erased: 0,
+            archived_files: 0,
+            error: "interrupted".to_string(),
+            requested_at: 1_700_000_000_000,
+            completed_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_erase_subject() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = mock_client(&mock);
+
+        mock.add(handlers::provide(vec!["s1".to_string()]));
+        let recorded_sessions = mock.add(handlers::record_ddl());
+        let mut deletes = Vec::new();
+        for table in USER_TABLES.iter().chain(SESSION_TABLES) {
+            let count: u64 = match *table {
+                "overwatch.events" => 3,
+                "overwatch.clicks" => 2,
+                _ => 0,
+            };
+            mock.add(handlers::provide(vec![count]));
+            if count > 0 {
+                deletes.push(mock.add(handlers::record_ddl()));
+            }
+        }
+        // One dead letter, then four archive files
+        mock.add(handlers::provide(vec![1u64]));
+        deletes.push(mock.add(handlers::record_ddl()));
+        mock.add(handlers::provide(vec![4u64]));
+        let recorded = mock.add(handlers::record_ddl());
+
+        let erased = erase_subject(&client, failed_request(&[])).await.unwrap();
+        assert_eq!(erased.status, "completed");
+        assert_eq!(erased.sessions, 1);
+        assert_eq!(erased.session_ids, vec!["s1"]);
+        assert_eq!(erased.rows_erased, 6);
+        assert_eq!(erased.archived_files, 4);
+        assert!(erased.error.is_empty());
+
+        let pending = recorded_sessions.query().await;
+        assert!(pending.starts_with("INSERT INTO overwatch.erasure_requests"));
+        assert!(pending.contains("'pending'") && pending.contains("['s1']"));
+
+        let mut deleted = Vec::new();
+        for delete in deletes {
+            deleted.push(delete.query().await);
+        }
+        assert!(deleted[0].starts_with("DELETE FROM overwatch.events"));
+        assert!(deleted[1].starts_with("DELETE FROM overwatch.clicks"));
+        assert!(deleted[2].starts_with("DELETE FROM overwatch.forwarding_dlq"));
+        assert!(recorded
+            .query()
+            .await
+            .starts_with("INSERT INTO overwatch.erasure_requests"));
+    }
+
+    #[tokio::test]
+    async fn test_resume_uses_recorded_sessions() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = mock_client(&mock);
+
+        // `events` and `sessions` were erased before the interruption
+        mock.add(handlers::provide(Vec::<String>::new()));
+        mock.add(handlers::record_ddl());
+        let mut deletes = Vec::new();
+        for table in USER_TABLES.iter().chain(SESSION_TABLES) {
+            let count = u64::from(*table == "overwatch.clicks");
+            mock.add(handlers::provide(vec![count]));
+            if count > 0 {
+                deletes.push(mock.add(handlers::record_ddl()));
+            }
+        }
+        mock.add(handlers::provide(vec![0u64]));
+        mock.add(handlers::provide(vec![0u64]));
+        mock.add(handlers::record_ddl());
+
+        let erased = erase_subject(&client, failed_request(&["s1"]))
+            .await
+            .unwrap();
+        assert_eq!(erased.status, "completed");
+        assert_eq!(erased.session_ids, vec!["s1"]);
+        assert_eq!(erased.rows_erased, 1);
+
+        let delete = deletes.remove(0).query().await;
+        assert!(delete.starts_with("DELETE FROM overwatch.clicks"));
+        assert!(delete.contains("has(['s1'], session_id)"));
+    }
+}
@@ -19,16 +19,10 @@ pub async fn check_connection(client: &ClickHouseClient) -> bool {
 
 /// Initialize database schema.
 pub async fn init_schema(client: &ClickHouseClient) -> Result<(), String> {
-    use crate::schema::all_tables;
-
-    for ddl in all_tables() {
-        client
-            .inner()
-            .query(ddl)
-            .execute()
-            .await
-            .map_err(|e| format!("Failed to execute DDL: {}", e))?;
-    }
+    // Same tables and migrations as the integration test setup
+    crate::schema::init_schema(client)
+        .await
+        .map_err(|e| e.to_string())?;
 
     debug!("ClickHouse schema initialized");
     Ok(())
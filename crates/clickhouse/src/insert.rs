@@ -1,6 +1,10 @@
 //! Batch insert helpers for ClickHouse.
 
 use crate::client::ClickHouseClient;
+use crate::properties::{
+    batch_properties, extract_properties, property_maps, NumberMap, PropertyValue, StringMap,
+};
+use crate::streaming::StreamingInserter;
 use clickhouse::Row;
 use engine_core::{ClickHouseEvent, Event, EventPayload, Result};
 use serde::{Deserialize, Serialize};
@@ -244,10 +248,22 @@ pub struct ClickHouseEventRow {
     pub region: Option<String>,
     pub city: Option<String>,
     pub data: String, // JSON blob
+    pub properties: StringMap,
+    pub properties_num: NumberMap,
 }
 
 impl From<ClickHouseEvent> for ClickHouseEventRow {
     fn from(event: ClickHouseEvent) -> Self {
+        let properties = extract_properties(&event.data);
+        Self::with_properties(event, &properties)
+    }
+}
+
+impl ClickHouseEventRow {
+    /// Build a row from an event and its already extracted properties.
+    pub fn with_properties(event: ClickHouseEvent, properties: &[PropertyValue]) -> Self {
+        let (properties, properties_num) = property_maps(properties);
+
         Self {
             event_id: event.event_id,
             project_id: event.project_id,
@@ -268,6 +284,8 @@ impl From<ClickHouseEvent> for ClickHouseEventRow {
             region: event.region,
             city: event.city,
             data: event.data,
+            properties,
+            properties_num,
         }
     }
 }
@@ -275,43 +293,45 @@ impl From<ClickHouseEvent> for ClickHouseEventRow {
 /// Insert ClickHouseEvent records from the consumer.
 ///
 /// This is the main insert function for the production pipeline,
-/// inserting into the overwatch.events table.
+/// inserting into the overwatch.events table. Large batches are split into
+/// bounded `INSERT`s by [`StreamingInserter`].
 pub async fn insert_clickhouse_events(
     client: &ClickHouseClient,
     events: Vec<ClickHouseEvent>,
+) -> Result<usize> {
+    let properties = batch_properties(&events);
+    insert_clickhouse_events_with_properties(client, events, &properties).await
+}
+
+/// [`insert_clickhouse_events`] with each event's properties already
+/// extracted by [`batch_properties`], so callers that also record the
+/// property catalog parse `data` only once.
+pub async fn insert_clickhouse_events_with_properties(
+    client: &ClickHouseClient,
+    events: Vec<ClickHouseEvent>,
+    properties: &[Vec<PropertyValue>],
 ) -> Result<usize> {
     if events.is_empty() {
         return Ok(0);
     }
 
-    let count = events.len();
     let start = std::time::Instant::now();
 
-    let rows: Vec<ClickHouseEventRow> = events.into_iter().map(ClickHouseEventRow::from).collect();
+    let rows: Vec<ClickHouseEventRow> = events
+        .into_iter()
+        .zip(properties)
+        .map(|(event, properties)| ClickHouseEventRow::with_properties(event, properties))
+        .collect();
 
     // Insert into overwatch.events table
-    let mut insert = client.inner().insert("overwatch.events").map_err(|e| {
-        metrics().clickhouse_insert_errors.inc();
-        engine_core::Error::internal(format!("Insert error: {}", e))
-    })?;
-
-    for row in &rows {
-        insert.write(row).await.map_err(|e| {
-            metrics().clickhouse_insert_errors.inc();
-            engine_core::Error::internal(format!("Write error: {}", e))
-        })?;
-    }
-
-    insert.end().await.map_err(|e| {
-        metrics().clickhouse_insert_errors.inc();
-        engine_core::Error::internal(format!("End error: {}", e))
-    })?;
+    let count = StreamingInserter::new(client, "overwatch.events")
+        .insert(&rows)
+        .await?;
 
     let elapsed = start.elapsed();
     metrics()
         .batch_insert_latency_ms
         .observe(elapsed.as_millis() as u64);
-    metrics().clickhouse_inserts.inc();
     metrics().events_inserted.inc_by(count as u64);
 
     debug!(
@@ -549,7 +569,7 @@ pub struct CustomEventRow {
     pub session_id: String,
     pub timestamp: i64,
     pub name: String,
-    pub properties: String,
+    pub properties: StringMap,
     pub url: String,
 }
 
@@ -0,0 +1,121 @@
+//! Tenant and API key records for the local key store.
+//!
+//! `overwatch.tenants` and `overwatch.api_keys` let self-hosted deployments
+//! validate keys without the auth daemon. Both tables are
+//! `ReplacingMergeTree`s, so updates are new rows and reads use `FINAL`.
+
+use crate::client::ClickHouseClient;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use engine_core::{ApiKey, KeyType, Result, RetentionTier, SigningSecret, Tenant};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Row, Deserialize)]
+struct TenantRow {
+    id: String,
+    name: String,
+    tier: String,
+    active: bool,
+    rate_limit: Option<u32>,
+    signing_secret: Option<String>,
+    require_signature: bool,
+    ip_allowlist: Vec<String>,
+    ip_denylist: Vec<String>,
+    allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Row, Deserialize)]
+struct ApiKeyRow {
+    key_hash: String,
+    tenant_id: String,
+    name: String,
+    active: bool,
+    /// Unix milliseconds
+    expires_at: Option<i64>,
+    permissions: Vec<String>,
+    key_type: String,
+}
+
+fn parse_uuid(value: &str) -> Result<Uuid> {
+    Uuid::parse_str(value)
+        .map_err(|e| engine_core::Error::internal(format!("Invalid UUID {}: {}", value, e)))
+}
+
+fn parse_tier(value: &str) -> Result<RetentionTier> {
+    match value {
+        "free" => Ok(RetentionTier::Free),
+        "paid" => Ok(RetentionTier::Paid),
+        "enterprise" => Ok(RetentionTier::Enterprise),
+        other => Err(engine_core::Error::internal(format!(
+            "Unknown tier: {}",
+            other
+        ))),
+    }
+}
+
+/// All tenants.
+pub async fn load_tenants(client: &ClickHouseClient) -> Result<Vec<Tenant>> {
+    let rows: Vec<TenantRow> = client
+        .inner()
+        .query(
+            "SELECT toString(id) AS id, name, toString(tier) AS tier, active, rate_limit, \
+                    signing_secret, require_signature, ip_allowlist, ip_denylist, allowed_origins \
+             FROM overwatch.tenants FINAL",
+        )
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Tenant query error: {}", e)))?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Tenant {
+                id: parse_uuid(&row.id)?,
+                name: row.name,
+                tier: parse_tier(&row.tier)?,
+                active: row.active,
+                rate_limit: row.rate_limit,
+                signing_secret: row.signing_secret.map(SigningSecret::new),
+                require_signature: row.require_signature,
+                ip_allowlist: (!row.ip_allowlist.is_empty()).then_some(row.ip_allowlist),
+                ip_denylist: (!row.ip_denylist.is_empty()).then_some(row.ip_denylist),
+                allowed_origins: (!row.allowed_origins.is_empty()).then_some(row.allowed_origins),
+            })
+        })
+        .collect()
+}
+
+/// All API keys.
+pub async fn load_api_keys(client: &ClickHouseClient) -> Result<Vec<ApiKey>> {
+    let rows: Vec<ApiKeyRow> = client
+        .inner()
+        .query(
+            "SELECT key_hash, toString(tenant_id) AS tenant_id, name, active, \
+                    toUnixTimestamp64Milli(expires_at) AS expires_at, permissions, \
+                    toString(key_type) AS key_type \
+             FROM overwatch.api_keys FINAL",
+        )
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("API key query error: {}", e)))?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ApiKey {
+                key_hash: row.key_hash,
+                tenant_id: parse_uuid(&row.tenant_id)?,
+                name: row.name,
+                active: row.active,
+                expires_at: row
+                    .expires_at
+                    .and_then(DateTime::<Utc>::from_timestamp_millis),
+                permissions: (!row.permissions.is_empty()).then_some(row.permissions),
+                key_type: if row.key_type == "server" {
+                    KeyType::Server
+                } else {
+                    KeyType::Browser
+                },
+            })
+        })
+        .collect()
+}
@@ -1,14 +1,23 @@
 //! ClickHouse client for the ingestion engine.
 
+pub mod analytics;
+pub mod archive;
 pub mod client;
 pub mod config;
+pub mod forwarding;
+pub mod gdpr;
 pub mod health;
 pub mod insert;
+pub mod keys;
+pub mod mau;
 pub mod ops;
+pub mod properties;
 pub mod query;
 pub mod schema;
+pub mod streaming;
 
 pub use client::*;
 pub use config::*;
 pub use ops::{collect_ops_metrics, log_ops_metrics, ClickHouseOpsMetrics};
 pub use query::*;
+pub use streaming::{StreamingInsertConfig, StreamingInserter};
@@ -0,0 +1,135 @@
+//! MAU overage records for billing.
+//!
+//! Events accepted from a project that is over its monthly active user
+//! limit are summed per month and over-limit action in
+//! `overwatch.mau_overages`, together with the engine's own MAU estimate.
+//! Stored distinct users per project and month are kept in
+//! `overwatch.mau_users` so estimates survive restarts.
+
+use crate::client::ClickHouseClient;
+use clickhouse::Row;
+use engine_core::Result;
+use serde::{Deserialize, Serialize};
+
+/// Over-limit events for one project, month and action.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct MauOverage {
+    pub project_id: String,
+    /// First day of the month (`YYYY-MM-01`)
+    pub month: String,
+    /// `sample` or `flag`
+    pub action: String,
+    /// Events received while over the limit
+    pub events: u64,
+    /// Events not stored because of sampling
+    pub sampled_out: u64,
+    /// Engine's distinct user estimate for the month
+    pub estimated_users: u64,
+}
+
+/// Add overage counts in one insert; rows for the same key are summed on
+/// merge.
+pub async fn record_overages(client: &ClickHouseClient, overages: &[MauOverage]) -> Result<()> {
+    if overages.is_empty() {
+        return Ok(());
+    }
+
+    let values = vec!["(?, toDate(?), ?, ?, ?, ?)"; overages.len()].join(", ");
+    let sql = format!(
+        "INSERT INTO overwatch.mau_overages \
+         (project_id, month, action, events, sampled_out, estimated_users) \
+         VALUES {}",
+        values
+    );
+    let mut query = client.inner().query(&sql);
+    for overage in overages {
+        query = query
+            .bind(overage.project_id.as_str())
+            .bind(overage.month.as_str())
+            .bind(overage.action.as_str())
+            .bind(overage.events)
+            .bind(overage.sampled_out)
+            .bind(overage.estimated_users);
+    }
+    query
+        .execute()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("MAU overage insert error: {}", e)))
+}
+
+/// Stored distinct users for one project and month.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct ProjectUsers {
+    pub project_id: String,
+    pub users: u64,
+}
+
+/// Distinct users stored this month (`YYYY-MM-01`), per project.
+pub async fn stored_users(client: &ClickHouseClient, month: &str) -> Result<Vec<ProjectUsers>> {
+    client
+        .inner()
+        .query(
+            "SELECT project_id, uniqCombinedMerge(users) AS users \
+             FROM overwatch.mau_users \
+             WHERE month = toDate(?) \
+             GROUP BY project_id",
+        )
+        .bind(month)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+/// A project's overages per month, newest first.
+pub async fn project_overages(
+    client: &ClickHouseClient,
+    project_id: &str,
+) -> Result<Vec<MauOverage>> {
+    client
+        .inner()
+        .query(
+            "SELECT project_id, toString(month) AS month, toString(action) AS action, \
+                    sum(events) AS events, sum(sampled_out) AS sampled_out, \
+                    max(estimated_users) AS estimated_users \
+             FROM overwatch.mau_overages \
+             WHERE project_id = ? \
+             GROUP BY project_id, month, action \
+             ORDER BY month DESC, action",
+        )
+        .bind(project_id)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clickhouse::test::{handlers, Mock};
+
+    #[tokio::test]
+    async fn test_overages_are_one_insert() {
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let overage = |project_id: &str| MauOverage {
+            project_id: project_id.to_string(),
+            month: "2026-10-01".to_string(),
+            action: "flag".to_string(),
+            events: 10,
+            sampled_out: 0,
+            estimated_users: 1200,
+        };
+
+        let recorded = mock.add(handlers::record_ddl());
+        record_overages(&client, &[overage("a"), overage("b")])
+            .await
+            .unwrap();
+        let query = recorded.query().await;
+        assert!(query.starts_with("INSERT INTO overwatch.mau_overages"));
+        assert!(query.contains("('a', toDate('2026-10-01'), 'flag', 10, 0, 1200), ('b'"));
+    }
+}
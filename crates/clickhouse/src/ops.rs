@@ -4,11 +4,15 @@
 //! - Parts count and merge pressure
 //! - Active merges
 //! - Disk usage
+//! - Slow and failed inserts from our own user (`system.query_log`)
+//! - Replica lag and stuck replication entries (replicated setups only)
+//! - Stuck mutations
 
 use crate::client::ClickHouseClient;
 use clickhouse::Row;
 use engine_core::Result;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use tracing::{debug, error, warn};
 
 /// Parts count per table from system.parts.
@@ -46,6 +50,69 @@ pub struct DiskInfo {
     pub total_space: u64,
 }
 
+/// Inserts longer than this count as slow.
+pub const SLOW_INSERT_MS: u64 = 5_000;
+
+/// Slow inserts per window before alerting.
+pub const SLOW_INSERT_ALERT_COUNT: u64 = 10;
+
+/// Failed inserts per window before alerting.
+pub const FAILED_INSERT_ALERT_COUNT: u64 = 1;
+
+/// Replica delay before alerting.
+pub const REPLICA_DELAY_ALERT_SECS: u64 = 300;
+
+/// Replication queue length before alerting.
+pub const REPLICA_QUEUE_ALERT_SIZE: u64 = 100;
+
+/// Retries of one replication queue entry before it counts as stuck.
+pub const REPLICATION_ENTRY_STUCK_TRIES: u64 = 10;
+
+/// Age of an unfinished mutation before it counts as stuck.
+pub const MUTATION_STUCK_SECS: u64 = 3_600;
+
+/// Insert outcomes for our own user from system.query_log.
+#[derive(Debug, Clone, Default, Row, Deserialize, Serialize)]
+pub struct QueryLogStats {
+    pub slow_inserts: u64,
+    pub failed_inserts: u64,
+    pub max_insert_duration_ms: u64,
+    /// Most recent insert exception in the window
+    pub last_error: String,
+}
+
+/// Replica status from system.replicas.
+#[derive(Debug, Clone, Row, Deserialize, Serialize)]
+pub struct ReplicaInfo {
+    pub database: String,
+    pub table: String,
+    pub is_readonly: u8,
+    pub absolute_delay: u64,
+    pub queue_size: u64,
+}
+
+/// Replication queue entries retried past the stuck threshold, per table.
+#[derive(Debug, Clone, Row, Deserialize, Serialize)]
+pub struct ReplicationQueueInfo {
+    pub database: String,
+    pub table: String,
+    pub stuck_entries: u64,
+    pub max_num_tries: u64,
+    pub last_exception: String,
+}
+
+/// Unfinished mutation from system.mutations.
+#[derive(Debug, Clone, Row, Deserialize, Serialize)]
+pub struct MutationInfo {
+    pub database: String,
+    pub table: String,
+    pub mutation_id: String,
+    pub command: String,
+    pub age_secs: u64,
+    pub parts_to_do: i64,
+    pub latest_fail_reason: String,
+}
+
 /// ClickHouse operational metrics snapshot.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClickHouseOpsMetrics {
@@ -56,6 +123,10 @@ pub struct ClickHouseOpsMetrics {
     pub merge_pressure_score: f64,
     pub max_parts_count: u64,
     pub total_active_merges: usize,
+    pub query_log: QueryLogStats,
+    pub replicas: Vec<ReplicaInfo>,
+    pub replication_queue: Vec<ReplicationQueueInfo>,
+    pub mutations: Vec<MutationInfo>,
 }
 
 impl ClickHouseOpsMetrics {
@@ -92,10 +163,46 @@ impl ClickHouseOpsMetrics {
             })
             .collect()
     }
+
+    /// Check if slow inserts in the last window exceed the alert count.
+    pub fn has_slow_inserts(&self) -> bool {
+        self.query_log.slow_inserts >= SLOW_INSERT_ALERT_COUNT
+    }
+
+    /// Check if failed inserts in the last window exceed the alert count.
+    pub fn has_failed_inserts(&self) -> bool {
+        self.query_log.failed_inserts >= FAILED_INSERT_ALERT_COUNT
+    }
+
+    /// Get replicas that are read-only, delayed or backed up.
+    pub fn unhealthy_replicas(&self) -> Vec<&ReplicaInfo> {
+        self.replicas
+            .iter()
+            .filter(|r| {
+                r.is_readonly != 0
+                    || r.absolute_delay > REPLICA_DELAY_ALERT_SECS
+                    || r.queue_size > REPLICA_QUEUE_ALERT_SIZE
+            })
+            .collect()
+    }
+
+    /// Get mutations that have failed or run past the stuck threshold.
+    pub fn stuck_mutations(&self) -> Vec<&MutationInfo> {
+        self.mutations
+            .iter()
+            .filter(|m| !m.latest_fail_reason.is_empty() || m.age_secs > MUTATION_STUCK_SECS)
+            .collect()
+    }
 }
 
 /// Collect ClickHouse operational metrics.
-pub async fn collect_ops_metrics(client: &ClickHouseClient) -> Result<ClickHouseOpsMetrics> {
+///
+/// `query_log_window` should match the caller's poll interval, so each insert
+/// failure is counted (and alerted on) once.
+pub async fn collect_ops_metrics(
+    client: &ClickHouseClient,
+    query_log_window: Duration,
+) -> Result<ClickHouseOpsMetrics> {
     let tables = get_table_parts_info(client).await.unwrap_or_else(|e| {
         error!(error = %e, "Failed to get table parts info");
         vec![]
@@ -111,6 +218,28 @@ pub async fn collect_ops_metrics(client: &ClickHouseClient) -> Result<ClickHouse
         vec![]
     });
 
+    let query_log = get_query_log_stats(client, query_log_window)
+        .await
+        .unwrap_or_else(|e| {
+            debug!(error = %e, "Failed to read query log (may be disabled)");
+            QueryLogStats::default()
+        });
+
+    let replicas = get_replicas(client).await.unwrap_or_else(|e| {
+        error!(error = %e, "Failed to get replica status");
+        vec![]
+    });
+
+    let replication_queue = get_replication_queue(client).await.unwrap_or_else(|e| {
+        error!(error = %e, "Failed to get replication queue");
+        vec![]
+    });
+
+    let mutations = get_pending_mutations(client).await.unwrap_or_else(|e| {
+        error!(error = %e, "Failed to get mutations");
+        vec![]
+    });
+
     // Calculate merge pressure score
     let merge_pressure_score = calculate_merge_pressure(&tables, &active_merges);
     let max_parts_count = tables.iter().map(|t| t.active_parts).max().unwrap_or(0);
@@ -123,6 +252,10 @@ pub async fn collect_ops_metrics(client: &ClickHouseClient) -> Result<ClickHouse
         disks,
         merge_pressure_score,
         max_parts_count,
+        query_log,
+        replicas,
+        replication_queue,
+        mutations,
     })
 }
 
@@ -198,6 +331,149 @@ async fn get_disk_info(client: &ClickHouseClient) -> Result<Vec<DiskInfo>> {
     Ok(rows)
 }
 
+/// Get insert outcomes for the current user over the last `window`.
+async fn get_query_log_stats(client: &ClickHouseClient, window: Duration) -> Result<QueryLogStats> {
+    let sql = r#"
+        SELECT
+            countIf(type = 'QueryFinish' AND query_duration_ms > ?) as slow_inserts,
+            countIf(type != 'QueryFinish') as failed_inserts,
+            max(query_duration_ms) as max_insert_duration_ms,
+            argMax(exception, if(exception != '', event_time, toDateTime(0))) as last_error
+        FROM system.query_log
+        WHERE event_time > now() - INTERVAL ? SECOND
+          AND user = currentUser()
+          AND query_kind = 'Insert'
+          AND type != 'QueryStart'
+    "#;
+
+    client
+        .inner()
+        .query(sql)
+        .bind(SLOW_INSERT_MS)
+        .bind(window.as_secs().max(1))
+        .fetch_one()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+/// Get replica status for replicated tables (empty on single-node setups).
+async fn get_replicas(client: &ClickHouseClient) -> Result<Vec<ReplicaInfo>> {
+    let sql = r#"
+        SELECT
+            database,
+            table,
+            is_readonly,
+            absolute_delay,
+            toUInt64(queue_size) as queue_size
+        FROM system.replicas
+        WHERE database = 'overwatch'
+        ORDER BY absolute_delay DESC
+    "#;
+
+    let rows: Vec<ReplicaInfo> = client
+        .inner()
+        .query(sql)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    Ok(rows)
+}
+
+/// Get replication queue entries that keep failing.
+async fn get_replication_queue(client: &ClickHouseClient) -> Result<Vec<ReplicationQueueInfo>> {
+    let sql = r#"
+        SELECT
+            database,
+            table,
+            count() as stuck_entries,
+            toUInt64(max(num_tries)) as max_num_tries,
+            argMax(last_exception, num_tries) as last_exception
+        FROM system.replication_queue
+        WHERE database = 'overwatch'
+          AND num_tries > ?
+        GROUP BY database, table
+        ORDER BY max_num_tries DESC
+    "#;
+
+    let rows: Vec<ReplicationQueueInfo> = client
+        .inner()
+        .query(sql)
+        .bind(REPLICATION_ENTRY_STUCK_TRIES)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    Ok(rows)
+}
+
+/// Get unfinished mutations.
+async fn get_pending_mutations(client: &ClickHouseClient) -> Result<Vec<MutationInfo>> {
+    let sql = r#"
+        SELECT
+            database,
+            table,
+            mutation_id,
+            command,
+            toUInt64(dateDiff('second', create_time, now())) as age_secs,
+            parts_to_do,
+            latest_fail_reason
+        FROM system.mutations
+        WHERE database = 'overwatch'
+          AND is_done = 0
+        ORDER BY create_time
+    "#;
+
+    let rows: Vec<MutationInfo> = client
+        .inner()
+        .query(sql)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    Ok(rows)
+}
+
+/// Get the disks backing `volume` in a table's storage policy.
+///
+/// Returns an empty list if the table's policy has no such volume.
+pub async fn volume_disks(
+    client: &ClickHouseClient,
+    database: &str,
+    table: &str,
+    volume: &str,
+) -> Result<Vec<DiskInfo>> {
+    let sql = r#"
+        SELECT
+            name,
+            path,
+            free_space,
+            total_space
+        FROM system.disks
+        WHERE name IN (
+            SELECT arrayJoin(disks)
+            FROM system.storage_policies
+            WHERE volume_name = ?
+              AND policy_name = (
+                  SELECT storage_policy FROM system.tables
+                  WHERE database = ? AND name = ?
+              )
+        )
+    "#;
+
+    let rows: Vec<DiskInfo> = client
+        .inner()
+        .query(sql)
+        .bind(volume)
+        .bind(database)
+        .bind(table)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+
+    Ok(rows)
+}
+
 /// Calculate a merge pressure score (0-100).
 ///
 /// Higher values indicate more merge pressure and potential issues.
@@ -277,6 +553,54 @@ pub fn log_ops_metrics(metrics: &ClickHouseOpsMetrics) {
         );
     }
 
+    // Log slow and failed inserts
+    if metrics.has_failed_inserts() {
+        error!(
+            failed_inserts = metrics.query_log.failed_inserts,
+            last_error = %metrics.query_log.last_error,
+            "ClickHouse inserts failing"
+        );
+    }
+    if metrics.has_slow_inserts() {
+        warn!(
+            slow_inserts = metrics.query_log.slow_inserts,
+            max_duration_ms = metrics.query_log.max_insert_duration_ms,
+            "ClickHouse inserts slow"
+        );
+    }
+
+    // Log replication problems
+    for replica in metrics.unhealthy_replicas() {
+        warn!(
+            table = format!("{}.{}", replica.database, replica.table),
+            readonly = replica.is_readonly != 0,
+            delay_secs = replica.absolute_delay,
+            queue_size = replica.queue_size,
+            "Replica unhealthy"
+        );
+    }
+    for queue in &metrics.replication_queue {
+        warn!(
+            table = format!("{}.{}", queue.database, queue.table),
+            stuck_entries = queue.stuck_entries,
+            max_tries = queue.max_num_tries,
+            last_exception = %queue.last_exception,
+            "Replication queue entries retrying"
+        );
+    }
+
+    // Log stuck mutations
+    for mutation in metrics.stuck_mutations() {
+        warn!(
+            table = format!("{}.{}", mutation.database, mutation.table),
+            mutation_id = %mutation.mutation_id,
+            age_secs = mutation.age_secs,
+            parts_to_do = mutation.parts_to_do,
+            fail_reason = %mutation.latest_fail_reason,
+            "Mutation stuck"
+        );
+    }
+
     // Log disk usage warnings
     for (disk, usage_pct) in metrics.high_usage_disks() {
         warn!(
@@ -288,3 +612,122 @@ pub fn log_ops_metrics(metrics: &ClickHouseOpsMetrics) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_metrics() -> ClickHouseOpsMetrics {
+        ClickHouseOpsMetrics {
+            timestamp: chrono::Utc::now(),
+            tables: Vec::new(),
+            active_merges: Vec::new(),
+            disks: Vec::new(),
+            merge_pressure_score: 0.0,
+            max_parts_count: 0,
+            total_active_merges: 0,
+            query_log: QueryLogStats::default(),
+            replicas: Vec::new(),
+            replication_queue: Vec::new(),
+            mutations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_unhealthy_replicas() {
+        let replica = |readonly: u8, delay: u64, queue: u64| ReplicaInfo {
+            database: "overwatch".to_string(),
+            table: "events".to_string(),
+            is_readonly: readonly,
+            absolute_delay: delay,
+            queue_size: queue,
+        };
+        let mut metrics = empty_metrics();
+        metrics.replicas = vec![
+            replica(0, 10, 5),
+            replica(1, 0, 0),
+            replica(0, 600, 0),
+            replica(0, 0, 500),
+        ];
+        assert_eq!(metrics.unhealthy_replicas().len(), 3);
+    }
+
+    #[test]
+    fn test_stuck_mutations() {
+        let mutation = |age_secs: u64, fail_reason: &str| MutationInfo {
+            database: "overwatch".to_string(),
+            table: "events".to_string(),
+            mutation_id: "mutation_1.txt".to_string(),
+            command: "DELETE WHERE 1".to_string(),
+            age_secs,
+            parts_to_do: 3,
+            latest_fail_reason: fail_reason.to_string(),
+        };
+        let mut metrics = empty_metrics();
+        metrics.mutations = vec![
+            mutation(60, ""),
+            mutation(7200, ""),
+            mutation(10, "Memory limit exceeded"),
+        ];
+        assert_eq!(metrics.stuck_mutations().len(), 2);
+
+        metrics.query_log.failed_inserts = 1;
+        assert!(metrics.has_failed_inserts());
+        assert!(!metrics.has_slow_inserts());
+    }
+
+    /// Rows as ClickHouse encodes them in RowBinary for our queries. The
+    /// `UInt32` system columns are cast with `toUInt64` in the SQL.
+    #[derive(Serialize)]
+    struct ReplicaWire {
+        database: String,
+        table: String,
+        is_readonly: u8,
+        absolute_delay: u64,
+        queue_size: u64,
+    }
+
+    #[derive(Serialize)]
+    struct ReplicationQueueWire {
+        database: String,
+        table: String,
+        stuck_entries: u64,
+        max_num_tries: u64,
+        last_exception: String,
+    }
+
+    #[tokio::test]
+    async fn test_replica_rows_decode() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        mock.add(handlers::provide(vec![ReplicaWire {
+            database: "overwatch".to_string(),
+            table: "events".to_string(),
+            is_readonly: 0,
+            absolute_delay: 42,
+            queue_size: 7,
+        }]));
+        let replicas = get_replicas(&client).await.unwrap();
+        assert_eq!(replicas.len(), 1);
+        assert_eq!(replicas[0].absolute_delay, 42);
+        assert_eq!(replicas[0].queue_size, 7);
+
+        mock.add(handlers::provide(vec![ReplicationQueueWire {
+            database: "overwatch".to_string(),
+            table: "events".to_string(),
+            stuck_entries: 2,
+            max_num_tries: 15,
+            last_exception: "Code: 999".to_string(),
+        }]));
+        let queue = get_replication_queue(&client).await.unwrap();
+        assert_eq!(queue[0].max_num_tries, 15);
+        assert_eq!(queue[0].last_exception, "Code: 999");
+    }
+}
@@ -0,0 +1,358 @@
+//! Event property maps and the per-project property key catalog.
+//!
+//! The `properties` object inside an event's `data` JSON is stored twice on
+//! `overwatch.events`: every value stringified in `properties`
+//! (`Map(String, String)`), and numeric values again in `properties_num`
+//! (`Map(String, Float64)`) so they can be aggregated without casts.
+//!
+//! Each event's `data` is parsed once per batch ([`batch_properties`]) and
+//! the result feeds both the row maps and the catalog.
+//!
+//! After each insert the consumer writes the batch's property observations
+//! to `overwatch.property_observations`, a `Null` table whose materialized
+//! view folds them into `overwatch.property_keys`, an `AggregatingMergeTree`
+//! keyed on project, event name, key and value type. A key seen with two
+//! types shows up as two rows, which is how type drift surfaces.
+
+use crate::client::ClickHouseClient;
+use clickhouse::Row;
+use engine_core::{ClickHouseEvent, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// RowBinary form of `Map(String, String)`.
+pub type StringMap = Vec<(String, String)>;
+
+/// RowBinary form of `Map(String, Float64)`.
+pub type NumberMap = Vec<(String, f64)>;
+
+/// Type of a property value as seen in the SDK payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+    String,
+    Number,
+    Boolean,
+    /// Nested object or array, stored as JSON text
+    Json,
+}
+
+impl PropertyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// One property value, stringified, with its original type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyValue {
+    pub key: String,
+    pub value: String,
+    pub value_type: PropertyType,
+    pub number: Option<f64>,
+}
+
+/// Extract the `properties` object from an event's `data` JSON.
+///
+/// Null values are skipped. Returns an empty list if `data` has no
+/// `properties` object.
+pub fn extract_properties(data: &str) -> Vec<PropertyValue> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(data) => properties_from_value(data),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Extract the `properties` object from already parsed `data`.
+pub fn properties_from_value(data: Value) -> Vec<PropertyValue> {
+    let Value::Object(mut root) = data else {
+        return Vec::new();
+    };
+    let Some(Value::Object(properties)) = root.remove("properties") else {
+        return Vec::new();
+    };
+
+    properties
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let (value, value_type, number) = match value {
+                Value::Null => return None,
+                Value::String(s) => (s, PropertyType::String, None),
+                Value::Number(n) => (n.to_string(), PropertyType::Number, n.as_f64()),
+                Value::Bool(b) => (b.to_string(), PropertyType::Boolean, None),
+                other => (other.to_string(), PropertyType::Json, None),
+            };
+            Some(PropertyValue {
+                key,
+                value,
+                value_type,
+                number,
+            })
+        })
+        .collect()
+}
+
+/// Split extracted properties into the `properties` and `properties_num` maps.
+pub fn property_maps(properties: &[PropertyValue]) -> (StringMap, NumberMap) {
+    let strings = properties
+        .iter()
+        .map(|p| (p.key.clone(), p.value.clone()))
+        .collect();
+    let numbers = properties
+        .iter()
+        .filter_map(|p| p.number.map(|n| (p.key.clone(), n)))
+        .collect();
+    (strings, numbers)
+}
+
+/// Properties of each event in a batch, in order.
+pub fn batch_properties(events: &[ClickHouseEvent]) -> Vec<Vec<PropertyValue>> {
+    events
+        .iter()
+        .map(|event| extract_properties(&event.data))
+        .collect()
+}
+
+/// Catalog name for an event: the custom name, or the event type.
+fn event_name(event: &ClickHouseEvent) -> &str {
+    event.custom_name.as_deref().unwrap_or(&event.event_type)
+}
+
+/// Observations of one property value within a batch, as a row of
+/// `overwatch.property_observations`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct PropertyObservation {
+    project_id: String,
+    event_name: String,
+    key: String,
+    value_type: String,
+    value: String,
+    /// Unix ms
+    first_seen: i64,
+    /// Unix ms
+    last_seen: i64,
+    occurrences: u64,
+}
+
+/// Group a batch's properties by (project, event, key, type, value).
+///
+/// `properties` holds each event's properties, as from [`batch_properties`].
+fn observe(
+    events: &[ClickHouseEvent],
+    properties: &[Vec<PropertyValue>],
+) -> Vec<PropertyObservation> {
+    let mut observations: HashMap<(&str, &str, String, PropertyType, String), PropertyObservation> =
+        HashMap::new();
+
+    for (event, event_properties) in events.iter().zip(properties) {
+        for property in event_properties.iter().cloned() {
+            let entry = observations
+                .entry((
+                    event.project_id.as_str(),
+                    event_name(event),
+                    property.key.clone(),
+                    property.value_type,
+                    property.value.clone(),
+                ))
+                .or_insert_with(|| PropertyObservation {
+                    project_id: event.project_id.clone(),
+                    event_name: event_name(event).to_string(),
+                    key: property.key,
+                    value_type: property.value_type.as_str().to_string(),
+                    value: property.value,
+                    first_seen: event.timestamp,
+                    last_seen: event.timestamp,
+                    occurrences: 0,
+                });
+            entry.first_seen = entry.first_seen.min(event.timestamp);
+            entry.last_seen = entry.last_seen.max(event.timestamp);
+            entry.occurrences += 1;
+        }
+    }
+
+    observations.into_values().collect()
+}
+
+/// Fold a batch's property keys into `overwatch.property_keys`.
+///
+/// The batch is pre-aggregated in memory and inserted as RowBinary rows;
+/// the view builds the distinct-value states, so counts merge server-side.
+/// Returns the number of distinct (key, type, value) observations written.
+pub async fn record_property_keys(
+    client: &ClickHouseClient,
+    events: &[ClickHouseEvent],
+    properties: &[Vec<PropertyValue>],
+) -> Result<usize> {
+    let observations = observe(events, properties);
+    if observations.is_empty() {
+        return Ok(0);
+    }
+
+    let mut insert = client
+        .inner()
+        .insert("overwatch.property_observations")
+        .map_err(|e| engine_core::Error::internal(format!("Insert error: {}", e)))?;
+    for observation in &observations {
+        insert
+            .write(observation)
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Write error: {}", e)))?;
+    }
+    insert
+        .end()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Property catalog error: {}", e)))?;
+
+    Ok(observations.len())
+}
+
+/// Catalog entry for one property key and value type.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct PropertyKeyInfo {
+    pub event_name: String,
+    pub key: String,
+    pub value_type: String,
+    /// First seen (Unix ms)
+    pub first_seen: i64,
+    /// Last seen (Unix ms)
+    pub last_seen: i64,
+    pub occurrences: u64,
+    /// Approximate distinct values
+    pub cardinality: u64,
+}
+
+/// List a project's property keys, most used first.
+pub async fn property_catalog(
+    client: &ClickHouseClient,
+    project_id: &str,
+    event_name: Option<&str>,
+) -> Result<Vec<PropertyKeyInfo>> {
+    let event_filter = if event_name.is_some() {
+        "AND event_name = ?"
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT event_name, key, toString(value_type) AS value_type, \
+                toUnixTimestamp64Milli(min(first_seen)) AS first_seen, \
+                toUnixTimestamp64Milli(max(last_seen)) AS last_seen, \
+                sum(occurrences) AS occurrences, \
+                uniqMerge(distinct_values) AS cardinality \
+         FROM overwatch.property_keys \
+         WHERE project_id = ? {event_filter} \
+         GROUP BY event_name, key, value_type \
+         ORDER BY occurrences DESC"
+    );
+
+    let mut query = client.inner().query(&sql).bind(project_id);
+    if let Some(event_name) = event_name {
+        query = query.bind(event_name);
+    }
+
+    query
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_properties() {
+        let data = r#"{"name":"signup","properties":{"plan":"pro","seats":5,"trial":true,"tags":["a"],"gone":null}}"#;
+        let mut props = extract_properties(data);
+        props.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let keys: Vec<&str> = props.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, vec!["plan", "seats", "tags", "trial"]);
+        assert_eq!(props[1].value_type, PropertyType::Number);
+        assert_eq!(props[1].number, Some(5.0));
+        assert_eq!(props[2].value, r#"["a"]"#);
+        assert_eq!(props[3].value, "true");
+
+        let (strings, numbers) = property_maps(&props);
+        assert_eq!(strings.len(), 4);
+        assert_eq!(numbers, vec![("seats".to_string(), 5.0)]);
+    }
+
+    #[test]
+    fn test_extract_properties_missing() {
+        assert!(extract_properties("{}").is_empty());
+        assert!(extract_properties(r#"{"properties":"nope"}"#).is_empty());
+        assert!(extract_properties("not json").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_property_keys_inserts_rows() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let event = |timestamp: i64, plan: &str| ClickHouseEvent {
+            event_id: format!("e{}", timestamp),
+            project_id: "proj".to_string(),
+            session_id: "s1".to_string(),
+            user_id: None,
+            event_type: "custom".to_string(),
+            custom_name: Some("signup".to_string()),
+            timestamp,
+            url: String::new(),
+            path: String::new(),
+            referrer: String::new(),
+            user_agent: String::new(),
+            device_type: String::new(),
+            browser: String::new(),
+            browser_version: String::new(),
+            os: String::new(),
+            country: String::new(),
+            region: None,
+            city: None,
+            data: format!(r#"{{"properties":{{"plan":"{}","seats":5}}}}"#, plan),
+        };
+        let events = vec![event(1, "pro"), event(2, "pro"), event(3, "free")];
+        let recorded = mock.add(handlers::record::<PropertyObservation>());
+
+        let written = record_property_keys(&client, &events, &batch_properties(&events))
+            .await
+            .unwrap();
+        assert_eq!(written, 3);
+
+        let mut rows: Vec<PropertyObservation> = recorded.collect().await;
+        rows.sort_by(|a, b| (&a.key, &a.value).cmp(&(&b.key, &b.value)));
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.event_name.as_str(),
+                    r.key.as_str(),
+                    r.value_type.as_str(),
+                    r.value.as_str(),
+                    r.first_seen,
+                    r.last_seen,
+                    r.occurrences,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("signup", "plan", "string", "free", 3, 3, 1),
+                ("signup", "plan", "string", "pro", 1, 2, 2),
+                ("signup", "seats", "number", "5", 1, 3, 3),
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
 //! Query functions for reading data back (used in tests and admin).
+//!
+//! Dashboard reads live in [`crate::analytics`].
 
 use crate::client::ClickHouseClient;
 use clickhouse::Row;
@@ -69,6 +71,39 @@ pub async fn query_all_events(client: &ClickHouseClient, limit: u32) -> Result<V
     Ok(rows)
 }
 
+/// Events per project and day.
+#[derive(Debug, Clone, PartialEq, Eq, Row, Deserialize)]
+pub struct DailyProjectCount {
+    pub project_id: String,
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub events: u64,
+}
+
+/// Count events per project for each of the last `days` completed days.
+///
+/// Today is excluded so in-flight batches don't show up as differences
+/// when comparing sinks.
+pub async fn daily_project_counts(
+    client: &ClickHouseClient,
+    days: u32,
+) -> Result<Vec<DailyProjectCount>> {
+    let rows: Vec<DailyProjectCount> = client
+        .inner()
+        .query(
+            "SELECT project_id, toString(toDate(timestamp)) AS day, count() AS events \
+             FROM overwatch.events \
+             WHERE timestamp >= today() - ? AND timestamp < today() \
+             GROUP BY project_id, day \
+             ORDER BY project_id, day",
+        )
+        .bind(days)
+        .fetch_all()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Query error: {}", e)))?;
+    Ok(rows)
+}
+
 /// Delete all events for a project (test cleanup).
 pub async fn delete_project_events(client: &ClickHouseClient, project_id: &str) -> Result<()> {
     client
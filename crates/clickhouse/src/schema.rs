@@ -44,6 +44,10 @@ CREATE TABLE IF NOT EXISTS overwatch.events (
     -- Extensible JSON data blob for event-specific fields
     data String,
 
+    -- Custom properties from data.properties (all values, and numeric values)
+    properties Map(String, String),
+    properties_num Map(String, Float64),
+
     -- Metadata
     created_at DateTime DEFAULT now()
 )
@@ -322,13 +326,15 @@ SETTINGS index_granularity = 8192
 "#;
 
 /// SQL for creating the custom_events table.
+///
+/// `properties` is stringified like `overwatch.events.properties`.
 pub const CREATE_CUSTOM_EVENTS_TABLE: &str = r#"
 CREATE TABLE IF NOT EXISTS overwatch.custom_events (
     project_id String,
     session_id String,
     timestamp DateTime64(3),
     name String,
-    properties String,
+    properties Map(String, String),
     url String
 )
 ENGINE = MergeTree()
@@ -337,6 +343,280 @@ ORDER BY (project_id, timestamp)
 SETTINGS index_granularity = 8192
 "#;
 
+/// SQL for creating the per-project property key catalog.
+///
+/// Fed by [`CREATE_PROPERTY_KEYS_VIEW`] from the observations the consumer
+/// writes with [`crate::properties::record_property_keys`].
+pub const CREATE_PROPERTY_KEYS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.property_keys (
+    project_id String,
+    event_name String,
+    key String,
+    value_type LowCardinality(String),
+    first_seen SimpleAggregateFunction(min, DateTime64(3)),
+    last_seen SimpleAggregateFunction(max, DateTime64(3)),
+    occurrences SimpleAggregateFunction(sum, UInt64),
+    distinct_values AggregateFunction(uniq, String)
+)
+ENGINE = AggregatingMergeTree()
+ORDER BY (project_id, event_name, key, value_type)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the property observation input table.
+///
+/// Stores nothing; [`CREATE_PROPERTY_KEYS_VIEW`] folds each insert into
+/// [`CREATE_PROPERTY_KEYS_TABLE`].
+pub const CREATE_PROPERTY_OBSERVATIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.property_observations (
+    project_id String,
+    event_name String,
+    key String,
+    value_type LowCardinality(String),
+    value String,
+    first_seen DateTime64(3),
+    last_seen DateTime64(3),
+    occurrences UInt64
+)
+ENGINE = Null
+"#;
+
+/// SQL for creating the view that feeds [`CREATE_PROPERTY_KEYS_TABLE`].
+pub const CREATE_PROPERTY_KEYS_VIEW: &str = r#"
+CREATE MATERIALIZED VIEW IF NOT EXISTS overwatch.property_keys_mv
+TO overwatch.property_keys AS
+SELECT
+    project_id,
+    event_name,
+    key,
+    value_type,
+    min(first_seen) AS first_seen,
+    max(last_seen) AS last_seen,
+    sum(occurrences) AS occurrences,
+    uniqState(value) AS distinct_values
+FROM overwatch.property_observations
+GROUP BY project_id, event_name, key, value_type
+"#;
+
+/// SQL for creating the Parquet archive manifest.
+///
+/// One row per exported file, written by [`crate::archive::export_partition`].
+pub const CREATE_ARCHIVE_MANIFEST_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.archive_manifest (
+    table LowCardinality(String),
+    partition_id String,
+    project_id String,
+    location String,
+    rows UInt64,
+    checksum UInt64,
+    exported_at DateTime64(3)
+)
+ENGINE = MergeTree()
+ORDER BY (table, partition_id, project_id, exported_at)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the retention audit log.
+///
+/// One row per partition dropped by the retention worker.
+pub const CREATE_RETENTION_AUDIT_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.retention_audit (
+    executed_at DateTime64(3),
+    table LowCardinality(String),
+    partition String,
+    partition_id String,
+    rows UInt64,
+    bytes UInt64,
+    reason String,
+    archived_files UInt32
+)
+ENGINE = MergeTree()
+ORDER BY (table, partition_id, executed_at)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the data-subject erasure request log.
+///
+/// Every status change inserts a new version; read with `FINAL`.
+pub const CREATE_ERASURE_REQUESTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.erasure_requests (
+    request_id String,
+    project_id String,
+    user_id String,
+    status LowCardinality(String),
+    sessions UInt64,
+    session_ids Array(String),
+    rows_erased UInt64,
+    archived_files UInt64 DEFAULT 0,
+    error String,
+    requested_at DateTime64(3),
+    completed_at Nullable(DateTime64(3)),
+    updated_at DateTime64(3)
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (project_id, request_id)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the event forwarding delivery log.
+///
+/// One row per batch sent to a customer destination, whether it was
+/// delivered or dead-lettered.
+pub const CREATE_FORWARDING_DELIVERIES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.forwarding_deliveries (
+    delivery_id String,
+    project_id String,
+    destination LowCardinality(String),
+    status LowCardinality(String),
+    events UInt32,
+    attempts UInt32,
+    http_status UInt16,
+    error String,
+    duration_ms UInt64,
+    created_at DateTime64(3)
+)
+ENGINE = MergeTree()
+ORDER BY (project_id, destination, created_at)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the event forwarding dead-letter queue.
+///
+/// Holds the JSON payload of every batch that exhausted its retries,
+/// keyed by destination.
+pub const CREATE_FORWARDING_DLQ_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.forwarding_dlq (
+    delivery_id String,
+    project_id String,
+    destination LowCardinality(String),
+    url String,
+    payload String,
+    events UInt32,
+    attempts UInt32,
+    error String,
+    failed_at DateTime64(3)
+)
+ENGINE = MergeTree()
+ORDER BY (project_id, destination, failed_at)
+SETTINGS index_granularity = 8192
+"#;
+
+/// SQL for creating the event forwarding cursor table.
+///
+/// The next Redpanda offset to read per forwarding destination; the latest
+/// row per destination wins.
+pub const CREATE_FORWARDING_OFFSETS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.forwarding_offsets (
+    group_id String,
+    project_id String,
+    destination String,
+    partition Int32,
+    next_offset Int64,
+    updated_at DateTime64(3) DEFAULT now64(3)
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (group_id, project_id, destination, partition)
+"#;
+
+/// SQL for creating the MAU overage table.
+///
+/// Summed per project, month and over-limit action for billing.
+pub const CREATE_MAU_OVERAGES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.mau_overages (
+    project_id String,
+    month Date,
+    action LowCardinality(String),
+    events UInt64,
+    sampled_out UInt64,
+    estimated_users SimpleAggregateFunction(max, UInt64)
+)
+ENGINE = SummingMergeTree()
+ORDER BY (project_id, month, action)
+"#;
+
+/// SQL for creating the per-project monthly user table.
+///
+/// Distinct users (`user_id`, else `session_id`) per project and month as a
+/// `uniqCombined` state, fed from `overwatch.events` by
+/// [`CREATE_MAU_USERS_VIEW`]. Lets MAU estimates survive restarts and cover
+/// every replica.
+pub const CREATE_MAU_USERS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.mau_users (
+    project_id String,
+    month Date,
+    users AggregateFunction(uniqCombined, String)
+)
+ENGINE = AggregatingMergeTree()
+ORDER BY (project_id, month)
+"#;
+
+/// SQL for creating the view that feeds [`CREATE_MAU_USERS_TABLE`].
+pub const CREATE_MAU_USERS_VIEW: &str = r#"
+CREATE MATERIALIZED VIEW IF NOT EXISTS overwatch.mau_users_mv
+TO overwatch.mau_users AS
+SELECT
+    project_id,
+    toStartOfMonth(timestamp, 'UTC') AS month,
+    uniqCombinedState(ifNull(user_id, session_id)) AS users
+FROM overwatch.events
+GROUP BY project_id, month
+"#;
+
+/// SQL for creating the tenants table.
+///
+/// Read by the local key store; the latest row per tenant wins.
+pub const CREATE_TENANTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.tenants (
+    id UUID,
+    name String,
+    tier LowCardinality(String) DEFAULT 'free',
+    active Bool DEFAULT true,
+    rate_limit Nullable(UInt32),
+    signing_secret Nullable(String),
+    require_signature Bool DEFAULT false,
+    ip_allowlist Array(String),
+    ip_denylist Array(String),
+    allowed_origins Array(String),
+    updated_at DateTime64(3) DEFAULT now64(3)
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY id
+"#;
+
+/// SQL for creating the API keys table.
+///
+/// Keys are stored as SHA-256 hex; the latest row per hash wins.
+pub const CREATE_API_KEYS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.api_keys (
+    key_hash String,
+    tenant_id UUID,
+    name String,
+    active Bool DEFAULT true,
+    expires_at Nullable(DateTime64(3)),
+    permissions Array(String),
+    key_type LowCardinality(String) DEFAULT 'browser',
+    updated_at DateTime64(3) DEFAULT now64(3)
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY key_hash
+"#;
+
+/// SQL for creating the schema backfill log.
+///
+/// One row per `MATERIALIZE` mutation submitted by
+/// [`migrate_data_columns`], so a backfill that failed to submit is retried
+/// on the next startup even though its column or index already exists.
+pub const CREATE_SCHEMA_BACKFILLS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS overwatch.schema_backfills (
+    table String,
+    kind LowCardinality(String),
+    name String,
+    submitted_at DateTime64(3) DEFAULT now64(3)
+)
+ENGINE = ReplacingMergeTree(submitted_at)
+ORDER BY (table, kind, name)
+"#;
+
 /// SQL for creating the database.
 pub const CREATE_DATABASE: &str = r#"
 CREATE DATABASE IF NOT EXISTS overwatch
@@ -362,10 +642,26 @@ pub fn all_tables() -> Vec<&'static str> {
         CREATE_RESOURCE_LOADS_TABLE,
         CREATE_GEOGRAPHIC_TABLE,
         CREATE_CUSTOM_EVENTS_TABLE,
+        CREATE_PROPERTY_KEYS_TABLE,
+        CREATE_PROPERTY_OBSERVATIONS_TABLE,
+        CREATE_PROPERTY_KEYS_VIEW,
+        CREATE_ARCHIVE_MANIFEST_TABLE,
+        CREATE_RETENTION_AUDIT_TABLE,
+        CREATE_ERASURE_REQUESTS_TABLE,
+        CREATE_FORWARDING_DELIVERIES_TABLE,
+        CREATE_FORWARDING_DLQ_TABLE,
+        CREATE_FORWARDING_OFFSETS_TABLE,
+        CREATE_MAU_OVERAGES_TABLE,
+        CREATE_MAU_USERS_TABLE,
+        CREATE_MAU_USERS_VIEW,
+        CREATE_TENANTS_TABLE,
+        CREATE_API_KEYS_TABLE,
+        CREATE_SCHEMA_BACKFILLS_TABLE,
     ]
 }
 
 use crate::client::ClickHouseClient;
+use crate::config::{MaterializedColumn, MaterializedType};
 use engine_core::Result;
 use tracing::info;
 
@@ -429,6 +725,8 @@ pub async fn init_schema(client: &ClickHouseClient) -> Result<()> {
     }
 
     migrate_events_columns(client).await?;
+    migrate_added_columns(client).await?;
+    migrate_data_columns(client, &client.config().materialized_columns).await?;
 
     Ok(())
 }
@@ -440,6 +738,8 @@ pub async fn migrate_events_columns(client: &ClickHouseClient) -> Result<()> {
     let statements = [
         "ALTER TABLE overwatch.events ADD COLUMN IF NOT EXISTS event_type LowCardinality(String) DEFAULT type AFTER user_id",
         "ALTER TABLE overwatch.events ADD COLUMN IF NOT EXISTS custom_name Nullable(String) AFTER event_type",
+        "ALTER TABLE overwatch.events ADD COLUMN IF NOT EXISTS properties Map(String, String) AFTER data",
+        "ALTER TABLE overwatch.events ADD COLUMN IF NOT EXISTS properties_num Map(String, Float64) AFTER properties",
     ];
 
     for sql in statements {
@@ -454,6 +754,272 @@ pub async fn migrate_events_columns(client: &ClickHouseClient) -> Result<()> {
     Ok(())
 }
 
+/// Columns added to tables after they were first released, for databases
+/// created by an earlier version.
+pub const ADDED_COLUMNS: &[&str] = &[
+    "ALTER TABLE overwatch.erasure_requests ADD COLUMN IF NOT EXISTS archived_files UInt64 DEFAULT 0 AFTER rows_erased",
+    "ALTER TABLE overwatch.erasure_requests ADD COLUMN IF NOT EXISTS session_ids Array(String) AFTER sessions",
+    "ALTER TABLE overwatch.tenants ADD COLUMN IF NOT EXISTS signing_secret Nullable(String) AFTER rate_limit",
+    "ALTER TABLE overwatch.tenants ADD COLUMN IF NOT EXISTS require_signature Bool DEFAULT false AFTER signing_secret",
+    "ALTER TABLE overwatch.tenants ADD COLUMN IF NOT EXISTS ip_allowlist Array(String) AFTER require_signature",
+    "ALTER TABLE overwatch.tenants ADD COLUMN IF NOT EXISTS ip_denylist Array(String) AFTER ip_allowlist",
+    "ALTER TABLE overwatch.tenants ADD COLUMN IF NOT EXISTS allowed_origins Array(String) AFTER ip_denylist",
+    "ALTER TABLE overwatch.api_keys ADD COLUMN IF NOT EXISTS key_type LowCardinality(String) DEFAULT 'browser' AFTER permissions",
+];
+
+/// Add [`ADDED_COLUMNS`] to existing tables.
+///
+/// This migration is idempotent and safe to run on every startup.
+pub async fn migrate_added_columns(client: &ClickHouseClient) -> Result<()> {
+    for sql in ADDED_COLUMNS {
+        client
+            .inner()
+            .query(sql)
+            .execute()
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Column migration error: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// Data-skipping indexes on `overwatch.events` (name, definition).
+///
+/// Bloom filters let per-session and per-user lookups skip granules instead
+/// of scanning every row of the project.
+pub const EVENTS_SKIP_INDEXES: &[(&str, &str)] = &[
+    (
+        "idx_session_id",
+        "session_id TYPE bloom_filter(0.01) GRANULARITY 4",
+    ),
+    (
+        "idx_user_id",
+        "user_id TYPE bloom_filter(0.01) GRANULARITY 4",
+    ),
+];
+
+/// Convert a JSON `String` `custom_events.properties` to a map.
+///
+/// The old column is kept as `properties_json` and the map is backfilled
+/// from it by [`migrate_data_columns`]; string values are unquoted and nulls
+/// dropped, matching [`crate::properties::extract_properties`].
+pub const CUSTOM_EVENTS_PROPERTIES_MIGRATION: &[&str] = &[
+    "ALTER TABLE overwatch.custom_events RENAME COLUMN IF EXISTS properties TO properties_json",
+    "ALTER TABLE overwatch.custom_events ADD COLUMN IF NOT EXISTS properties Map(String, String) \
+     DEFAULT CAST(arrayMap(kv -> (kv.1, if(startsWith(kv.2, '\"'), JSONExtractString(kv.2), kv.2)), \
+     arrayFilter(kv -> kv.2 != 'null', JSONExtractKeysAndValuesRaw(properties_json))), \
+     'Map(String, String)') AFTER name",
+];
+
+/// Build the `ADD COLUMN` definition for a materialized `data` field.
+///
+/// Names and keys come from config and are interpolated into DDL, so both
+/// are restricted to a safe character set.
+pub fn materialized_column_definition(column: &MaterializedColumn) -> Result<String> {
+    let valid_name = column
+        .name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && column
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(engine_core::Error::internal(format!(
+            "Invalid materialized column name: {}",
+            column.name
+        )));
+    }
+
+    let valid_key = !column.json_key.is_empty()
+        && column.json_key.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        });
+    if !valid_key {
+        return Err(engine_core::Error::internal(format!(
+            "Invalid materialized column key for {}: {}",
+            column.name, column.json_key
+        )));
+    }
+
+    let path = column
+        .json_key
+        .split('.')
+        .map(|part| format!("'{}'", part))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok(match column.column_type {
+        MaterializedType::Float64 => format!(
+            "{} Nullable(Float64) MATERIALIZED if(JSONHas(data, {path}), JSONExtractFloat(data, {path}), NULL)",
+            column.name
+        ),
+        MaterializedType::String => format!(
+            "{} String MATERIALIZED JSONExtractString(data, {path})",
+            column.name
+        ),
+    })
+}
+
+/// Add materialized `data` columns and skip indexes to `overwatch.events`,
+/// and convert a `String` `custom_events.properties` to a map.
+///
+/// Idempotent. Columns and indexes are backfilled into existing parts with
+/// `MATERIALIZE COLUMN` / `MATERIALIZE INDEX`; these run as background
+/// mutations, so startup does not wait for them. Submitted backfills are
+/// recorded in `overwatch.schema_backfills`, and any that are not recorded
+/// are submitted again, so a failure between adding a column and
+/// materializing it is retried on the next run.
+pub async fn migrate_data_columns(
+    client: &ClickHouseClient,
+    columns: &[MaterializedColumn],
+) -> Result<()> {
+    let migration_error = |e: clickhouse::error::Error| {
+        engine_core::Error::internal(format!("Data column migration error: {}", e))
+    };
+
+    let backfills: Vec<(String, String, String)> = client
+        .inner()
+        .query("SELECT table, kind, name FROM overwatch.schema_backfills FINAL")
+        .fetch_all()
+        .await
+        .map_err(migration_error)?;
+
+    let existing_columns: Vec<String> = client
+        .inner()
+        .query("SELECT name FROM system.columns WHERE database = 'overwatch' AND table = 'events'")
+        .fetch_all()
+        .await
+        .map_err(migration_error)?;
+
+    for column in columns {
+        let definition = materialized_column_definition(column)?;
+        if !existing_columns.contains(&column.name) {
+            client
+                .inner()
+                .query(&format!(
+                    "ALTER TABLE overwatch.events ADD COLUMN IF NOT EXISTS {}",
+                    definition
+                ))
+                .execute()
+                .await
+                .map_err(migration_error)?;
+
+            info!(column = %column.name, key = %column.json_key, "Added materialized column");
+        }
+
+        backfill(client, &backfills, "events", "column", &column.name).await?;
+    }
+
+    let existing_indexes: Vec<String> = client
+        .inner()
+        .query("SELECT name FROM system.data_skipping_indices WHERE database = 'overwatch' AND table = 'events'")
+        .fetch_all()
+        .await
+        .map_err(migration_error)?;
+
+    for (name, definition) in EVENTS_SKIP_INDEXES {
+        if !existing_indexes.iter().any(|i| i == name) {
+            client
+                .inner()
+                .query(&format!(
+                    "ALTER TABLE overwatch.events ADD INDEX IF NOT EXISTS {} {}",
+                    name, definition
+                ))
+                .execute()
+                .await
+                .map_err(migration_error)?;
+
+            info!(index = name, "Added skip index");
+        }
+
+        backfill(client, &backfills, "events", "index", name).await?;
+    }
+
+    let custom_properties_type: Option<String> = client
+        .inner()
+        .query("SELECT type FROM system.columns WHERE database = 'overwatch' AND table = 'custom_events' AND name = 'properties'")
+        .fetch_optional()
+        .await
+        .map_err(migration_error)?;
+
+    // Also resumes a conversion interrupted after the rename
+    if custom_properties_type.as_deref() != Some("Map(String, String)") {
+        for sql in CUSTOM_EVENTS_PROPERTIES_MIGRATION {
+            client
+                .inner()
+                .query(sql)
+                .execute()
+                .await
+                .map_err(migration_error)?;
+        }
+
+        info!("Converted custom_events.properties to a map");
+    }
+
+    // Only tables created before the map column have anything to backfill
+    let converted: Option<String> = client
+        .inner()
+        .query("SELECT name FROM system.columns WHERE database = 'overwatch' AND table = 'custom_events' AND name = 'properties_json'")
+        .fetch_optional()
+        .await
+        .map_err(migration_error)?;
+    if converted.is_some() {
+        backfill(client, &backfills, "custom_events", "column", "properties").await?;
+    }
+
+    Ok(())
+}
+
+/// Submit a `MATERIALIZE` mutation for a column or index on an `overwatch`
+/// table and record it, unless an earlier run already did.
+async fn backfill(
+    client: &ClickHouseClient,
+    backfills: &[(String, String, String)],
+    table: &str,
+    kind: &str,
+    name: &str,
+) -> Result<()> {
+    let submitted = backfills
+        .iter()
+        .any(|(t, k, n)| t == table && k == kind && n == name);
+    if submitted {
+        return Ok(());
+    }
+
+    let backfill_error = |e: clickhouse::error::Error| {
+        engine_core::Error::internal(format!("Backfill of {}.{} failed: {}", table, name, e))
+    };
+
+    client
+        .inner()
+        .query(&format!(
+            "ALTER TABLE overwatch.{} MATERIALIZE {} {}",
+            table,
+            kind.to_uppercase(),
+            name
+        ))
+        .execute()
+        .await
+        .map_err(backfill_error)?;
+    client
+        .inner()
+        .query("INSERT INTO overwatch.schema_backfills (table, kind, name) VALUES (?, ?, ?)")
+        .bind(table)
+        .bind(kind)
+        .bind(name)
+        .execute()
+        .await
+        .map_err(backfill_error)?;
+
+    info!(table, kind, name, "Backfill scheduled");
+    Ok(())
+}
+
 /// Event type values for the type column.
 pub mod event_types {
     // Core analytics events
@@ -538,3 +1104,120 @@ pub mod event_types {
     /// High-volume event types that may need sampling.
     pub const HIGH_VOLUME: &[&str] = &[MOUSE_MOVE, ENGAGEMENT_SNAPSHOT];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_materialized_column_definition() {
+        let float = MaterializedColumn::new("lcp", "metrics.lcp", MaterializedType::Float64);
+        assert_eq!(
+            materialized_column_definition(&float).unwrap(),
+            "lcp Nullable(Float64) MATERIALIZED if(JSONHas(data, 'metrics', 'lcp'), \
+             JSONExtractFloat(data, 'metrics', 'lcp'), NULL)"
+        );
+
+        let string = MaterializedColumn::new("trigger_id", "triggerId", MaterializedType::String);
+        assert_eq!(
+            materialized_column_definition(&string).unwrap(),
+            "trigger_id String MATERIALIZED JSONExtractString(data, 'triggerId')"
+        );
+    }
+
+    #[test]
+    fn test_materialized_column_rejects_unsafe_input() {
+        let bad_name = MaterializedColumn::new("lcp; DROP", "lcp", MaterializedType::Float64);
+        assert!(materialized_column_definition(&bad_name).is_err());
+
+        let bad_key = MaterializedColumn::new("lcp", "lcp')", MaterializedType::Float64);
+        assert!(materialized_column_definition(&bad_key).is_err());
+
+        let empty_part = MaterializedColumn::new("lcp", "metrics..lcp", MaterializedType::Float64);
+        assert!(materialized_column_definition(&empty_part).is_err());
+    }
+
+    fn index_backfills() -> Vec<(String, String, String)> {
+        EVENTS_SKIP_INDEXES
+            .iter()
+            .map(|(name, _)| ("events".to_string(), "index".to_string(), name.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_migrate_custom_events_properties() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        mock.add(handlers::provide(index_backfills()));
+        mock.add(handlers::provide(Vec::<String>::new()));
+        let indexes: Vec<String> = EVENTS_SKIP_INDEXES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        mock.add(handlers::provide(indexes));
+        mock.add(handlers::provide(vec!["String".to_string()]));
+        let recorded: Vec<_> = CUSTOM_EVENTS_PROPERTIES_MIGRATION
+            .iter()
+            .map(|_| mock.add(handlers::record_ddl()))
+            .collect();
+        mock.add(handlers::provide(vec!["properties_json".to_string()]));
+        let materialize = mock.add(handlers::record_ddl());
+        let record = mock.add(handlers::record_ddl());
+
+        migrate_data_columns(&client, &[]).await.unwrap();
+        for (control, sql) in recorded.into_iter().zip(CUSTOM_EVENTS_PROPERTIES_MIGRATION) {
+            assert_eq!(control.query().await, *sql);
+        }
+        assert_eq!(
+            materialize.query().await,
+            "ALTER TABLE overwatch.custom_events MATERIALIZE COLUMN properties"
+        );
+        assert!(record
+            .query()
+            .await
+            .contains("VALUES ('custom_events', 'column', 'properties')"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_resubmits_unrecorded_backfill() {
+        use clickhouse::test::{handlers, Mock};
+
+        let mock = Mock::new();
+        let client = ClickHouseClient::new(crate::ClickHouseConfig {
+            url: mock.url().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // The column was added by an earlier run whose MATERIALIZE failed
+        mock.add(handlers::provide(index_backfills()));
+        mock.add(handlers::provide(vec!["lcp".to_string()]));
+        let materialize = mock.add(handlers::record_ddl());
+        let record = mock.add(handlers::record_ddl());
+        let indexes: Vec<String> = EVENTS_SKIP_INDEXES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        mock.add(handlers::provide(indexes));
+        mock.add(handlers::provide(vec!["Map(String, String)".to_string()]));
+        mock.add(handlers::provide(Vec::<String>::new()));
+
+        let lcp = MaterializedColumn::new("lcp", "metrics.lcp", MaterializedType::Float64);
+        migrate_data_columns(&client, &[lcp]).await.unwrap();
+        assert_eq!(
+            materialize.query().await,
+            "ALTER TABLE overwatch.events MATERIALIZE COLUMN lcp"
+        );
+        assert!(record
+            .query()
+            .await
+            .contains("VALUES ('events', 'column', 'lcp')"));
+    }
+}
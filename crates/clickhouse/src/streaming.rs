@@ -0,0 +1,284 @@
+//! Streaming RowBinary inserts with row- and byte-bounded chunking.
+//!
+//! A single `INSERT` per consumer batch means part size (and client memory)
+//! grows with whatever the broker hands us. The streaming inserter instead
+//! cuts a new `INSERT` whenever the current one reaches `insert_max_rows`
+//! rows or `insert_max_bytes` RowBinary bytes, so every part lands in a
+//! predictable size band even during load spikes.
+//!
+//! Chunks are committed independently. If a later chunk fails, earlier chunks
+//! are already stored and a retry of the whole batch re-inserts them - the
+//! same at-least-once contract the consumer already has with Redpanda.
+
+use crate::client::ClickHouseClient;
+use crate::config::ClickHouseConfig;
+use crate::insert::ClickHouseEventRow;
+use clickhouse::Row;
+use engine_core::Result;
+use serde::Serialize;
+use std::ops::Range;
+use std::time::Instant;
+use telemetry::metrics;
+use tracing::debug;
+
+/// Size of a row once encoded as RowBinary.
+///
+/// Used to bound the bytes sent per `INSERT` without serializing twice.
+/// The estimate only needs to be close; it must not be wildly low.
+pub trait EncodedLen {
+    /// Returns the RowBinary-encoded size of this row in bytes.
+    fn encoded_len(&self) -> usize;
+}
+
+/// RowBinary size of a `String` (LEB128 length prefix + bytes).
+pub fn string_len(s: &str) -> usize {
+    varint_len(s.len()) + s.len()
+}
+
+/// RowBinary size of a `Nullable(String)` (null marker + optional string).
+pub fn nullable_string_len(s: Option<&str>) -> usize {
+    1 + s.map(string_len).unwrap_or(0)
+}
+
+/// Length of an unsigned LEB128 varint.
+fn varint_len(mut n: usize) -> usize {
+    let mut len = 1;
+    while n >= 0x80 {
+        n >>= 7;
+        len += 1;
+    }
+    len
+}
+
+impl EncodedLen for ClickHouseEventRow {
+    fn encoded_len(&self) -> usize {
+        string_len(&self.event_id)
+            + string_len(&self.project_id)
+            + string_len(&self.session_id)
+            + nullable_string_len(self.user_id.as_deref())
+            + string_len(&self.event_type)
+            + nullable_string_len(self.custom_name.as_deref())
+            + 8 // timestamp: DateTime64(3)
+            + string_len(&self.url)
+            + string_len(&self.path)
+            + string_len(&self.referrer)
+            + string_len(&self.user_agent)
+            + string_len(&self.device_type)
+            + string_len(&self.browser)
+            + string_len(&self.browser_version)
+            + string_len(&self.os)
+            + string_len(&self.country)
+            + nullable_string_len(self.region.as_deref())
+            + nullable_string_len(self.city.as_deref())
+            + string_len(&self.data)
+            + varint_len(self.properties.len())
+            + self
+                .properties
+                .iter()
+                .map(|(k, v)| string_len(k) + string_len(v))
+                .sum::<usize>()
+            + varint_len(self.properties_num.len())
+            + self
+                .properties_num
+                .iter()
+                .map(|(k, _)| string_len(k) + 8)
+                .sum::<usize>()
+    }
+}
+
+/// Streaming insert settings.
+#[derive(Debug, Clone)]
+pub struct StreamingInsertConfig {
+    /// Maximum rows per `INSERT`
+    pub max_rows: usize,
+    /// Maximum RowBinary bytes per `INSERT`
+    pub max_bytes: usize,
+    /// Use `async_insert=1, wait_for_async_insert=1`
+    pub async_insert: bool,
+}
+
+impl StreamingInsertConfig {
+    /// Builds streaming settings from the client configuration.
+    pub fn from_config(config: &ClickHouseConfig) -> Self {
+        Self {
+            max_rows: config.insert_max_rows.max(1),
+            max_bytes: config.insert_max_bytes.max(1),
+            async_insert: config.async_insert,
+        }
+    }
+}
+
+impl Default for StreamingInsertConfig {
+    fn default() -> Self {
+        Self::from_config(&ClickHouseConfig::default())
+    }
+}
+
+/// Inserter that splits a batch into bounded `INSERT` statements.
+pub struct StreamingInserter {
+    client: ClickHouseClient,
+    table: String,
+    config: StreamingInsertConfig,
+}
+
+impl StreamingInserter {
+    /// Creates an inserter for `table` using the client's configuration.
+    pub fn new(client: &ClickHouseClient, table: impl Into<String>) -> Self {
+        Self {
+            config: StreamingInsertConfig::from_config(client.config()),
+            client: client.clone(),
+            table: table.into(),
+        }
+    }
+
+    /// Overrides the streaming settings.
+    pub fn with_config(mut self, config: StreamingInsertConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the streaming settings.
+    pub fn config(&self) -> &StreamingInsertConfig {
+        &self.config
+    }
+
+    /// Inserts `rows`, cutting a new `INSERT` at the configured bounds.
+    ///
+    /// Each chunk's latency is recorded in `clickhouse_latency_ms`.
+    /// Returns the number of rows inserted.
+    pub async fn insert<T>(&self, rows: &[T]) -> Result<usize>
+    where
+        T: Row + Serialize + EncodedLen,
+    {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let chunks = chunk_bounds(
+            rows.iter().map(EncodedLen::encoded_len),
+            self.config.max_rows,
+            self.config.max_bytes,
+        );
+        let chunk_count = chunks.len();
+
+        for chunk in chunks {
+            self.insert_chunk(&rows[chunk]).await?;
+        }
+
+        debug!(
+            table = %self.table,
+            rows = rows.len(),
+            chunks = chunk_count,
+            "Streamed rows to ClickHouse"
+        );
+
+        Ok(rows.len())
+    }
+
+    /// Sends one bounded `INSERT`.
+    async fn insert_chunk<T>(&self, rows: &[T]) -> Result<()>
+    where
+        T: Row + Serialize,
+    {
+        let start = Instant::now();
+
+        let mut insert = self.client.inner().insert::<T>(&self.table).map_err(|e| {
+            metrics().clickhouse_insert_errors.inc();
+            engine_core::Error::internal(format!("Insert error: {}", e))
+        })?;
+
+        if self.config.async_insert {
+            insert = insert
+                .with_option("async_insert", "1")
+                .with_option("wait_for_async_insert", "1");
+        }
+
+        for row in rows {
+            insert.write(row).await.map_err(|e| {
+                metrics().clickhouse_insert_errors.inc();
+                engine_core::Error::internal(format!("Write error: {}", e))
+            })?;
+        }
+
+        insert.end().await.map_err(|e| {
+            metrics().clickhouse_insert_errors.inc();
+            engine_core::Error::internal(format!("End error: {}", e))
+        })?;
+
+        metrics()
+            .clickhouse_latency_ms
+            .observe(start.elapsed().as_millis() as u64);
+        metrics().clickhouse_inserts.inc();
+
+        Ok(())
+    }
+}
+
+/// Split rows into consecutive ranges bounded by row count and byte size.
+///
+/// A single row larger than `max_bytes` gets a chunk of its own rather than
+/// being dropped.
+fn chunk_bounds(
+    sizes: impl Iterator<Item = usize>,
+    max_rows: usize,
+    max_bytes: usize,
+) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut rows = 0;
+    let mut bytes = 0;
+
+    for (i, size) in sizes.enumerate() {
+        if rows > 0 && (rows >= max_rows || bytes + size > max_bytes) {
+            chunks.push(start..i);
+            start = i;
+            rows = 0;
+            bytes = 0;
+        }
+        rows += 1;
+        bytes += size;
+    }
+
+    if rows > 0 {
+        chunks.push(start..start + rows);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_bounds_by_rows() {
+        let chunks = chunk_bounds([10; 5].into_iter(), 2, usize::MAX);
+        assert_eq!(chunks, vec![0..2, 2..4, 4..5]);
+    }
+
+    #[test]
+    fn test_chunk_bounds_by_bytes() {
+        let chunks = chunk_bounds([40, 40, 40, 10].into_iter(), 100, 90);
+        assert_eq!(chunks, vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn test_chunk_bounds_oversized_row() {
+        let chunks = chunk_bounds([10, 500, 10].into_iter(), 100, 100);
+        assert_eq!(chunks, vec![0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn test_chunk_bounds_empty() {
+        assert!(chunk_bounds(std::iter::empty(), 10, 10).is_empty());
+    }
+
+    #[test]
+    fn test_string_len() {
+        assert_eq!(string_len(""), 1);
+        assert_eq!(string_len("abc"), 4);
+        assert_eq!(string_len(&"x".repeat(200)), 202);
+        assert_eq!(nullable_string_len(None), 1);
+        assert_eq!(nullable_string_len(Some("abc")), 5);
+    }
+}
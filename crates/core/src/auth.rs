@@ -6,10 +6,13 @@
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::sync::LazyLock;
 
 use crate::error::{AuthErrorCode, Error, Result};
 use crate::limits::API_KEY_PATTERN;
+use crate::retention::RetentionTier;
 
 /// Compiled API key regex (lazy initialization).
 static API_KEY_REGEX: LazyLock<Regex> =
@@ -35,7 +38,9 @@ impl ApiKeyEnv {
 /// Parsed and validated API key from a request.
 ///
 /// This is distinct from the stored `ApiKey` record in tenant.rs.
-#[derive(Debug, Clone)]
+/// `Debug` and `Display` show only the prefix and last four characters; use
+/// [`hash`](Self::hash) wherever a stable identifier is needed.
+#[derive(Clone)]
 pub struct ParsedApiKey {
     /// Raw key string.
     raw: String,
@@ -76,10 +81,23 @@ impl ParsedApiKey {
     }
 
     /// Get the raw key string.
+    ///
+    /// Only for sending to the auth service; never log or store it.
     pub fn as_str(&self) -> &str {
         &self.raw
     }
 
+    /// Hex SHA-256 of the key, for cache keys and logs.
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.raw.as_bytes()))
+    }
+
+    /// Redacted form, e.g. `owk_live_…mn45`.
+    pub fn redacted(&self) -> String {
+        let tail = &self.raw[self.raw.len().saturating_sub(4)..];
+        format!("owk_{}_…{}", self.env.as_str(), tail)
+    }
+
     /// Get the key environment.
     pub fn env(&self) -> ApiKeyEnv {
         self.env
@@ -96,22 +114,88 @@ impl ParsedApiKey {
     }
 }
 
+impl fmt::Debug for ParsedApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParsedApiKey")
+            .field("key", &self.redacted())
+            .field("env", &self.env)
+            .finish()
+    }
+}
+
+impl fmt::Display for ParsedApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.redacted())
+    }
+}
+
+/// Access a route requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Send events
+    Ingest,
+    /// Query analytics
+    Read,
+    /// Operator endpoints
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ingest => "ingest",
+            Self::Read => "read",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Whether `permissions` grant this scope.
+    ///
+    /// `write` is the older name for `ingest`; `admin` grants every scope.
+    pub fn granted_by(&self, permissions: &[String]) -> bool {
+        permissions.iter().any(|permission| {
+            let permission = permission.as_str();
+            permission == self.as_str()
+                || permission == Scope::Admin.as_str()
+                || (*self == Scope::Ingest && permission == "write")
+        })
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Request to auth service.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthRequest {
     /// The API key to validate.
     pub api_key: String,
-    /// Required permission (typically "write" for ingestion).
-    pub required_permission: String,
+    /// Permission the daemon should require, if any. Scopes are checked per
+    /// route against the response's permissions, so none is sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_permission: Option<String>,
+}
+
+impl fmt::Debug for AuthRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthRequest")
+            .field("api_key", &"<redacted>")
+            .field("required_permission", &self.required_permission)
+            .finish()
+    }
 }
 
 impl AuthRequest {
-    /// Create a new auth request for write permission.
-    pub fn write(api_key: &str) -> Self {
+    /// Create an auth request returning the key's permissions.
+    pub fn new(api_key: &str) -> Self {
         Self {
             api_key: api_key.to_string(),
-            required_permission: "write".to_string(),
+            required_permission: None,
         }
     }
 }
@@ -126,14 +210,65 @@ pub struct AuthResponse {
     pub project_id: Option<String>,
     /// Granted permissions.
     pub permissions: Option<Vec<String>>,
-    /// Rate limit (requests per minute).
+    /// Rate limit (events per second).
     pub rate_limit: Option<u32>,
+    /// Plan tier, used for the rate limit when none is given.
+    #[serde(default)]
+    pub tier: Option<RetentionTier>,
     /// Allowed origins for CORS.
     pub allowed_origins: Option<Vec<String>>,
+    /// Browser or server key; server keys skip origin checks.
+    #[serde(default)]
+    pub key_type: KeyType,
     /// Error details if invalid.
     pub error: Option<AuthResponseError>,
     /// MAU status for the project.
     pub mau: Option<MauStatus>,
+    /// Secret for signed requests, if the project has one.
+    #[serde(default)]
+    pub signing_secret: Option<SigningSecret>,
+    /// Whether live server keys must sign their requests.
+    #[serde(default)]
+    pub require_signature: bool,
+    /// CIDRs server keys may ingest from; any when absent.
+    #[serde(default)]
+    pub ip_allowlist: Option<Vec<String>>,
+    /// CIDRs blocked from ingesting.
+    #[serde(default)]
+    pub ip_denylist: Option<Vec<String>>,
+}
+
+/// Per-project HMAC secret for signed requests; redacted in `Debug`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SigningSecret(String);
+
+impl SigningSecret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// The secret itself, for computing signatures.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SigningSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SigningSecret(<redacted>)")
+    }
+}
+
+/// Where an API key is meant to be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    /// Embedded in web pages, restricted to the project's allowed origins
+    #[default]
+    Browser,
+    /// Used from backends, never subject to origin checks
+    Server,
 }
 
 /// Auth error in response.
@@ -144,7 +279,7 @@ pub struct AuthResponseError {
 }
 
 /// MAU (Monthly Active Users) status from daemon.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MauStatus {
     /// MAU limit for the project's plan.
@@ -154,6 +289,28 @@ pub struct MauStatus {
 }
 
 impl AuthResponse {
+    /// Response rejecting a key, as the daemon reports it.
+    pub fn invalid(code: AuthErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            valid: false,
+            project_id: None,
+            permissions: None,
+            rate_limit: None,
+            tier: None,
+            allowed_origins: None,
+            key_type: KeyType::default(),
+            error: Some(AuthResponseError {
+                code: code.code().to_string(),
+                message: message.into(),
+            }),
+            mau: None,
+            signing_secret: None,
+            require_signature: false,
+            ip_allowlist: None,
+            ip_denylist: None,
+        }
+    }
+
     /// Check if auth was successful and extract project ID.
     pub fn project_id(&self) -> Result<&str> {
         if !self.valid {
@@ -176,9 +333,11 @@ impl AuthResponse {
             .ok_or_else(|| Error::auth(AuthErrorCode::InvalidKey, "Missing project ID in response"))
     }
 
-    /// Get rate limit or default.
+    /// Get rate limit (events per second), falling back to the tier default.
     pub fn rate_limit_or_default(&self) -> u32 {
-        self.rate_limit.unwrap_or(1000)
+        self.rate_limit
+            .or_else(|| self.tier.map(|tier| tier.default_rate_limit()))
+            .unwrap_or(1000)
     }
 }
 
@@ -229,6 +388,33 @@ mod tests {
         assert_eq!(key.env(), ApiKeyEnv::Test);
     }
 
+    #[test]
+    fn test_key_is_redacted_and_hashed() {
+        let raw = "owk_live_ABC123xyz789DEF456ghi012JKL345mn";
+        let key = ParsedApiKey::parse(raw).unwrap();
+
+        assert_eq!(key.to_string(), "owk_live_…45mn");
+        assert!(!format!("{:?}", key).contains(raw));
+        assert!(!format!("{:?}", AuthRequest::new(raw)).contains(raw));
+
+        let hash = key.hash();
+        assert_eq!(hash.len(), 64);
+        assert_ne!(hash, raw);
+        assert_eq!(hash, ParsedApiKey::parse(raw).unwrap().hash());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let permissions = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        assert!(Scope::Ingest.granted_by(&permissions(&["ingest"])));
+        assert!(Scope::Ingest.granted_by(&permissions(&["write"])));
+        assert!(!Scope::Read.granted_by(&permissions(&["ingest", "write"])));
+        assert!(!Scope::Admin.granted_by(&permissions(&["read"])));
+        assert!(Scope::Read.granted_by(&permissions(&["admin"])));
+        assert!(!Scope::Ingest.granted_by(&[]));
+    }
+
     #[test]
     fn test_invalid_key_format() {
         // Too short
@@ -270,6 +456,12 @@ mod tests {
         assert_eq!(AuthErrorCode::InvalidKey.code(), "AUTH_003");
         assert_eq!(AuthErrorCode::Revoked.code(), "AUTH_004");
         assert_eq!(AuthErrorCode::InsufficientPermissions.code(), "AUTH_005");
+        assert_eq!(AuthErrorCode::ServiceUnavailable.code(), "AUTH_006");
+        assert_eq!(AuthErrorCode::ServiceUnavailable.http_status(), 503);
+        assert_eq!(AuthErrorCode::InvalidSignature.code(), "AUTH_007");
+        assert_eq!(AuthErrorCode::SignatureExpired.code(), "AUTH_008");
+        assert_eq!(AuthErrorCode::IpNotAllowed.code(), "AUTH_009");
+        assert_eq!(AuthErrorCode::IpNotAllowed.http_status(), 403);
     }
 
     #[test]
@@ -279,9 +471,15 @@ mod tests {
             project_id: Some("proj-123".into()),
             permissions: Some(vec!["read".into(), "write".into()]),
             rate_limit: Some(5000),
+            tier: Some(RetentionTier::Free),
             allowed_origins: None,
+            key_type: KeyType::Browser,
             error: None,
             mau: None,
+            signing_secret: None,
+            require_signature: false,
+            ip_allowlist: None,
+            ip_denylist: None,
         };
         assert_eq!(response.project_id().unwrap(), "proj-123");
         assert_eq!(response.rate_limit_or_default(), 5000);
@@ -294,13 +492,26 @@ mod tests {
             project_id: None,
             permissions: None,
             rate_limit: None,
+            tier: None,
             allowed_origins: None,
+            key_type: KeyType::Browser,
             error: Some(AuthResponseError {
                 code: "AUTH_003".into(),
                 message: "Invalid API key".into(),
             }),
             mau: None,
+            signing_secret: None,
+            require_signature: false,
+            ip_allowlist: None,
+            ip_denylist: None,
         };
         assert!(response.project_id().is_err());
+        assert_eq!(response.rate_limit_or_default(), 1000);
+
+        let response = AuthResponse {
+            tier: Some(RetentionTier::Paid),
+            ..response
+        };
+        assert_eq!(response.rate_limit_or_default(), 10_000);
     }
 }
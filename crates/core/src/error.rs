@@ -1,9 +1,9 @@
 //! Unified error types for the ingestion engine.
 //!
 //! Error codes follow the spec:
-//! - AUTH_001-005: Authentication errors
+//! - AUTH_001-009: Authentication errors
 //! - VALID_001-003: Validation errors
-//! - DB_001: Database errors
+//! - DB_001-002: Database errors
 //! - RATE_001: Rate limit errors
 
 use thiserror::Error;
@@ -24,6 +24,14 @@ pub enum AuthErrorCode {
     Revoked,
     /// AUTH_005: Insufficient permissions
     InsufficientPermissions,
+    /// AUTH_006: Auth service unavailable
+    ServiceUnavailable,
+    /// AUTH_007: Request signature missing, malformed or wrong
+    InvalidSignature,
+    /// AUTH_008: Signed request outside the replay window, or replayed
+    SignatureExpired,
+    /// AUTH_009: Client IP address not allowed for the project
+    IpNotAllowed,
 }
 
 impl AuthErrorCode {
@@ -35,6 +43,10 @@ impl AuthErrorCode {
             Self::InvalidKey => "AUTH_003",
             Self::Revoked => "AUTH_004",
             Self::InsufficientPermissions => "AUTH_005",
+            Self::ServiceUnavailable => "AUTH_006",
+            Self::InvalidSignature => "AUTH_007",
+            Self::SignatureExpired => "AUTH_008",
+            Self::IpNotAllowed => "AUTH_009",
         }
     }
 
@@ -46,6 +58,10 @@ impl AuthErrorCode {
             Self::InvalidKey => 401,
             Self::Revoked => 401,
             Self::InsufficientPermissions => 403,
+            Self::ServiceUnavailable => 503,
+            Self::InvalidSignature => 401,
+            Self::SignatureExpired => 401,
+            Self::IpNotAllowed => 403,
         }
     }
 }
@@ -82,6 +98,8 @@ impl ValidationErrorCode {
 pub enum DbErrorCode {
     /// DB_001: Failed to store events
     StoreFailed,
+    /// DB_002: Storage backlog too deep, retry later
+    Backlogged,
 }
 
 impl DbErrorCode {
@@ -89,12 +107,16 @@ impl DbErrorCode {
     pub fn code(&self) -> &'static str {
         match self {
             Self::StoreFailed => "DB_001",
+            Self::Backlogged => "DB_002",
         }
     }
 
     /// Get the HTTP status code.
     pub fn http_status(&self) -> u16 {
-        500
+        match self {
+            Self::StoreFailed => 500,
+            Self::Backlogged => 503,
+        }
     }
 }
 
@@ -103,6 +125,8 @@ impl DbErrorCode {
 pub enum RateLimitErrorCode {
     /// RATE_001: Rate limit exceeded
     Exceeded,
+    /// RATE_002: Monthly active user limit exceeded
+    MauExceeded,
 }
 
 impl RateLimitErrorCode {
@@ -110,12 +134,17 @@ impl RateLimitErrorCode {
     pub fn code(&self) -> &'static str {
         match self {
             Self::Exceeded => "RATE_001",
+            Self::MauExceeded => "RATE_002",
         }
     }
 
     /// Get the HTTP status code.
     pub fn http_status(&self) -> u16 {
-        429
+        match self {
+            Self::Exceeded => 429,
+            // Not transient, so clients should not retry
+            Self::MauExceeded => 402,
+        }
     }
 }
 
@@ -0,0 +1,199 @@
+//! IP ranges for per-project access rules and trusted proxies.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::error::{AuthErrorCode, Error, Result};
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8`. A bare address is a /32 or /128.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || Error::validation(format!("Invalid CIDR: {}", value));
+        let (addr, prefix) = match value.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let network = IpAddr::from_str(addr)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { network, prefix })
+    }
+
+    /// Whether `ip` is inside this network. IPv4-mapped IPv6 addresses
+    /// match IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+/// Loopback and private ranges, where load balancers usually live.
+pub fn default_trusted_proxies() -> Vec<Cidr> {
+    [
+        "127.0.0.0/8",
+        "10.0.0.0/8",
+        "172.16.0.0/12",
+        "192.168.0.0/16",
+        "::1/128",
+        "fc00::/7",
+    ]
+    .iter()
+    .map(|cidr| Cidr::parse(cidr).expect("valid default proxy range"))
+    .collect()
+}
+
+/// A project's IP allowlist and denylist.
+#[derive(Debug, Clone, Default)]
+pub struct IpRules {
+    /// `None` when the project has no allowlist
+    allow: Option<Vec<Cidr>>,
+    deny: Vec<Cidr>,
+}
+
+impl IpRules {
+    /// Parse the lists, returning the entries that are not valid CIDRs.
+    ///
+    /// Invalid entries are skipped; an allowlist whose entries are all
+    /// invalid admits nothing rather than everything.
+    pub fn parse(allow: Option<&[String]>, deny: Option<&[String]>) -> (Self, Vec<String>) {
+        let mut invalid = Vec::new();
+        let mut parse_list = |list: &[String]| -> Vec<Cidr> {
+            list.iter()
+                .filter_map(|entry| match Cidr::parse(entry) {
+                    Ok(cidr) => Some(cidr),
+                    Err(_) => {
+                        invalid.push(entry.clone());
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        let allow = allow.filter(|list| !list.is_empty()).map(&mut parse_list);
+        let deny = deny.map(&mut parse_list).unwrap_or_default();
+        (Self { allow, deny }, invalid)
+    }
+
+    /// Check a client address. The denylist always applies; the allowlist
+    /// only when `apply_allowlist` is set, and then an unknown address is
+    /// rejected.
+    pub fn check(&self, ip: Option<IpAddr>, apply_allowlist: bool) -> Result<()> {
+        if let Some(ip) = ip {
+            if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+                return Err(Error::auth(
+                    AuthErrorCode::IpNotAllowed,
+                    format!("IP address {} is blocked for this project", ip),
+                ));
+            }
+        }
+
+        match (&self.allow, ip) {
+            (Some(allow), Some(ip)) if apply_allowlist && !allow.iter().any(|c| c.contains(ip)) => {
+                Err(Error::auth(
+                    AuthErrorCode::IpNotAllowed,
+                    format!("IP address {} is not allowed for this project", ip),
+                ))
+            }
+            (Some(_), None) if apply_allowlist => Err(Error::auth(
+                AuthErrorCode::IpNotAllowed,
+                "Client IP address could not be determined",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn list(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let v4 = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(v4.contains(ip("10.1.255.3")));
+        assert!(!v4.contains(ip("10.2.0.1")));
+        assert!(v4.contains(ip("::ffff:10.1.0.9")));
+
+        let v6 = Cidr::parse("2001:db8::/32").unwrap();
+        assert!(v6.contains(ip("2001:db8:1::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(Cidr::parse("1.2.3.4").unwrap().contains(ip("1.2.3.4")));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("example.com/8").is_err());
+    }
+
+    #[test]
+    fn test_ip_rules() {
+        let allow = list(&["203.0.113.0/24", "not-a-cidr"]);
+        let deny = list(&["203.0.113.66"]);
+        let (rules, invalid) = IpRules::parse(Some(&allow), Some(&deny));
+        assert_eq!(invalid, vec!["not-a-cidr".to_string()]);
+
+        assert!(rules.check(Some(ip("203.0.113.5")), true).is_ok());
+        assert!(rules.check(Some(ip("198.51.100.1")), true).is_err());
+        assert!(rules.check(None, true).is_err());
+        // Allowlist only applies when asked; the denylist always does
+        assert!(rules.check(Some(ip("198.51.100.1")), false).is_ok());
+        assert!(rules.check(Some(ip("203.0.113.66")), false).is_err());
+
+        let (none_valid, _) = IpRules::parse(Some(&list(&["bogus"])), None);
+        assert!(none_valid.check(Some(ip("203.0.113.5")), true).is_err());
+        assert!(IpRules::default().check(None, true).is_ok());
+    }
+}
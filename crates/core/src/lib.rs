@@ -3,7 +3,9 @@
 pub mod auth;
 pub mod error;
 pub mod events;
+pub mod ip;
 pub mod limits;
+pub mod mau;
 pub mod retention;
 pub mod schema;
 pub mod sdk_event;
@@ -15,6 +17,8 @@ pub use error::{
     AuthErrorCode, DbErrorCode, Error, RateLimitErrorCode, Result, ValidationErrorCode,
 };
 pub use events::*;
+pub use ip::{Cidr, IpRules};
+pub use mau::{HyperLogLog, MauConfig, OverLimitAction};
 pub use retention::*;
 pub use sdk_event::*;
 pub use session::*;
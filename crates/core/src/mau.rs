@@ -0,0 +1,218 @@
+//! Monthly active user limits.
+//!
+//! The auth daemon reports whether a project is over its plan's MAU limit;
+//! [`MauConfig`] decides what ingestion does about it per plan. The engine
+//! also keeps its own [`HyperLogLog`] estimate of distinct users so it can
+//! warn before the daemon's (possibly stale) view flips.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::retention::RetentionTier;
+
+/// Register index bits; 2^12 registers (4KB) give ~1.6% standard error.
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// What ingestion does with a project that is over its MAU limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverLimitAction {
+    /// Reject batches with `RATE_002`
+    Reject,
+    /// Store only a sample of sessions
+    Sample,
+    /// Store everything and record the overage for billing
+    #[default]
+    Flag,
+}
+
+impl OverLimitAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::Sample => "sample",
+            Self::Flag => "flag",
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    0.1
+}
+
+fn default_warn_ratio() -> f64 {
+    0.9
+}
+
+/// Over-limit behavior per plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MauConfig {
+    #[serde(default)]
+    pub free: OverLimitAction,
+    #[serde(default)]
+    pub paid: OverLimitAction,
+    #[serde(default)]
+    pub enterprise: OverLimitAction,
+    /// Fraction of sessions kept by [`OverLimitAction::Sample`]
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Warn once the estimate reaches this fraction of the limit
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: f64,
+}
+
+impl Default for MauConfig {
+    fn default() -> Self {
+        Self {
+            free: OverLimitAction::default(),
+            paid: OverLimitAction::default(),
+            enterprise: OverLimitAction::default(),
+            sample_rate: default_sample_rate(),
+            warn_ratio: default_warn_ratio(),
+        }
+    }
+}
+
+impl MauConfig {
+    /// Action for a plan; projects without a tier are treated as free.
+    pub fn action(&self, tier: Option<RetentionTier>) -> OverLimitAction {
+        match tier.unwrap_or_default() {
+            RetentionTier::Free => self.free,
+            RetentionTier::Paid => self.paid,
+            RetentionTier::Enterprise => self.enterprise,
+        }
+    }
+
+    /// Whether a session survives sampling.
+    ///
+    /// Decided by the session ID so sampled sessions are kept whole.
+    pub fn sample_keeps(&self, session_id: &str) -> bool {
+        let bucket = stable_hash(session_id) % 10_000;
+        (bucket as f64) < self.sample_rate.clamp(0.0, 1.0) * 10_000.0
+    }
+}
+
+/// 64-bit hash that is stable within a build.
+fn stable_hash(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// HyperLogLog distinct-count estimator.
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Box<[u8]>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS].into_boxed_slice(),
+        }
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        let hash = stable_hash(value);
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // Leading zeros of the remaining bits, plus one; capped by a sentinel bit
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Fold another estimator's values into this one.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, &theirs) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(theirs);
+        }
+    }
+
+    /// Estimated number of distinct values inserted.
+    pub fn estimate(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+
+        // Linear counting is more accurate while many registers are empty
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hll_estimate_within_error() {
+        let mut hll = HyperLogLog::new();
+        assert_eq!(hll.estimate(), 0);
+
+        for i in 0..50_000 {
+            let user = format!("user-{}", i);
+            hll.insert(&user);
+            // Repeat visits do not count twice
+            hll.insert(&user);
+        }
+
+        let estimate = hll.estimate() as f64;
+        assert!(
+            (estimate - 50_000.0).abs() / 50_000.0 < 0.05,
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_hll_merge() {
+        let mut a = HyperLogLog::new();
+        let mut b = HyperLogLog::new();
+        for i in 0..1000 {
+            a.insert(&format!("user-{}", i));
+            b.insert(&format!("user-{}", i + 500));
+        }
+        a.merge(&b);
+
+        let estimate = a.estimate() as f64;
+        assert!((estimate - 1500.0).abs() / 1500.0 < 0.05, "{}", estimate);
+    }
+
+    #[test]
+    fn test_action_per_plan_and_sampling() {
+        let config = MauConfig {
+            free: OverLimitAction::Reject,
+            sample_rate: 0.25,
+            ..MauConfig::default()
+        };
+        assert_eq!(config.action(None), OverLimitAction::Reject);
+        assert_eq!(
+            config.action(Some(RetentionTier::Paid)),
+            OverLimitAction::Flag
+        );
+
+        let kept = (0..10_000)
+            .filter(|i| config.sample_keeps(&format!("session-{}", i)))
+            .count();
+        assert!((2_000..3_000).contains(&kept), "{}", kept);
+        assert_eq!(
+            config.sample_keeps("session-1"),
+            config.sample_keeps("session-1")
+        );
+    }
+}
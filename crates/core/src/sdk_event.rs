@@ -574,27 +574,79 @@ fn validate_custom_properties_size(properties: &Value) -> Result<()> {
     Ok(())
 }
 
+/// Per-project switches applied while transforming a batch.
+pub trait EventFilter {
+    /// Whether events of this type are stored.
+    fn is_event_enabled(&self, event_type: &str) -> bool;
+
+    /// Whether location fields are stored.
+    fn keep_location(&self) -> bool;
+}
+
+/// Stores every event as sent.
+pub struct AllowAll;
+
+impl EventFilter for AllowAll {
+    fn is_event_enabled(&self, _event_type: &str) -> bool {
+        true
+    }
+
+    fn keep_location(&self) -> bool {
+        true
+    }
+}
+
+/// Events an [`EventFilter`] dropped or changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterCounts {
+    /// Events of a disabled type
+    pub dropped: usize,
+    /// Events whose location fields were removed
+    pub location_stripped: usize,
+}
+
 /// Validate and transform a batch of SDK events.
 pub fn transform_batch(
     events: Vec<SDKEvent>,
     project_id: &str,
 ) -> Result<(Vec<ClickHouseEvent>, Vec<Error>)> {
+    transform_batch_with(events, project_id, &AllowAll).map(|(events, errors, _)| (events, errors))
+}
+
+/// Validate and transform a batch of SDK events, dropping disabled event
+/// types and stripping location unless `filter` allows them.
+pub fn transform_batch_with(
+    events: Vec<SDKEvent>,
+    project_id: &str,
+    filter: &impl EventFilter,
+) -> Result<(Vec<ClickHouseEvent>, Vec<Error>, FilterCounts)> {
     let mut transformed = Vec::with_capacity(events.len());
     let mut errors = Vec::new();
+    let mut counts = FilterCounts::default();
+    let keep_location = filter.keep_location();
+
+    for (i, mut event) in events.into_iter().enumerate() {
+        if !filter.is_event_enabled(event.event_type.as_str()) {
+            counts.dropped += 1;
+            continue;
+        }
 
-    for (i, event) in events.into_iter().enumerate() {
         if let Err(e) = validate_sdk_event(&event) {
             errors.push(Error::validation(format!("event[{}]: {}", i, e)));
             continue;
         }
 
+        if !keep_location && event.location.take().is_some() {
+            counts.location_stripped += 1;
+        }
+
         match ClickHouseEvent::from_sdk(event, project_id) {
             Ok(ch_event) => transformed.push(ch_event),
             Err(e) => errors.push(Error::validation(format!("event[{}]: {}", i, e))),
         }
     }
 
-    Ok((transformed, errors))
+    Ok((transformed, errors, counts))
 }
 
 #[cfg(test)]
@@ -834,6 +886,44 @@ mod tests {
         assert_eq!(ch_event.custom_name.as_deref(), Some("purchase"));
     }
 
+    struct NoClicksNoLocation;
+
+    impl EventFilter for NoClicksNoLocation {
+        fn is_event_enabled(&self, event_type: &str) -> bool {
+            event_type != "click"
+        }
+
+        fn keep_location(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_transform_batch_with_filter() {
+        let mut located = valid_sdk_event();
+        located.location = Some(LocationInfo {
+            country: Some("DE".into()),
+            region: Some("Berlin".into()),
+            city: Some("Berlin".into()),
+        });
+        let mut click = valid_sdk_event();
+        click.event_type = EventType::Click;
+
+        let (events, errors, counts) =
+            transform_batch_with(vec![located, click], "project-123", &NoClicksNoLocation).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(
+            counts,
+            FilterCounts {
+                dropped: 1,
+                location_stripped: 1,
+            }
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].country, "unknown");
+        assert_eq!(events[0].city, None);
+    }
+
     #[test]
     fn test_serde_roundtrip_all_trigger_types() {
         let event_types = vec![
@@ -1,9 +1,13 @@
 //! Tenant and API key management types.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::auth::{AuthResponse, KeyType, SigningSecret};
+use crate::error::{AuthErrorCode, Error, Result};
 use crate::retention::RetentionTier;
 
 /// A tenant/project in the system.
@@ -15,11 +19,28 @@ pub struct Tenant {
     #[validate(length(min = 1, max = 200))]
     pub name: String,
     /// Retention tier
+    #[serde(default)]
     pub tier: RetentionTier,
     /// Whether the tenant is active
+    #[serde(default = "default_active")]
     pub active: bool,
     /// Rate limit override (events per second)
     pub rate_limit: Option<u32>,
+    /// Secret for signed requests
+    #[serde(default)]
+    pub signing_secret: Option<SigningSecret>,
+    /// Whether live server keys must sign their requests
+    #[serde(default)]
+    pub require_signature: bool,
+    /// CIDRs server keys may ingest from
+    #[serde(default)]
+    pub ip_allowlist: Option<Vec<String>>,
+    /// CIDRs blocked from ingesting
+    #[serde(default)]
+    pub ip_denylist: Option<Vec<String>>,
+    /// Origins browser keys may send from
+    #[serde(default)]
+    pub allowed_origins: Option<Vec<String>>,
 }
 
 /// API key for authentication.
@@ -34,9 +55,21 @@ pub struct ApiKey {
     #[validate(length(min = 1, max = 200))]
     pub name: String,
     /// Whether the key is active
+    #[serde(default = "default_active")]
     pub active: bool,
     /// Optional expiration
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Granted scopes (`ingest`, `read`, `admin`). When absent, browser keys
+    /// may only ingest and server keys may also read.
+    #[serde(default)]
+    pub permissions: Option<Vec<String>>,
+    /// Browser or server key
+    #[serde(default)]
+    pub key_type: KeyType,
+}
+
+fn default_active() -> bool {
+    true
 }
 
 impl Tenant {
@@ -48,6 +81,11 @@ impl Tenant {
             tier: RetentionTier::Free,
             active: true,
             rate_limit: None,
+            signing_secret: None,
+            require_signature: false,
+            ip_allowlist: None,
+            ip_denylist: None,
+            allowed_origins: None,
         }
     }
 
@@ -58,6 +96,98 @@ impl Tenant {
     }
 }
 
+impl ApiKey {
+    /// Whether the key has expired at `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Explicit permissions, else the key type's defaults. Browser keys ship
+    /// in page scripts, so they never read analytics by default.
+    pub fn effective_permissions(&self) -> Vec<String> {
+        if let Some(permissions) = &self.permissions {
+            return permissions.clone();
+        }
+        match self.key_type {
+            KeyType::Browser => vec!["ingest".into()],
+            KeyType::Server => vec!["ingest".into(), "read".into()],
+        }
+    }
+}
+
+/// Tenants and keys indexed for key validation without the auth daemon.
+#[derive(Debug, Clone, Default)]
+pub struct KeyStore {
+    tenants: HashMap<Uuid, Tenant>,
+    /// Lowercase `key_hash` -> key
+    keys: HashMap<String, ApiKey>,
+}
+
+impl KeyStore {
+    /// Build from records, rejecting any that fail validation.
+    pub fn new(tenants: Vec<Tenant>, keys: Vec<ApiKey>) -> Result<Self> {
+        let mut store = Self::default();
+        for tenant in tenants {
+            tenant
+                .validate()
+                .map_err(|e| Error::validation(format!("Tenant {}: {}", tenant.id, e)))?;
+            store.tenants.insert(tenant.id, tenant);
+        }
+        for key in keys {
+            key.validate()
+                .map_err(|e| Error::validation(format!("API key {}: {}", key.name, e)))?;
+            store.keys.insert(key.key_hash.to_ascii_lowercase(), key);
+        }
+        Ok(store)
+    }
+
+    pub fn tenant_count(&self) -> usize {
+        self.tenants.len()
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Validate a key by its SHA-256 hash, as the auth daemon would.
+    ///
+    /// Unknown keys, and keys whose tenant is missing, are `AUTH_003`;
+    /// revoked or expired keys and deactivated tenants are `AUTH_004`.
+    pub fn authenticate(&self, key_hash: &str, now: DateTime<Utc>) -> AuthResponse {
+        let Some(key) = self.keys.get(&key_hash.to_ascii_lowercase()) else {
+            return AuthResponse::invalid(AuthErrorCode::InvalidKey, "Invalid API key");
+        };
+        let Some(tenant) = self.tenants.get(&key.tenant_id) else {
+            return AuthResponse::invalid(AuthErrorCode::InvalidKey, "Invalid API key");
+        };
+        if !key.active {
+            return AuthResponse::invalid(AuthErrorCode::Revoked, "API key has been revoked");
+        }
+        if key.is_expired(now) {
+            return AuthResponse::invalid(AuthErrorCode::Revoked, "API key has expired");
+        }
+        if !tenant.active {
+            return AuthResponse::invalid(AuthErrorCode::Revoked, "Project is deactivated");
+        }
+
+        AuthResponse {
+            valid: true,
+            project_id: Some(tenant.id.to_string()),
+            permissions: Some(key.effective_permissions()),
+            rate_limit: Some(tenant.effective_rate_limit()),
+            tier: Some(tenant.tier),
+            allowed_origins: tenant.allowed_origins.clone(),
+            key_type: key.key_type,
+            error: None,
+            mau: None,
+            signing_secret: tenant.signing_secret.clone(),
+            require_signature: tenant.require_signature,
+            ip_allowlist: tenant.ip_allowlist.clone(),
+            ip_denylist: tenant.ip_denylist.clone(),
+        }
+    }
+}
+
 /// Validated tenant context extracted from request.
 #[derive(Debug, Clone, Validate)]
 pub struct TenantContext {
@@ -66,3 +196,70 @@ pub struct TenantContext {
     #[validate(length(max = 200))]
     pub api_key_name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(tenant_id: Uuid, key_hash: &str) -> ApiKey {
+        ApiKey {
+            key_hash: key_hash.to_string(),
+            tenant_id,
+            name: "default".to_string(),
+            active: true,
+            expires_at: None,
+            permissions: None,
+            key_type: KeyType::Browser,
+        }
+    }
+
+    #[test]
+    fn test_key_store_enforces_revocation_and_expiry() {
+        let tenant = Tenant::new("Acme");
+        let mut revoked = key(tenant.id, "bb");
+        revoked.active = false;
+        let mut expired = key(tenant.id, "cc");
+        expired.expires_at = Some(Utc::now() - chrono::Duration::minutes(1));
+
+        let store = KeyStore::new(
+            vec![tenant.clone()],
+            vec![
+                key(tenant.id, "AA"),
+                revoked,
+                expired,
+                key(Uuid::new_v4(), "dd"),
+            ],
+        )
+        .unwrap();
+        let now = Utc::now();
+
+        let ok = store.authenticate("aa", now);
+        assert_eq!(ok.project_id().unwrap(), tenant.id.to_string());
+        assert_eq!(ok.rate_limit, Some(tenant.effective_rate_limit()));
+
+        let code = |hash: &str| store.authenticate(hash, now).error.unwrap().code;
+        assert_eq!(code("bb"), "AUTH_004");
+        assert_eq!(code("cc"), "AUTH_004");
+        assert_eq!(code("dd"), "AUTH_003");
+        assert_eq!(code("ee"), "AUTH_003");
+    }
+
+    #[test]
+    fn test_default_permissions_by_key_type() {
+        let tenant = Tenant::new("Acme");
+        let mut server = key(tenant.id, "bb");
+        server.key_type = KeyType::Server;
+        let mut explicit = key(tenant.id, "cc");
+        explicit.permissions = Some(vec!["ingest".into(), "read".into()]);
+
+        let store = KeyStore::new(
+            vec![tenant.clone()],
+            vec![key(tenant.id, "aa"), server, explicit],
+        )
+        .unwrap();
+        let permissions = |hash: &str| store.authenticate(hash, Utc::now()).permissions.unwrap();
+        assert_eq!(permissions("aa"), vec!["ingest"]);
+        assert_eq!(permissions("bb"), vec!["ingest", "read"]);
+        assert_eq!(permissions("cc"), vec!["ingest", "read"]);
+    }
+}
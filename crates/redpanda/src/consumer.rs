@@ -51,6 +51,8 @@ pub struct Consumer {
     current_offset: AtomicI64,
     /// Whether consumer has been initialized
     initialized: std::sync::atomic::AtomicBool,
+    /// Whether fetches update the pipeline's consumed, lag and error metrics
+    pipeline_metrics: bool,
 }
 
 impl Consumer {
@@ -76,9 +78,18 @@ impl Consumer {
             partition_client: RwLock::new(None),
             current_offset: AtomicI64::new(-1),
             initialized: std::sync::atomic::AtomicBool::new(false),
+            pipeline_metrics: true,
         })
     }
 
+    /// Leave the pipeline metrics alone, for consumers that read the topic
+    /// alongside the pipeline (such as forwarding). Ingestion sheds load on
+    /// `consumer_lag`, which must reflect the pipeline consumer only.
+    pub fn without_pipeline_metrics(mut self) -> Self {
+        self.pipeline_metrics = false;
+        self
+    }
+
     /// Initializes the consumer connection.
     async fn ensure_connected(&self) -> Result<Arc<rskafka::client::partition::PartitionClient>> {
         // Check if already connected
@@ -157,15 +168,33 @@ impl Consumer {
     /// Returns the events and the offset to commit after processing.
     pub async fn fetch_batch(&self) -> Result<(Vec<ClickHouseEvent>, Option<Offset>)> {
         let client = self.ensure_connected().await?;
+        let current = self.current_offset.load(Ordering::SeqCst);
+        self.fetch_from(&client, current).await
+    }
+
+    /// Fetches a batch starting at `offset` instead of the committed offset.
+    ///
+    /// Lets callers accumulate several fetches into one insert before
+    /// committing. Pass the offset returned by the previous fetch.
+    pub async fn fetch_batch_from(
+        &self,
+        offset: i64,
+    ) -> Result<(Vec<ClickHouseEvent>, Option<Offset>)> {
+        let client = self.ensure_connected().await?;
+        self.fetch_from(&client, offset).await
+    }
 
+    async fn fetch_from(
+        &self,
+        client: &rskafka::client::partition::PartitionClient,
+        current: i64,
+    ) -> Result<(Vec<ClickHouseEvent>, Option<Offset>)> {
         let start = std::time::Instant::now();
         let timeout = Duration::from_millis(self.config.batch_timeout_ms);
         let max_bytes = self.config.batch_size * 64 * 1024; // Assume ~64KB max per event
 
-        let current = self.current_offset.load(Ordering::SeqCst);
-
         // Fetch records
-        let (records, _watermark) = client
+        let (records, watermark) = client
             .fetch_records(current, 1..max_bytes as i32, timeout.as_millis() as i32)
             .await
             .map_err(|e| {
@@ -175,6 +204,11 @@ impl Consumer {
             })?;
 
         if records.is_empty() {
+            if self.pipeline_metrics {
+                metrics()
+                    .consumer_lag
+                    .set(watermark.saturating_sub(current).max(0) as u64);
+            }
             return Ok((Vec::new(), None));
         }
 
@@ -202,9 +236,14 @@ impl Consumer {
         }
 
         // Update metrics
-        metrics().events_consumed.inc_by(events.len() as u64);
-        if errors > 0 {
-            metrics().consumer_errors.inc_by(errors);
+        if self.pipeline_metrics {
+            metrics().events_consumed.inc_by(events.len() as u64);
+            metrics()
+                .consumer_lag
+                .set(watermark.saturating_sub(max_offset + 1).max(0) as u64);
+            if errors > 0 {
+                metrics().consumer_errors.inc_by(errors);
+            }
         }
 
         let elapsed = start.elapsed();
@@ -251,6 +290,15 @@ impl Consumer {
         Ok(())
     }
 
+    /// Connects if needed and returns the next offset to read.
+    ///
+    /// On first connection this is the latest offset. For callers that keep
+    /// their own cursor and read with [`Self::fetch_batch_from`].
+    pub async fn start_offset(&self) -> Result<i64> {
+        self.ensure_connected().await?;
+        Ok(self.current_offset())
+    }
+
     /// Returns the current consumer offset.
     pub fn current_offset(&self) -> i64 {
         self.current_offset.load(Ordering::SeqCst)
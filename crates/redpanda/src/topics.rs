@@ -7,9 +7,11 @@ pub mod topic {
     pub const SCROLL: &str = "events_scroll";
     pub const PERFORMANCE: &str = "events_performance";
     pub const CUSTOM: &str = "events_custom";
+    /// Suggested `sandbox_topic` for `owk_test_` events; never consumed into production tables
+    pub const SANDBOX: &str = "events_sandbox";
 
     /// All topics for initialization.
-    pub const ALL: &[&str] = &[PAGEVIEW, CLICK, SCROLL, PERFORMANCE, CUSTOM];
+    pub const ALL: &[&str] = &[PAGEVIEW, CLICK, SCROLL, PERFORMANCE, CUSTOM, SANDBOX];
 }
 
 /// Topic configuration.
@@ -55,5 +57,8 @@ pub fn default_topic_configs() -> Vec<TopicConfig> {
         TopicConfig::new(topic::SCROLL).with_partitions(6),
         TopicConfig::new(topic::PERFORMANCE).with_partitions(6),
         TopicConfig::new(topic::CUSTOM).with_partitions(12),
+        TopicConfig::new(topic::SANDBOX)
+            .with_partitions(3)
+            .with_retention_ms(24 * 60 * 60 * 1000), // 1 day
     ]
 }
@@ -3,8 +3,11 @@
 //! Collects metrics in-memory and periodically flushes to ClickHouse.
 
 use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 /// A counter metric.
 #[derive(Debug, Default)]
@@ -126,6 +129,15 @@ impl Histogram {
     }
 }
 
+/// Metrics for one named insert sink.
+#[derive(Debug, Default)]
+pub struct SinkMetrics {
+    pub inserts: Counter,
+    pub insert_errors: Counter,
+    pub events_inserted: Counter,
+    pub latency_ms: Histogram,
+}
+
 /// Collected metrics for the ingestion engine.
 #[derive(Debug, Default)]
 pub struct Metrics {
@@ -133,6 +145,28 @@ pub struct Metrics {
     pub events_received: Counter,
     pub events_validated: Counter,
     pub events_failed_validation: Counter,
+    /// Events dropped because their feature is disabled for the project
+    pub events_dropped_disabled: Counter,
+    /// Events stored without location because the feature is disabled
+    pub events_location_stripped: Counter,
+    /// Batches rejected because the project is over its MAU limit
+    pub mau_rejected_batches: Counter,
+    /// Events not stored because an over-limit project is sampled
+    pub events_mau_sampled_out: Counter,
+    /// Projects whose estimated MAU approached or passed their limit
+    pub mau_limit_warnings: Counter,
+    /// Events from test keys, routed to the sandbox
+    pub events_sandboxed: Counter,
+
+    // Auth service metrics
+    /// Requests authorized from the last good response during an outage
+    pub auth_stale_responses: Counter,
+    /// Times a circuit breaker opened
+    pub circuit_breaker_opened: Counter,
+    /// Requests rejected for a missing, wrong or replayed signature
+    pub signature_failures: Counter,
+    /// Ingest requests rejected by a project's IP allowlist or denylist
+    pub ip_rejected_requests: Counter,
     pub batches_received: Counter,
     pub rate_limited_requests: Counter,
 
@@ -150,6 +184,15 @@ pub struct Metrics {
     pub clickhouse_inserts: Counter,
     pub clickhouse_insert_errors: Counter,
 
+    // Storage tiering metrics
+    pub partitions_moved: Counter,
+    pub bytes_moved: Counter,
+
+    // Event forwarding metrics
+    pub events_forwarded: Counter,
+    pub forwarding_deliveries: Counter,
+    pub forwarding_dead_letters: Counter,
+
     // Latency histograms
     pub ingest_latency_ms: Histogram,
     pub redpanda_latency_ms: Histogram,
@@ -161,12 +204,36 @@ pub struct Metrics {
     pub queue_depth: Gauge,
     pub backpressure_active: Gauge,
     pub consumer_lag: Gauge,
+
+    // Per-sink metrics, keyed by sink name
+    sinks: RwLock<HashMap<String, Arc<SinkMetrics>>>,
 }
 
 impl Metrics {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the metrics for a named sink, registering it on first use.
+    pub fn sink(&self, name: &str) -> Arc<SinkMetrics> {
+        if let Some(sink) = self.sinks.read().get(name) {
+            return sink.clone();
+        }
+        self.sinks
+            .write()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns all registered sinks and their metrics.
+    pub fn sinks(&self) -> Vec<(String, Arc<SinkMetrics>)> {
+        self.sinks
+            .read()
+            .iter()
+            .map(|(name, sink)| (name.clone(), sink.clone()))
+            .collect()
+    }
 }
 
 /// A snapshot of metrics at a point in time.
@@ -176,6 +243,7 @@ pub struct MetricsSnapshot {
     pub events_received: u64,
     pub events_validated: u64,
     pub events_failed_validation: u64,
+    pub events_dropped_disabled: u64,
     pub batches_received: u64,
     pub batches_sent_to_redpanda: u64,
     pub events_sent_to_redpanda: u64,
@@ -198,6 +266,7 @@ impl Metrics {
             events_received: self.events_received.get(),
             events_validated: self.events_validated.get(),
             events_failed_validation: self.events_failed_validation.get(),
+            events_dropped_disabled: self.events_dropped_disabled.get(),
             batches_received: self.batches_received.get(),
             batches_sent_to_redpanda: self.batches_sent_to_redpanda.get(),
             events_sent_to_redpanda: self.events_sent_to_redpanda.get(),
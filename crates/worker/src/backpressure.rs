@@ -0,0 +1,205 @@
+//! Merge-pressure-aware backpressure for the consumer pipeline.
+//!
+//! The metrics flush loop feeds every `collect_ops_metrics` snapshot into a
+//! shared [`Backpressure`] handle, and the consumer checks it before each batch:
+//! - Normal: fetch and insert as configured
+//! - Elevated: wait between fetches and accumulate larger inserts, so
+//!   ClickHouse receives fewer, bigger parts while merges catch up
+//! - Critical: stop inserting entirely; offsets stay uncommitted and events
+//!   wait in Redpanda until pressure drops
+//!
+//! While the level is above normal the `backpressure_active` gauge is set. A
+//! failed collection resets the level to normal.
+
+use clickhouse_client::ClickHouseOpsMetrics;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+use telemetry::metrics;
+use tracing::{info, warn};
+
+/// Merge pressure level derived from ClickHouse ops metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PressureLevel {
+    Normal,
+    Elevated,
+    Critical,
+}
+
+impl PressureLevel {
+    /// Classify an ops snapshot using the same thresholds as alerting.
+    pub fn from_ops(ops: &ClickHouseOpsMetrics) -> Self {
+        if ops.is_merge_pressure_critical() {
+            Self::Critical
+        } else if ops.is_merge_pressure_elevated() {
+            Self::Elevated
+        } else {
+            Self::Normal
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            2 => Self::Critical,
+            1 => Self::Elevated,
+            _ => Self::Normal,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Normal => 0,
+            Self::Elevated => 1,
+            Self::Critical => 2,
+        }
+    }
+}
+
+/// Backpressure tuning.
+#[derive(Debug, Clone)]
+pub struct BackpressureConfig {
+    /// Insert batch size multiplier while pressure is elevated
+    pub elevated_batch_multiplier: usize,
+    /// Delay before each fetch while pressure is elevated
+    pub elevated_fetch_delay: Duration,
+    /// How long to hold off between checks while pressure is critical
+    pub critical_pause: Duration,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            elevated_batch_multiplier: 4,
+            elevated_fetch_delay: Duration::from_secs(2),
+            critical_pause: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Shared backpressure state, updated from ops metrics and read by the consumer.
+#[derive(Debug, Default)]
+pub struct Backpressure {
+    level: AtomicU8,
+    config: BackpressureConfig,
+}
+
+impl Backpressure {
+    /// Creates a handle starting at [`PressureLevel::Normal`].
+    pub fn new(config: BackpressureConfig) -> Self {
+        Self {
+            level: AtomicU8::new(PressureLevel::Normal.as_u8()),
+            config,
+        }
+    }
+
+    /// Returns the current pressure level.
+    pub fn level(&self) -> PressureLevel {
+        PressureLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Returns the backpressure tuning.
+    pub fn config(&self) -> &BackpressureConfig {
+        &self.config
+    }
+
+    /// Updates the level from a fresh ops snapshot.
+    pub fn update(&self, ops: &ClickHouseOpsMetrics) {
+        self.set_level(PressureLevel::from_ops(ops));
+    }
+
+    /// Drops back to normal when no ops snapshot could be collected.
+    ///
+    /// A stale elevated or critical level would otherwise throttle or stall
+    /// the consumer for as long as ClickHouse metrics stay unavailable.
+    pub fn reset(&self) {
+        if self.level() != PressureLevel::Normal {
+            warn!(from = ?self.level(), "Ops metrics unavailable, releasing backpressure");
+        }
+        self.set_level(PressureLevel::Normal);
+    }
+
+    /// Sets the level and the `backpressure_active` gauge.
+    pub fn set_level(&self, level: PressureLevel) {
+        let prev = PressureLevel::from_u8(self.level.swap(level.as_u8(), Ordering::Relaxed));
+        metrics()
+            .backpressure_active
+            .set(u64::from(level != PressureLevel::Normal));
+
+        if prev != level {
+            if level > prev {
+                warn!(from = ?prev, to = ?level, "Merge pressure backpressure raised");
+            } else {
+                info!(from = ?prev, to = ?level, "Merge pressure backpressure lowered");
+            }
+        }
+    }
+
+    /// Number of events to accumulate per insert at the current level.
+    pub fn batch_target(&self, base: usize) -> usize {
+        match self.level() {
+            PressureLevel::Normal => base,
+            _ => base.saturating_mul(self.config.elevated_batch_multiplier.max(1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ops_with_score(score: f64) -> ClickHouseOpsMetrics {
+        ClickHouseOpsMetrics {
+            timestamp: chrono::Utc::now(),
+            tables: Vec::new(),
+            active_merges: Vec::new(),
+            disks: Vec::new(),
+            merge_pressure_score: score,
+            max_parts_count: 0,
+            total_active_merges: 0,
+            query_log: Default::default(),
+            replicas: Vec::new(),
+            replication_queue: Vec::new(),
+            mutations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_level_from_ops() {
+        assert_eq!(
+            PressureLevel::from_ops(&ops_with_score(10.0)),
+            PressureLevel::Normal
+        );
+        assert_eq!(
+            PressureLevel::from_ops(&ops_with_score(50.0)),
+            PressureLevel::Elevated
+        );
+        assert_eq!(
+            PressureLevel::from_ops(&ops_with_score(90.0)),
+            PressureLevel::Critical
+        );
+    }
+
+    #[test]
+    fn test_batch_target_grows_under_pressure() {
+        let bp = Backpressure::new(BackpressureConfig::default());
+        assert_eq!(bp.batch_target(5000), 5000);
+
+        bp.update(&ops_with_score(50.0));
+        assert_eq!(bp.level(), PressureLevel::Elevated);
+        assert_eq!(bp.batch_target(5000), 20000);
+
+        bp.update(&ops_with_score(0.0));
+        assert_eq!(bp.level(), PressureLevel::Normal);
+        assert_eq!(bp.batch_target(5000), 5000);
+    }
+
+    #[test]
+    fn test_reset_releases_pressure() {
+        let bp = Backpressure::new(BackpressureConfig::default());
+        bp.update(&ops_with_score(90.0));
+        assert_eq!(bp.level(), PressureLevel::Critical);
+
+        bp.reset();
+        assert_eq!(bp.level(), PressureLevel::Normal);
+        assert_eq!(bp.batch_target(5000), 5000);
+    }
+}
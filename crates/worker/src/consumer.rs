@@ -6,8 +6,20 @@
 //! 3. Route events to specialized tables by type
 //! 4. Commit offset (at-least-once delivery)
 //! 5. Repeat
+//!
+//! Batches are written through the [`Sink`] trait: the primary ClickHouse
+//! sink plus any extra sinks. Required sinks are retried with the primary and
+//! hold back the offset commit: a batch an extra required sink could not
+//! take is never skipped, whatever `skip_on_failure` says. Best-effort sinks
+//! get one attempt per batch.
+//!
+//! Each batch honours the shared [`Backpressure`] level fed from ClickHouse
+//! merge pressure: elevated pressure slows fetches and grows inserts,
+//! critical pressure pauses inserts.
 
+use crate::backpressure::{Backpressure, PressureLevel};
 use crate::enrichment::EnrichmentWorker;
+use crate::sinks::{write_batch, ClickHouseSink, Sink, SinkPolicy, PRIMARY_SINK};
 use clickhouse_client::ClickHouseClient;
 use engine_core::{ClickHouseEvent, Result};
 use redpanda::Consumer;
@@ -22,7 +34,8 @@ pub struct ConsumerWorkerConfig {
     pub max_retries: u32,
     /// Backoff between retries
     pub retry_backoff: Duration,
-    /// Whether to continue on insert failure (skip batch)
+    /// Whether to skip (and commit) a batch the primary sink could not
+    /// insert. Failures of extra required sinks are never skipped.
     pub skip_on_failure: bool,
 }
 
@@ -36,23 +49,27 @@ impl Default for ConsumerWorkerConfig {
     }
 }
 
+/// A batch some required sinks could not take after retries.
+struct InsertFailure {
+    error: engine_core::Error,
+    /// Whether only the primary failed, so `skip_on_failure` may apply
+    primary_only: bool,
+}
+
 /// Worker that consumes events from Redpanda and inserts to ClickHouse.
 pub struct ConsumerWorker {
     consumer: Arc<Consumer>,
-    clickhouse: Arc<ClickHouseClient>,
     config: ConsumerWorkerConfig,
     enrichment: EnrichmentWorker,
+    backpressure: Arc<Backpressure>,
+    /// Primary sink first, then any extra sinks
+    sinks: Vec<Arc<dyn Sink>>,
 }
 
 impl ConsumerWorker {
     /// Creates a new consumer worker.
     pub fn new(consumer: Arc<Consumer>, clickhouse: Arc<ClickHouseClient>) -> Self {
-        Self {
-            consumer,
-            clickhouse,
-            config: ConsumerWorkerConfig::default(),
-            enrichment: EnrichmentWorker::new(),
-        }
+        Self::with_config(consumer, clickhouse, ConsumerWorkerConfig::default())
     }
 
     /// Creates a new consumer worker with custom config.
@@ -63,12 +80,25 @@ impl ConsumerWorker {
     ) -> Self {
         Self {
             consumer,
-            clickhouse,
+            sinks: vec![Arc::new(ClickHouseSink::primary(clickhouse))],
             config,
             enrichment: EnrichmentWorker::new(),
+            backpressure: Arc::new(Backpressure::default()),
         }
     }
 
+    /// Also writes every batch to these sinks.
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn Sink>>) -> Self {
+        self.sinks.extend(sinks);
+        self
+    }
+
+    /// Uses a shared backpressure handle instead of a private one.
+    pub fn with_backpressure(mut self, backpressure: Arc<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
     /// Main run loop - fetch, insert, commit.
     ///
     /// This runs indefinitely, processing batches of events.
@@ -101,8 +131,40 @@ impl ConsumerWorker {
 
     /// Processes a single batch: fetch → insert → commit.
     async fn process_batch(&self) -> Result<usize> {
+        let level = self.backpressure.level();
+        match level {
+            PressureLevel::Critical => {
+                // Leave events in Redpanda until merges catch up
+                debug!("Merge pressure critical, pausing inserts");
+                tokio::time::sleep(self.backpressure.config().critical_pause).await;
+                return Ok(0);
+            }
+            PressureLevel::Elevated => {
+                tokio::time::sleep(self.backpressure.config().elevated_fetch_delay).await;
+            }
+            PressureLevel::Normal => {}
+        }
+
         // 1. Fetch batch from Redpanda
-        let (events, offset) = self.consumer.fetch_batch().await?;
+        let (mut events, mut offset) = self.consumer.fetch_batch().await?;
+
+        // Under pressure, accumulate several fetches into one larger insert
+        if level == PressureLevel::Elevated {
+            let target = self
+                .backpressure
+                .batch_target(self.consumer.config().batch_size);
+            while events.len() < target {
+                let Some(next) = offset else {
+                    break;
+                };
+                let (more, more_offset) = self.consumer.fetch_batch_from(next.offset).await?;
+                if more_offset.is_none() {
+                    break;
+                }
+                events.extend(more);
+                offset = more_offset;
+            }
+        }
 
         if events.is_empty() {
             return Ok(0);
@@ -122,14 +184,14 @@ impl ConsumerWorker {
 
                 Ok(inserted)
             }
-            Err(e) => {
+            Err(failure) => {
                 error!(
                     count = count,
-                    error = %e,
+                    error = %failure.error,
                     "Failed to insert batch after retries"
                 );
 
-                if self.config.skip_on_failure {
+                if self.config.skip_on_failure && failure.primary_only {
                     // Skip this batch and commit anyway to avoid infinite retry
                     warn!("Skipping failed batch, committing offset");
                     if let Some(offset) = offset {
@@ -137,7 +199,10 @@ impl ConsumerWorker {
                     }
                     Ok(0)
                 } else {
-                    Err(e)
+                    if !failure.primary_only {
+                        warn!("Required sink failed, holding back offset commit");
+                    }
+                    Err(failure.error)
                 }
             }
         }
@@ -152,12 +217,23 @@ impl ConsumerWorker {
 
     /// Inserts events with retry logic.
     ///
-    /// Events are enriched (UA parsing) before insertion, then routed to specialized tables.
-    async fn insert_with_retry(&self, events: Vec<ClickHouseEvent>) -> Result<usize> {
+    /// Events are enriched (UA parsing) before insertion. Required sinks are
+    /// retried until all have the batch; only the ones still failing are
+    /// retried. Best-effort sinks are then tried once.
+    async fn insert_with_retry(
+        &self,
+        events: Vec<ClickHouseEvent>,
+    ) -> std::result::Result<usize, InsertFailure> {
         // Enrich events before insertion
         let mut events = events;
         self.enrichment.enrich_batch(&mut events);
 
+        let (mut pending, best_effort): (Vec<_>, Vec<_>) = self
+            .sinks
+            .iter()
+            .partition(|sink| sink.policy() == SinkPolicy::Required);
+
+        let mut inserted = 0;
         let mut last_error = None;
 
         for attempt in 0..=self.config.max_retries {
@@ -166,48 +242,41 @@ impl ConsumerWorker {
                 warn!(
                     attempt = attempt,
                     backoff_ms = %backoff.as_millis(),
-                    "Retrying ClickHouse insert"
+                    sinks = pending.len(),
+                    "Retrying sink writes"
                 );
                 tokio::time::sleep(backoff).await;
             }
 
-            // Route events to specialized tables
-            match self.route_and_insert(&events).await {
-                Ok(count) => return Ok(count),
-                Err(e) => {
-                    last_error = Some(e);
+            let mut failed = Vec::new();
+            for sink in pending {
+                match write_batch(sink.as_ref(), &events).await {
+                    Ok(count) => inserted = inserted.max(count),
+                    Err(e) => {
+                        warn!(sink = %sink.name(), error = %e, "Sink write failed");
+                        last_error = Some(e);
+                        failed.push(sink);
+                    }
                 }
             }
-        }
+            pending = failed;
 
-        Err(last_error
-            .unwrap_or_else(|| engine_core::Error::internal("Insert failed with unknown error")))
-    }
-
-    /// Inserts all events into the unified events table.
-    ///
-    /// This follows the industry-standard analytics pattern (like Mixpanel/Amplitude):
-    /// - Single unified `events` table for all event types
-    /// - Event-specific data stored in JSON `data` field
-    /// - ClickHouse handles JSON queries efficiently
-    ///
-    /// Benefits:
-    /// - Single source of truth (no consistency issues)
-    /// - No write amplification
-    /// - Simpler architecture to maintain
-    /// - Daemon queries work out of the box
-    async fn route_and_insert(&self, events: &[ClickHouseEvent]) -> Result<usize> {
-        if events.is_empty() {
-            return Ok(0);
+            if pending.is_empty() {
+                for sink in best_effort {
+                    if let Err(e) = write_batch(sink.as_ref(), &events).await {
+                        warn!(sink = %sink.name(), error = %e, "Best-effort sink write failed");
+                    }
+                }
+                return Ok(inserted);
+            }
         }
 
-        // Insert all events into unified events table
-        let events_vec: Vec<ClickHouseEvent> = events.to_vec();
-        let count =
-            clickhouse_client::insert::insert_clickhouse_events(&self.clickhouse, events_vec)
-                .await?;
-
-        Ok(count)
+        Err(InsertFailure {
+            error: last_error.unwrap_or_else(|| {
+                engine_core::Error::internal("Insert failed with unknown error")
+            }),
+            primary_only: pending.iter().all(|sink| sink.name() == PRIMARY_SINK),
+        })
     }
 }
 
@@ -222,4 +291,77 @@ mod tests {
         assert_eq!(config.retry_backoff, Duration::from_millis(100));
         assert!(config.skip_on_failure);
     }
+
+    /// Sink that always succeeds or always fails.
+    struct StubSink {
+        name: &'static str,
+        policy: SinkPolicy,
+        fails: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for StubSink {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn policy(&self) -> SinkPolicy {
+            self.policy
+        }
+
+        async fn write(&self, events: &[ClickHouseEvent]) -> Result<usize> {
+            if self.fails {
+                return Err(engine_core::Error::internal("sink down"));
+            }
+            Ok(events.len())
+        }
+
+        async fn health(&self) -> bool {
+            !self.fails
+        }
+    }
+
+    async fn worker(sinks: &[(&'static str, SinkPolicy, bool)]) -> ConsumerWorker {
+        let consumer = Consumer::new(redpanda::ConsumerConfig::default(), vec![], None, None)
+            .await
+            .unwrap();
+        let clickhouse = ClickHouseClient::new(Default::default()).unwrap();
+        let config = ConsumerWorkerConfig {
+            max_retries: 1,
+            retry_backoff: Duration::ZERO,
+            skip_on_failure: true,
+        };
+        let mut worker =
+            ConsumerWorker::with_config(Arc::new(consumer), Arc::new(clickhouse), config);
+        worker.sinks = sinks
+            .iter()
+            .map(|&(name, policy, fails)| {
+                Arc::new(StubSink {
+                    name,
+                    policy,
+                    fails,
+                }) as Arc<dyn Sink>
+            })
+            .collect();
+        worker
+    }
+
+    #[tokio::test]
+    async fn test_only_primary_failures_are_skippable() {
+        use SinkPolicy::{BestEffort, Required};
+
+        let worker_ok =
+            worker(&[(PRIMARY_SINK, Required, false), ("logs", BestEffort, true)]).await;
+        assert!(matches!(worker_ok.insert_with_retry(vec![]).await, Ok(0)));
+
+        let primary_down =
+            worker(&[(PRIMARY_SINK, Required, true), ("backup", Required, false)]).await;
+        let failure = primary_down.insert_with_retry(vec![]).await.err().unwrap();
+        assert!(failure.primary_only);
+
+        let backup_down =
+            worker(&[(PRIMARY_SINK, Required, false), ("backup", Required, true)]).await;
+        let failure = backup_down.insert_with_retry(vec![]).await.err().unwrap();
+        assert!(!failure.primary_only);
+    }
 }
@@ -0,0 +1,547 @@
+//! Per-project event forwarding to customer webhooks.
+//!
+//! Runs as its own Redpanda consumer, independent of the ClickHouse
+//! pipeline. Each [`ForwardingRule`] selects a project's events (optionally
+//! by event type or custom event name) and POSTs them in batches to the
+//! customer's URL:
+//!
+//! ```json
+//! {"delivery_id": "...", "project_id": "...", "destination": "...", "events": [...]}
+//! ```
+//!
+//! Requests are signed with the rule's secret:
+//! - `X-Overwatch-Timestamp`: Unix seconds at send time
+//! - `X-Overwatch-Signature`: `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">`
+//! - `X-Overwatch-Delivery`: delivery ID, stable across retries
+//!
+//! Network errors, 429 and 5xx responses are retried with exponential
+//! backoff. Batches that exhaust their retries (or get any other 4xx) go to
+//! the destination's dead-letter queue. Every batch is recorded in
+//! `overwatch.forwarding_deliveries`.
+//!
+//! Each destination reads the topic in its own task with its own cursor, so
+//! a slow customer endpoint does not hold up the others. Cursors are saved
+//! to `overwatch.forwarding_offsets` after every batch and a destination
+//! resumes from its saved cursor on restart; a new destination starts at
+//! the latest offset.
+
+use chrono::Utc;
+use clickhouse_client::forwarding::{
+    load_offset, record_dead_letter, record_delivery, save_offset, DeadLetter, Delivery,
+    DeliveryStatus,
+};
+use clickhouse_client::ClickHouseClient;
+use engine_core::ClickHouseEvent;
+use hmac::{Hmac, Mac};
+use redpanda::Consumer;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use telemetry::metrics;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+
+/// Forwarding configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingConfig {
+    /// Consumer group for the forwarding consumer
+    #[serde(default = "default_group_id")]
+    pub group_id: String,
+    /// Per-request timeout
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub rules: Vec<ForwardingRule>,
+}
+
+impl Default for ForwardingConfig {
+    fn default() -> Self {
+        Self {
+            group_id: default_group_id(),
+            timeout_secs: default_timeout_secs(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+fn default_group_id() -> String {
+    "ingestion-forwarder".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+/// A customer destination for one project's events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingRule {
+    /// Destination name, unique within the project
+    pub destination: String,
+    pub project_id: String,
+    pub url: String,
+    /// HMAC-SHA256 signing secret shared with the customer
+    pub secret: String,
+    /// Event types to forward (empty forwards all)
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    /// Custom event names to forward (empty forwards all)
+    #[serde(default)]
+    pub custom_names: Vec<String>,
+    /// Maximum events per request
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Retries after the first attempt
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for each further retry
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl ForwardingRule {
+    /// Whether an event is forwarded by this rule.
+    pub fn matches(&self, event: &ClickHouseEvent) -> bool {
+        if event.project_id != self.project_id {
+            return false;
+        }
+        if !self.event_types.is_empty() && !self.event_types.contains(&event.event_type) {
+            return false;
+        }
+        if !self.custom_names.is_empty() {
+            return event
+                .custom_name
+                .as_ref()
+                .is_some_and(|name| self.custom_names.contains(name));
+        }
+        true
+    }
+
+    /// Check the rule before starting the forwarder.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.destination.is_empty() || self.project_id.is_empty() {
+            return Err("forwarding rule needs a destination and project_id".to_string());
+        }
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            return Err(format!(
+                "forwarding rule {}: url must be http(s)",
+                self.destination
+            ));
+        }
+        if self.secret.is_empty() {
+            return Err(format!(
+                "forwarding rule {}: secret must not be empty",
+                self.destination
+            ));
+        }
+        if self.batch_size == 0 {
+            return Err(format!(
+                "forwarding rule {}: batch_size must be positive",
+                self.destination
+            ));
+        }
+        Ok(())
+    }
+
+    /// Backoff before retry `attempt` (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(
+            self.retry_backoff_ms
+                .saturating_mul(1 << (attempt - 1).min(10)),
+        )
+    }
+}
+
+/// Request body sent to a destination.
+#[derive(Debug, Serialize)]
+struct ForwardPayload<'a> {
+    delivery_id: &'a str,
+    project_id: &'a str,
+    destination: &'a str,
+    events: &'a [ClickHouseEvent],
+}
+
+/// `sha256=<hex>` signature of `<timestamp>.<body>`.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Result of the last attempt to send a batch.
+struct AttemptError {
+    http_status: u16,
+    error: String,
+    retryable: bool,
+}
+
+/// Worker that forwards matching events to customer webhooks.
+pub struct ForwardingWorker {
+    consumer: Arc<Consumer>,
+    clickhouse: Arc<ClickHouseClient>,
+    rules: Vec<Arc<ForwardingRule>>,
+    http_client: Client,
+}
+
+impl ForwardingWorker {
+    pub fn new(
+        consumer: Arc<Consumer>,
+        clickhouse: Arc<ClickHouseClient>,
+        config: &ForwardingConfig,
+    ) -> Result<Self, String> {
+        for rule in &config.rules {
+            rule.validate()?;
+        }
+
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            consumer,
+            clickhouse,
+            rules: config.rules.iter().cloned().map(Arc::new).collect(),
+            http_client,
+        })
+    }
+
+    /// Main run loop - one forwarding task per destination.
+    pub async fn run(self: Arc<Self>) {
+        info!(
+            topic = %self.consumer.config().topic,
+            group_id = %self.consumer.config().group_id,
+            rules = self.rules.len(),
+            "Forwarding worker starting"
+        );
+
+        let mut destinations = JoinSet::new();
+        for rule in &self.rules {
+            destinations.spawn(self.clone().run_destination(rule.clone()));
+        }
+        while destinations.join_next().await.is_some() {}
+    }
+
+    /// Fetch, forward and save the cursor for one destination.
+    async fn run_destination(self: Arc<Self>, rule: Arc<ForwardingRule>) {
+        let mut cursor = None;
+        loop {
+            let result = match cursor {
+                Some(offset) => self.process_batch(&rule, offset).await,
+                None => self.resume_offset(&rule).await.map(|offset| {
+                    cursor = Some(offset);
+                    (0, None)
+                }),
+            };
+
+            match result {
+                Ok((count, next)) => {
+                    if let Some(next) = next {
+                        cursor = Some(next);
+                    }
+                    if count > 0 {
+                        debug!(destination = %rule.destination, count = count, "Forwarded batch");
+                    }
+                }
+                Err(e) => {
+                    error!(destination = %rule.destination, "Forwarding batch error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    self.consumer.reset_connection().await;
+                }
+            }
+        }
+    }
+
+    /// The destination's saved cursor, or the latest offset if it has none.
+    async fn resume_offset(&self, rule: &ForwardingRule) -> engine_core::Result<i64> {
+        let group_id = &self.consumer.config().group_id;
+        let offset = match load_offset(
+            &self.clickhouse,
+            group_id,
+            &rule.project_id,
+            &rule.destination,
+            0,
+        )
+        .await?
+        {
+            Some(offset) => offset,
+            None => self.consumer.start_offset().await?,
+        };
+
+        info!(
+            destination = %rule.destination,
+            project_id = %rule.project_id,
+            offset = offset,
+            "Forwarding destination resuming"
+        );
+        Ok(offset)
+    }
+
+    /// Forward one batch read from `offset` to a destination, then save the
+    /// cursor. Returns the events forwarded and the next offset.
+    ///
+    /// Failed deliveries are dead-lettered rather than blocking the cursor.
+    async fn process_batch(
+        &self,
+        rule: &ForwardingRule,
+        offset: i64,
+    ) -> engine_core::Result<(usize, Option<i64>)> {
+        let (events, next) = self.consumer.fetch_batch_from(offset).await?;
+        let Some(next) = next else {
+            return Ok((0, None));
+        };
+
+        let matched: Vec<ClickHouseEvent> =
+            events.into_iter().filter(|e| rule.matches(e)).collect();
+        // Batches for one destination go out in order
+        for chunk in matched.chunks(rule.batch_size) {
+            self.deliver(rule, chunk).await;
+        }
+
+        // A lost cursor write only means redelivery after a restart
+        if let Err(e) = save_offset(
+            &self.clickhouse,
+            &self.consumer.config().group_id,
+            &rule.project_id,
+            &rule.destination,
+            next.partition,
+            next.offset,
+        )
+        .await
+        {
+            warn!(destination = %rule.destination, error = %e, "Failed to save forwarding offset");
+        }
+        Ok((matched.len(), Some(next.offset)))
+    }
+
+    /// Send one batch with retries, then record the outcome.
+    async fn deliver(&self, rule: &ForwardingRule, events: &[ClickHouseEvent]) {
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let payload = ForwardPayload {
+            delivery_id: &delivery_id,
+            project_id: &rule.project_id,
+            destination: &rule.destination,
+            events,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                error!(destination = %rule.destination, error = %e, "Failed to encode batch");
+                return;
+            }
+        };
+
+        let start = Instant::now();
+        let mut attempts = 0;
+        let mut result = Ok(0);
+
+        for attempt in 0..=rule.max_retries {
+            if attempt > 0 {
+                let backoff = rule.backoff(attempt);
+                warn!(
+                    destination = %rule.destination,
+                    attempt = attempt,
+                    backoff_ms = %backoff.as_millis(),
+                    "Retrying forwarded batch"
+                );
+                tokio::time::sleep(backoff).await;
+            }
+
+            attempts += 1;
+            result = self.send(rule, &delivery_id, &body).await;
+            match &result {
+                Err(e) if e.retryable => continue,
+                _ => break,
+            }
+        }
+
+        let (status, http_status, error) = match result {
+            Ok(http_status) => {
+                metrics().events_forwarded.inc_by(events.len() as u64);
+                (DeliveryStatus::Delivered, http_status, String::new())
+            }
+            Err(failure) => {
+                error!(
+                    destination = %rule.destination,
+                    project_id = %rule.project_id,
+                    attempts = attempts,
+                    error = %failure.error,
+                    "Forwarded batch dead-lettered"
+                );
+                metrics().forwarding_dead_letters.inc();
+
+                let dead_letter = DeadLetter {
+                    delivery_id: delivery_id.clone(),
+                    project_id: rule.project_id.clone(),
+                    destination: rule.destination.clone(),
+                    url: rule.url.clone(),
+                    payload: String::from_utf8_lossy(&body).into_owned(),
+                    events: events.len() as u32,
+                    attempts,
+                    error: failure.error.clone(),
+                    failed_at: Utc::now().timestamp_millis(),
+                };
+                if let Err(e) = record_dead_letter(&self.clickhouse, &dead_letter).await {
+                    error!(destination = %rule.destination, error = %e, "Failed to write dead letter");
+                }
+
+                (
+                    DeliveryStatus::DeadLettered,
+                    failure.http_status,
+                    failure.error,
+                )
+            }
+        };
+
+        // Delivery status row (best-effort)
+        metrics().forwarding_deliveries.inc();
+        let delivery = Delivery {
+            delivery_id,
+            project_id: rule.project_id.clone(),
+            destination: rule.destination.clone(),
+            status: status.as_str().to_string(),
+            events: events.len() as u32,
+            attempts,
+            http_status,
+            error,
+            duration_ms: start.elapsed().as_millis() as u64,
+            created_at: Utc::now().timestamp_millis(),
+        };
+        if let Err(e) = record_delivery(&self.clickhouse, &delivery).await {
+            warn!(destination = %rule.destination, error = %e, "Failed to record delivery");
+        }
+    }
+
+    /// POST a signed body. Returns the response status on success.
+    async fn send(
+        &self,
+        rule: &ForwardingRule,
+        delivery_id: &str,
+        body: &[u8],
+    ) -> Result<u16, AttemptError> {
+        let timestamp = Utc::now().timestamp();
+        let signature = sign_payload(&rule.secret, timestamp, body);
+
+        let resp = self
+            .http_client
+            .post(&rule.url)
+            .header("Content-Type", "application/json")
+            .header("X-Overwatch-Timestamp", timestamp.to_string())
+            .header("X-Overwatch-Signature", signature)
+            .header("X-Overwatch-Delivery", delivery_id)
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| AttemptError {
+                http_status: 0,
+                error: format!("Request error: {}", e),
+                retryable: true,
+            })?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(status.as_u16());
+        }
+
+        Err(AttemptError {
+            http_status: status.as_u16(),
+            error: format!("Destination returned status {}", status),
+            retryable: status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> ForwardingRule {
+        ForwardingRule {
+            destination: "crm".to_string(),
+            project_id: "proj".to_string(),
+            url: "https://example.com/hook".to_string(),
+            secret: "s3cret".to_string(),
+            event_types: Vec::new(),
+            custom_names: Vec::new(),
+            batch_size: default_batch_size(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+        }
+    }
+
+    fn event(project_id: &str, event_type: &str, custom_name: Option<&str>) -> ClickHouseEvent {
+        ClickHouseEvent {
+            event_id: "e1".to_string(),
+            project_id: project_id.to_string(),
+            session_id: "s1".to_string(),
+            user_id: None,
+            event_type: event_type.to_string(),
+            custom_name: custom_name.map(str::to_string),
+            timestamp: 1_700_000_000_000,
+            url: String::new(),
+            path: String::new(),
+            referrer: String::new(),
+            user_agent: String::new(),
+            device_type: String::new(),
+            browser: String::new(),
+            browser_version: String::new(),
+            os: String::new(),
+            country: String::new(),
+            region: None,
+            city: None,
+            data: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let mut rule = rule();
+        assert!(rule.matches(&event("proj", "pageview", None)));
+        assert!(!rule.matches(&event("other", "pageview", None)));
+
+        rule.event_types = vec!["custom".to_string()];
+        rule.custom_names = vec!["signup".to_string()];
+        assert!(rule.matches(&event("proj", "custom", Some("signup"))));
+        assert!(!rule.matches(&event("proj", "custom", Some("login"))));
+        assert!(!rule.matches(&event("proj", "custom", None)));
+        assert!(!rule.matches(&event("proj", "pageview", None)));
+    }
+
+    #[test]
+    fn test_sign_payload() {
+        let signature = sign_payload("s3cret", 1_700_000_000, b"{}");
+        assert_eq!(
+            signature,
+            "sha256=97926816e98fbb41ccb1673225ff29a2f35369099990e1b1561651e7bd097ebf"
+        );
+        assert_ne!(signature, sign_payload("s3cret", 1_700_000_001, b"{}"));
+    }
+
+    #[test]
+    fn test_rule_backoff_and_validate() {
+        let rule = rule();
+        assert_eq!(rule.backoff(1), Duration::from_millis(500));
+        assert_eq!(rule.backoff(3), Duration::from_millis(2000));
+        assert!(rule.validate().is_ok());
+
+        let mut bad = rule.clone();
+        bad.secret.clear();
+        assert!(bad.validate().is_err());
+    }
+}
@@ -2,20 +2,32 @@
 //!
 //! Handles async workflows:
 //! - Consumer (Redpanda → ClickHouse pipeline)
+//! - Sinks (ClickHouse, JSONL file and webhook destinations)
+//! - Backpressure (merge pressure → consumer pacing)
 //! - Compression (free tier 24h → parquet rollup)
 //! - Retention (TTL enforcement)
 //! - Enrichment (event augmentation)
+//! - Forwarding (per-project customer webhooks)
 //! - Backfill (metric recomputation)
 //! - Notifications (admin alerts)
 
 pub mod backfill;
+pub mod backpressure;
 pub mod compression;
 pub mod consumer;
 pub mod enrichment;
+pub mod forwarding;
 pub mod notifications;
 pub mod retention;
 pub mod scheduler;
+pub mod sinks;
 
+pub use backpressure::{Backpressure, BackpressureConfig, PressureLevel};
 pub use consumer::*;
 pub use enrichment::EnrichmentWorker;
+pub use forwarding::{ForwardingConfig, ForwardingRule, ForwardingWorker};
+pub use retention::{PlannedAction, RetentionAction, RetentionWorker, TieringPolicy};
 pub use scheduler::*;
+pub use sinks::{
+    build_sink, build_sinks, ClickHouseSink, Sink, SinkConfig, SinkPolicy, SinkTarget,
+};
@@ -40,6 +40,41 @@ pub enum Notification {
         usage_percent: f64,
         free_gb: u64,
     },
+    /// Inserts slower than `SLOW_INSERT_MS` in the query log window
+    SlowInserts { count: u64, max_duration_ms: u64 },
+    /// Inserts that raised exceptions in the query log window
+    FailedInserts { count: u64, last_error: String },
+    /// Replica read-only, delayed or with a long queue
+    ReplicationLag {
+        table: String,
+        readonly: bool,
+        delay_secs: u64,
+        queue_size: u64,
+    },
+    /// Replication queue entries retried past the stuck threshold
+    ReplicationQueueStuck {
+        table: String,
+        stuck_entries: u64,
+        max_tries: u64,
+        last_exception: String,
+    },
+    /// Mutation failing or running past the stuck threshold
+    StuckMutation {
+        table: String,
+        mutation_id: String,
+        command: String,
+        age_secs: u64,
+        parts_to_do: i64,
+        fail_reason: String,
+    },
+    /// Per-project daily event count differs between a sink and the primary
+    SinkCountMismatch {
+        sink: String,
+        project_id: String,
+        day: String,
+        primary_events: u64,
+        sink_events: u64,
+    },
 }
 
 /// Webhook payload format.
@@ -201,6 +236,93 @@ impl NotificationWorker {
                     "Disk usage high"
                 );
             }
+            Notification::SlowInserts {
+                count,
+                max_duration_ms,
+            } => {
+                warn!(
+                    count = count,
+                    max_duration_ms = max_duration_ms,
+                    "ClickHouse inserts slow"
+                );
+            }
+            Notification::FailedInserts { count, last_error } => {
+                error!(
+                    count = count,
+                    last_error = last_error,
+                    "ClickHouse inserts failing"
+                );
+            }
+            Notification::ReplicationLag {
+                table,
+                readonly,
+                delay_secs,
+                queue_size,
+            } => {
+                if *readonly {
+                    error!(
+                        table = table,
+                        delay_secs = delay_secs,
+                        queue_size = queue_size,
+                        "CRITICAL: ClickHouse replica is read-only"
+                    );
+                } else {
+                    warn!(
+                        table = table,
+                        delay_secs = delay_secs,
+                        queue_size = queue_size,
+                        "ClickHouse replica lagging"
+                    );
+                }
+            }
+            Notification::ReplicationQueueStuck {
+                table,
+                stuck_entries,
+                max_tries,
+                last_exception,
+            } => {
+                warn!(
+                    table = table,
+                    stuck_entries = stuck_entries,
+                    max_tries = max_tries,
+                    last_exception = last_exception,
+                    "ClickHouse replication queue stuck"
+                );
+            }
+            Notification::StuckMutation {
+                table,
+                mutation_id,
+                command,
+                age_secs,
+                parts_to_do,
+                fail_reason,
+            } => {
+                warn!(
+                    table = table,
+                    mutation_id = mutation_id,
+                    command = command,
+                    age_secs = age_secs,
+                    parts_to_do = parts_to_do,
+                    fail_reason = fail_reason,
+                    "ClickHouse mutation stuck"
+                );
+            }
+            Notification::SinkCountMismatch {
+                sink,
+                project_id,
+                day,
+                primary_events,
+                sink_events,
+            } => {
+                warn!(
+                    sink = sink,
+                    project_id = project_id,
+                    day = day,
+                    primary_events = primary_events,
+                    sink_events = sink_events,
+                    "Sink event count differs from primary"
+                );
+            }
         }
     }
 
@@ -295,6 +417,55 @@ impl NotificationWorker {
             .await?;
         }
 
+        // Alert on slow and failed inserts from our own user
+        if ops.has_slow_inserts() {
+            self.send(Notification::SlowInserts {
+                count: ops.query_log.slow_inserts,
+                max_duration_ms: ops.query_log.max_insert_duration_ms,
+            })
+            .await?;
+        }
+        if ops.has_failed_inserts() {
+            self.send(Notification::FailedInserts {
+                count: ops.query_log.failed_inserts,
+                last_error: ops.query_log.last_error.clone(),
+            })
+            .await?;
+        }
+
+        // Alert on replication problems (replicated setups only)
+        for replica in ops.unhealthy_replicas() {
+            self.send(Notification::ReplicationLag {
+                table: format!("{}.{}", replica.database, replica.table),
+                readonly: replica.is_readonly != 0,
+                delay_secs: replica.absolute_delay,
+                queue_size: replica.queue_size,
+            })
+            .await?;
+        }
+        for queue in &ops.replication_queue {
+            self.send(Notification::ReplicationQueueStuck {
+                table: format!("{}.{}", queue.database, queue.table),
+                stuck_entries: queue.stuck_entries,
+                max_tries: queue.max_num_tries,
+                last_exception: queue.last_exception.clone(),
+            })
+            .await?;
+        }
+
+        // Alert on stuck mutations
+        for mutation in ops.stuck_mutations() {
+            self.send(Notification::StuckMutation {
+                table: format!("{}.{}", mutation.database, mutation.table),
+                mutation_id: mutation.mutation_id.clone(),
+                command: mutation.command.clone(),
+                age_secs: mutation.age_secs,
+                parts_to_do: mutation.parts_to_do,
+                fail_reason: mutation.latest_fail_reason.clone(),
+            })
+            .await?;
+        }
+
         Ok(())
     }
 }
@@ -2,12 +2,26 @@
 //!
 //! Instead of row-level TTL (which causes continuous background mutations),
 //! this worker drops entire partitions that are older than the retention period.
+//!
+//! With an [`ArchiveConfig`] set, each data table partition is first exported
+//! to Parquet per project (see `clickhouse_client::archive`). A partition
+//! whose export fails is kept for the next run unless `require_archive` is off.
+//!
+//! Tables with a [`TieringPolicy`] instead have partitions past their hot
+//! window moved to a cheaper volume (`ALTER TABLE ... MOVE PARTITION ... TO
+//! VOLUME`), and are only dropped if the policy sets `drop_after_months`.
+//!
+//! [`RetentionWorker::plan`] returns what a run would do without touching
+//! any data, and every executed drop is recorded in `overwatch.retention_audit`.
 
 use chrono::{Datelike, Utc};
 use clickhouse::Row;
+use clickhouse_client::archive::{self, ArchiveConfig};
+use clickhouse_client::ops::volume_disks;
 use clickhouse_client::ClickHouseClient;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use telemetry::metrics;
 use tracing::{debug, error, info, warn};
 
 /// All tables that need retention enforcement via partition drops.
@@ -37,6 +51,93 @@ const DEFAULT_RETENTION_MONTHS: u32 = 3; // ~90 days
 /// Retention in months for internal metrics.
 const METRICS_RETENTION_MONTHS: u32 = 1; // ~30 days
 
+/// Per-table policy for moving aging partitions to a cold volume.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieringPolicy {
+    /// Fully qualified table, e.g. `overwatch.events`
+    pub table: String,
+    /// Months kept on the table's default (hot) volume
+    #[serde(default = "default_hot_months")]
+    pub hot_months: u32,
+    /// Volume in the table's storage policy to move partitions to
+    #[serde(default = "default_cold_volume")]
+    pub volume: String,
+    /// Months before partitions are dropped; `None` keeps them on the cold
+    /// volume indefinitely
+    #[serde(default)]
+    pub drop_after_months: Option<u32>,
+}
+
+fn default_hot_months() -> u32 {
+    1
+}
+
+fn default_cold_volume() -> String {
+    "cold".to_string()
+}
+
+impl TieringPolicy {
+    /// Check the policy targets a retention table and a plain volume name.
+    fn validate(&self) -> Result<(), String> {
+        if !RETENTION_TABLES.contains(&self.table.as_str()) {
+            return Err(format!("Tiering not supported for table: {}", self.table));
+        }
+        if self.volume.is_empty()
+            || !self
+                .volume
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("Invalid volume name: {}", self.volume));
+        }
+        if let Some(drop_after) = self.drop_after_months {
+            if drop_after <= self.hot_months {
+                return Err(format!(
+                    "drop_after_months ({}) must exceed hot_months ({})",
+                    drop_after, self.hot_months
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the retention worker does with a partition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum RetentionAction {
+    Drop,
+    Move { volume: String },
+}
+
+/// One partition in a retention plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedAction {
+    pub table: String,
+    pub partition: String,
+    pub partition_id: String,
+    pub rows: u64,
+    pub bytes: u64,
+    #[serde(flatten)]
+    pub action: RetentionAction,
+    /// Rule that selected the partition
+    pub reason: String,
+}
+
+/// Remove moves of partitions that `plan` already drops.
+fn without_dropped(plan: &[PlannedAction], moves: Vec<PlannedAction>) -> Vec<PlannedAction> {
+    moves
+        .into_iter()
+        .filter(|planned| {
+            !plan.iter().any(|action| {
+                matches!(action.action, RetentionAction::Drop)
+                    && action.table == planned.table
+                    && action.partition_id == planned.partition_id
+            })
+        })
+        .collect()
+}
+
 /// Partition info from system.parts.
 #[derive(Debug, Clone, Row, Deserialize)]
 struct PartitionInfo {
@@ -51,106 +152,128 @@ struct PartitionInfo {
 /// Worker that enforces retention policies by dropping old partitions.
 pub struct RetentionWorker {
     clickhouse: Arc<ClickHouseClient>,
+    archive: Option<ArchiveConfig>,
+    tiering: Vec<TieringPolicy>,
 }
 
 impl RetentionWorker {
     pub fn new(clickhouse: Arc<ClickHouseClient>) -> Self {
-        Self { clickhouse }
+        Self {
+            clickhouse,
+            archive: None,
+            tiering: Vec::new(),
+        }
     }
 
-    /// Run retention enforcement across all tables.
-    pub async fn run(&self) -> Result<(), String> {
-        info!("Running retention worker - partition-based deletion");
+    /// Export data table partitions to Parquet before dropping them.
+    pub fn with_archive(mut self, archive: Option<ArchiveConfig>) -> Self {
+        self.archive = archive;
+        self
+    }
 
-        let now = Utc::now();
+    /// Move aging partitions to cold volumes per table. Invalid policies are
+    /// logged and ignored.
+    pub fn with_tiering(mut self, policies: Vec<TieringPolicy>) -> Self {
+        self.tiering = policies
+            .into_iter()
+            .filter(|policy| match policy.validate() {
+                Ok(()) => true,
+                Err(e) => {
+                    error!(table = %policy.table, error = %e, "Ignoring tiering policy");
+                    false
+                }
+            })
+            .collect();
+        self
+    }
 
-        // Enforce retention for data tables (90 days = ~3 months)
-        let data_cutoff = calculate_cutoff_partition(now, DEFAULT_RETENTION_MONTHS);
-        info!(
-            cutoff_partition = %data_cutoff,
-            retention_months = DEFAULT_RETENTION_MONTHS,
-            "Enforcing data table retention"
-        );
+    fn tiering_policy(&self, table: &str) -> Option<&TieringPolicy> {
+        self.tiering.iter().find(|policy| policy.table == table)
+    }
+
+    /// Build the retention plan without executing it.
+    ///
+    /// Lists every partition the next run would drop or move, with the rule
+    /// that selected it. Tables whose partitions can't be inspected are
+    /// logged and left out.
+    pub async fn plan(&self) -> Result<Vec<PlannedAction>, String> {
+        let now = Utc::now();
+        let mut plan = Vec::new();
 
+        // Data tables: 90 days = ~3 months, unless a tiering policy says otherwise
         for table in RETENTION_TABLES {
-            if let Err(e) = self.drop_old_partitions(table, &data_cutoff).await {
-                warn!(table = table, error = %e, "Failed to enforce retention");
+            let months = match self.tiering_policy(table) {
+                Some(policy) => match policy.drop_after_months {
+                    Some(months) => months,
+                    None => continue,
+                },
+                None => DEFAULT_RETENTION_MONTHS,
+            };
+            match self.plan_drops(table, now, months).await {
+                Ok(actions) => plan.extend(actions),
+                Err(e) => warn!(table = table, error = %e, "Failed to plan retention"),
             }
         }
 
-        // Enforce retention for internal metrics (30 days = ~1 month)
-        let metrics_cutoff = calculate_cutoff_partition(now, METRICS_RETENTION_MONTHS);
-        info!(
-            cutoff_partition = %metrics_cutoff,
-            retention_months = METRICS_RETENTION_MONTHS,
-            "Enforcing metrics table retention"
-        );
+        // Partitions past their hot window move to cold volumes, unless
+        // they are being dropped anyway
+        for policy in &self.tiering {
+            match self.plan_moves(policy, now).await {
+                Ok(actions) => {
+                    let actions = without_dropped(&plan, actions);
+                    plan.extend(actions);
+                }
+                Err(e) => warn!(table = %policy.table, error = %e, "Failed to plan tiering"),
+            }
+        }
 
-        if let Err(e) = self
-            .drop_old_partitions(METRICS_TABLE, &metrics_cutoff)
+        // Internal metrics: 30 days = ~1 month
+        match self
+            .plan_drops(METRICS_TABLE, now, METRICS_RETENTION_MONTHS)
             .await
         {
-            warn!(table = METRICS_TABLE, error = %e, "Failed to enforce metrics retention");
+            Ok(actions) => plan.extend(actions),
+            Err(e) => warn!(table = METRICS_TABLE, error = %e, "Failed to plan metrics retention"),
         }
 
-        info!("Retention check complete");
-        Ok(())
+        Ok(plan)
     }
 
-    /// Drop partitions older than the cutoff.
-    async fn drop_old_partitions(&self, table: &str, cutoff_partition: &str) -> Result<(), String> {
-        // Query for partitions older than cutoff
-        let partitions = self.get_old_partitions(table, cutoff_partition).await?;
+    /// Run retention enforcement across all tables.
+    pub async fn run(&self) -> Result<(), String> {
+        info!("Running retention worker - partition-based deletion");
 
-        if partitions.is_empty() {
-            debug!(
-                table = table,
-                cutoff = cutoff_partition,
-                "No partitions to drop"
-            );
-            return Ok(());
+        let plan = self.plan().await?;
+        if plan.is_empty() {
+            debug!("No partitions to drop or move");
         }
 
         let mut dropped_count = 0;
         let mut dropped_rows = 0u64;
         let mut dropped_bytes = 0u64;
-
-        for partition in &partitions {
-            info!(
-                table = table,
-                partition = %partition.partition,
-                partition_id = %partition.partition_id,
-                rows = partition.rows,
-                bytes = partition.bytes_on_disk,
-                "Dropping partition"
-            );
-
-            // Use partition_id (which is the numeric value like "202301")
-            let sql = format!(
-                "ALTER TABLE {} DROP PARTITION '{}'",
-                table, partition.partition_id
-            );
-
-            match self.clickhouse.inner().query(&sql).execute().await {
-                Ok(_) => {
-                    dropped_count += 1;
-                    dropped_rows += partition.rows;
-                    dropped_bytes += partition.bytes_on_disk;
+        let mut moved_count = 0;
+        let mut moved_bytes = 0u64;
+
+        for action in &plan {
+            match &action.action {
+                RetentionAction::Drop => {
+                    if self.drop_partition(action).await {
+                        dropped_count += 1;
+                        dropped_rows += action.rows;
+                        dropped_bytes += action.bytes;
+                    }
                 }
-                Err(e) => {
-                    error!(
-                        table = table,
-                        partition = %partition.partition_id,
-                        error = %e,
-                        "Failed to drop partition"
-                    );
+                RetentionAction::Move { volume } => {
+                    if self.move_partition(action, volume).await {
+                        moved_count += 1;
+                        moved_bytes += action.bytes;
+                    }
                 }
             }
         }
 
         if dropped_count > 0 {
             info!(
-                table = table,
                 dropped_partitions = dropped_count,
                 dropped_rows = dropped_rows,
                 dropped_bytes = dropped_bytes,
@@ -158,10 +281,315 @@ impl RetentionWorker {
                 "Partition cleanup complete"
             );
         }
+        if moved_count > 0 {
+            info!(
+                moved_partitions = moved_count,
+                moved_bytes = moved_bytes,
+                moved_bytes_human = %format_bytes(moved_bytes),
+                "Partition tiering complete"
+            );
+        }
 
+        info!("Retention check complete");
         Ok(())
     }
 
+    /// Plan drops for partitions older than `months` of retention.
+    async fn plan_drops(
+        &self,
+        table: &str,
+        now: chrono::DateTime<Utc>,
+        months: u32,
+    ) -> Result<Vec<PlannedAction>, String> {
+        let cutoff = calculate_cutoff_partition(now, months);
+        let partitions = self.get_old_partitions(table, &cutoff).await?;
+
+        Ok(partitions
+            .into_iter()
+            .map(|p| PlannedAction {
+                table: table.to_string(),
+                partition: p.partition,
+                partition_id: p.partition_id,
+                rows: p.rows,
+                bytes: p.bytes_on_disk,
+                action: RetentionAction::Drop,
+                reason: format!("Older than {}-month retention (cutoff {})", months, cutoff),
+            })
+            .collect())
+    }
+
+    /// Plan moves for partitions past the policy's hot window.
+    ///
+    /// Partitions already fully on the volume are left out.
+    async fn plan_moves(
+        &self,
+        policy: &TieringPolicy,
+        now: chrono::DateTime<Utc>,
+    ) -> Result<Vec<PlannedAction>, String> {
+        let (database, table_name) = policy
+            .table
+            .split_once('.')
+            .ok_or_else(|| format!("Invalid table name: {}", policy.table))?;
+
+        let disks = volume_disks(&self.clickhouse, database, table_name, &policy.volume)
+            .await
+            .map_err(|e| e.to_string())?;
+        if disks.is_empty() {
+            return Err(format!(
+                "Volume '{}' not in storage policy of {}",
+                policy.volume, policy.table
+            ));
+        }
+
+        let hot_cutoff = calculate_cutoff_partition(now, policy.hot_months);
+        let partitions = self
+            .get_partitions_to_move(database, table_name, &policy.volume, &hot_cutoff)
+            .await?;
+
+        Ok(partitions
+            .into_iter()
+            .map(|p| PlannedAction {
+                table: policy.table.clone(),
+                partition: p.partition,
+                partition_id: p.partition_id,
+                rows: p.rows,
+                bytes: p.bytes_on_disk,
+                action: RetentionAction::Move {
+                    volume: policy.volume.clone(),
+                },
+                reason: format!(
+                    "Past {}-month hot window (cutoff {})",
+                    policy.hot_months, hot_cutoff
+                ),
+            })
+            .collect())
+    }
+
+    /// Archive (if configured), drop and audit one partition.
+    ///
+    /// Returns whether the partition was dropped.
+    async fn drop_partition(&self, action: &PlannedAction) -> bool {
+        let table = action.table.as_str();
+        info!(
+            table = table,
+            partition = %action.partition,
+            partition_id = %action.partition_id,
+            rows = action.rows,
+            bytes = action.bytes,
+            "Dropping partition"
+        );
+
+        let Some(archived_files) = self.archive_partition(table, &action.partition_id).await else {
+            return false;
+        };
+
+        // Use partition_id (which is the numeric value like "202301")
+        let sql = format!(
+            "ALTER TABLE {} DROP PARTITION '{}'",
+            table, action.partition_id
+        );
+
+        if let Err(e) = self.clickhouse.inner().query(&sql).execute().await {
+            error!(
+                table = table,
+                partition = %action.partition_id,
+                error = %e,
+                "Failed to drop partition"
+            );
+            return false;
+        }
+
+        if let Err(e) = self.record_drop(action, archived_files).await {
+            error!(
+                table = table,
+                partition = %action.partition_id,
+                error = %e,
+                "Failed to record partition drop in audit log"
+            );
+        }
+        true
+    }
+
+    /// Append an executed drop to `overwatch.retention_audit`.
+    async fn record_drop(
+        &self,
+        action: &PlannedAction,
+        archived_files: usize,
+    ) -> Result<(), String> {
+        self.clickhouse
+            .inner()
+            .query(
+                "INSERT INTO overwatch.retention_audit \
+                 (executed_at, table, partition, partition_id, rows, bytes, reason, archived_files) \
+                 VALUES (now64(3), ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(action.table.as_str())
+            .bind(action.partition.as_str())
+            .bind(action.partition_id.as_str())
+            .bind(action.rows)
+            .bind(action.bytes)
+            .bind(action.reason.as_str())
+            .bind(archived_files as u32)
+            .execute()
+            .await
+            .map_err(|e| format!("Audit insert error: {}", e))
+    }
+
+    /// Move one partition to a cold volume.
+    ///
+    /// Skipped if the volume's free space is smaller than the partition.
+    /// Returns whether the partition was moved.
+    async fn move_partition(&self, action: &PlannedAction, volume: &str) -> bool {
+        let table = action.table.as_str();
+        let Some((database, table_name)) = table.split_once('.') else {
+            return false;
+        };
+
+        let free_space: u64 = match volume_disks(&self.clickhouse, database, table_name, volume)
+            .await
+        {
+            Ok(disks) => disks.iter().map(|d| d.free_space).sum(),
+            Err(e) => {
+                error!(table = table, volume = volume, error = %e, "Failed to read volume disks");
+                return false;
+            }
+        };
+        if action.bytes > free_space {
+            warn!(
+                table = table,
+                partition_id = %action.partition_id,
+                bytes = action.bytes,
+                free_space = free_space,
+                volume = volume,
+                "Not enough space on cold volume, skipping move"
+            );
+            return false;
+        }
+
+        info!(
+            table = table,
+            partition = %action.partition,
+            partition_id = %action.partition_id,
+            rows = action.rows,
+            bytes = action.bytes,
+            volume = volume,
+            "Moving partition to cold volume"
+        );
+
+        let sql = format!(
+            "ALTER TABLE {} MOVE PARTITION ID '{}' TO VOLUME '{}'",
+            table, action.partition_id, volume
+        );
+
+        match self.clickhouse.inner().query(&sql).execute().await {
+            Ok(_) => {
+                metrics().partitions_moved.inc();
+                metrics().bytes_moved.inc_by(action.bytes);
+                true
+            }
+            Err(e) => {
+                error!(
+                    table = table,
+                    partition = %action.partition_id,
+                    error = %e,
+                    "Failed to move partition"
+                );
+                false
+            }
+        }
+    }
+
+    /// Get partitions older than the cutoff with parts off the given volume.
+    async fn get_partitions_to_move(
+        &self,
+        database: &str,
+        table_name: &str,
+        volume: &str,
+        cutoff_partition: &str,
+    ) -> Result<Vec<PartitionInfo>, String> {
+        let sql = r#"
+            SELECT
+                partition,
+                partition_id,
+                sum(rows) as total_rows,
+                sum(bytes_on_disk) as total_bytes
+            FROM system.parts
+            WHERE database = ?
+              AND table = ?
+              AND active = 1
+              AND partition_id < ?
+            GROUP BY partition, partition_id
+            HAVING countIf(disk_name NOT IN (
+                SELECT arrayJoin(disks)
+                FROM system.storage_policies
+                WHERE volume_name = ?
+                  AND policy_name = (
+                      SELECT storage_policy FROM system.tables
+                      WHERE database = ? AND name = ?
+                  )
+            )) > 0
+            ORDER BY partition_id
+        "#;
+
+        self.clickhouse
+            .inner()
+            .query(sql)
+            .bind(database)
+            .bind(table_name)
+            .bind(cutoff_partition)
+            .bind(volume)
+            .bind(database)
+            .bind(table_name)
+            .fetch_all()
+            .await
+            .map_err(|e| format!("Query error: {}", e))
+    }
+
+    /// Export a partition before it is dropped.
+    ///
+    /// Returns the number of archive files written, or `None` if the drop
+    /// must not go ahead. Only the per-project data tables are archived;
+    /// internal metrics are always dropped.
+    async fn archive_partition(&self, table: &str, partition_id: &str) -> Option<usize> {
+        let Some(config) = &self.archive else {
+            return Some(0);
+        };
+        if !RETENTION_TABLES.contains(&table) {
+            return Some(0);
+        }
+
+        match archive::export_partition(&self.clickhouse, &config.target, table, partition_id).await
+        {
+            Ok(entries) => {
+                info!(
+                    table = table,
+                    partition_id = partition_id,
+                    files = entries.len(),
+                    "Partition archived"
+                );
+                Some(entries.len())
+            }
+            Err(e) if config.require_archive => {
+                error!(
+                    table = table,
+                    partition_id = partition_id,
+                    error = %e,
+                    "Failed to archive partition, keeping it"
+                );
+                None
+            }
+            Err(e) => {
+                warn!(
+                    table = table,
+                    partition_id = partition_id,
+                    error = %e,
+                    "Failed to archive partition, dropping anyway"
+                );
+                Some(0)
+            }
+        }
+    }
+
     /// Get partitions older than the cutoff from system.parts.
     async fn get_old_partitions(
         &self,
@@ -257,6 +685,84 @@ mod tests {
         assert_eq!(calculate_cutoff_partition(now, 1), "202312");
     }
 
+    #[test]
+    fn test_tiering_policy_validate() {
+        let policy: TieringPolicy =
+            serde_json::from_str(r#"{"table":"overwatch.events"}"#).unwrap();
+        assert_eq!(policy.hot_months, 1);
+        assert_eq!(policy.volume, "cold");
+        assert!(policy.validate().is_ok());
+
+        let bad_table = TieringPolicy {
+            table: "system.parts".to_string(),
+            ..policy.clone()
+        };
+        assert!(bad_table.validate().is_err());
+
+        let bad_volume = TieringPolicy {
+            volume: "cold' SETTINGS x=1".to_string(),
+            ..policy.clone()
+        };
+        assert!(bad_volume.validate().is_err());
+
+        let bad_drop = TieringPolicy {
+            hot_months: 3,
+            drop_after_months: Some(2),
+            ..policy
+        };
+        assert!(bad_drop.validate().is_err());
+    }
+
+    #[test]
+    fn test_moves_skip_dropped_partitions() {
+        let action = |table: &str, partition_id: &str, action: RetentionAction| PlannedAction {
+            table: table.to_string(),
+            partition: partition_id.to_string(),
+            partition_id: partition_id.to_string(),
+            rows: 10,
+            bytes: 100,
+            action,
+            reason: String::new(),
+        };
+        let cold = || RetentionAction::Move {
+            volume: "cold".to_string(),
+        };
+
+        // drop_after_months = 12 and hot_months = 1 overlap below the drop cutoff
+        let plan = vec![
+            action("overwatch.events", "202301", RetentionAction::Drop),
+            action("overwatch.events", "202302", RetentionAction::Drop),
+            action("overwatch.sessions", "202303", RetentionAction::Drop),
+        ];
+        let moves = vec![
+            action("overwatch.events", "202302", cold()),
+            action("overwatch.events", "202303", cold()),
+        ];
+
+        let moves = without_dropped(&plan, moves);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].partition_id, "202303");
+    }
+
+    #[test]
+    fn test_planned_action_json() {
+        let action = PlannedAction {
+            table: "overwatch.events".to_string(),
+            partition: "202401".to_string(),
+            partition_id: "202401".to_string(),
+            rows: 10,
+            bytes: 2048,
+            action: RetentionAction::Move {
+                volume: "cold".to_string(),
+            },
+            reason: "Past 1-month hot window (cutoff 202402)".to_string(),
+        };
+        let json = serde_json::to_value(&action).unwrap();
+        assert_eq!(json["action"], "move");
+        assert_eq!(json["volume"], "cold");
+        assert_eq!(json["rows"], 10);
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(format_bytes(500), "500 B");
@@ -3,15 +3,19 @@
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::interval;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+use clickhouse_client::archive::ArchiveConfig;
 use clickhouse_client::ClickHouseClient;
 use redpanda::Consumer;
 
+use crate::backpressure::{Backpressure, BackpressureConfig};
 use crate::compression::CompressionWorker;
 use crate::consumer::ConsumerWorker;
-use crate::notifications::NotificationWorker;
-use crate::retention::RetentionWorker;
+use crate::forwarding::ForwardingWorker;
+use crate::notifications::{Notification, NotificationWorker};
+use crate::retention::{RetentionWorker, TieringPolicy};
+use crate::sinks::{compare_sinks, Sink};
 
 /// Worker scheduler configuration.
 #[derive(Debug, Clone)]
@@ -24,6 +28,18 @@ pub struct WorkerConfig {
     pub metrics_flush_interval: Duration,
     /// Notification check interval
     pub notification_check_interval: Duration,
+    /// Consumer backpressure tuning (driven by merge pressure)
+    pub backpressure: BackpressureConfig,
+    /// Parquet export before retention drops (disabled when `None`)
+    pub archive: Option<ArchiveConfig>,
+    /// Per-table cold volume moves
+    pub tiering: Vec<TieringPolicy>,
+    /// Sink count comparison interval (only runs with extra sinks)
+    pub sink_comparison_interval: Duration,
+    /// Completed days compared per sink comparison run
+    pub sink_comparison_days: u32,
+    /// How often interrupted or failed erasure requests are retried
+    pub erasure_resume_interval: Duration,
 }
 
 impl Default for WorkerConfig {
@@ -33,6 +49,12 @@ impl Default for WorkerConfig {
             retention_interval: Duration::from_secs(3600),   // 1 hour
             metrics_flush_interval: Duration::from_secs(60), // 1 minute
             notification_check_interval: Duration::from_secs(60), // 1 minute
+            backpressure: BackpressureConfig::default(),
+            archive: None,
+            tiering: Vec::new(),
+            sink_comparison_interval: Duration::from_secs(3600), // 1 hour
+            sink_comparison_days: 7,
+            erasure_resume_interval: Duration::from_secs(300), // 5 minutes
         }
     }
 }
@@ -42,14 +64,20 @@ pub struct WorkerScheduler {
     config: WorkerConfig,
     clickhouse: Arc<ClickHouseClient>,
     consumer: Option<Arc<Consumer>>,
+    backpressure: Arc<Backpressure>,
+    sinks: Vec<Arc<dyn Sink>>,
+    forwarding: Option<Arc<ForwardingWorker>>,
 }
 
 impl WorkerScheduler {
     pub fn new(config: WorkerConfig, clickhouse: Arc<ClickHouseClient>) -> Self {
         Self {
+            backpressure: Arc::new(Backpressure::new(config.backpressure.clone())),
             config,
             clickhouse,
             consumer: None,
+            sinks: Vec::new(),
+            forwarding: None,
         }
     }
 
@@ -60,12 +88,32 @@ impl WorkerScheduler {
         consumer: Arc<Consumer>,
     ) -> Self {
         Self {
+            backpressure: Arc::new(Backpressure::new(config.backpressure.clone())),
             config,
             clickhouse,
             consumer: Some(consumer),
+            sinks: Vec::new(),
+            forwarding: None,
         }
     }
 
+    /// Extra sinks written alongside the primary.
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn Sink>>) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    /// Runs customer event forwarding alongside the pipeline.
+    pub fn with_forwarding(mut self, forwarding: ForwardingWorker) -> Self {
+        self.forwarding = Some(Arc::new(forwarding));
+        self
+    }
+
+    /// Returns the shared backpressure handle.
+    pub fn backpressure(&self) -> Arc<Backpressure> {
+        self.backpressure.clone()
+    }
+
     /// Starts all background workers.
     pub fn start(self: Arc<Self>) -> Vec<tokio::task::JoinHandle<()>> {
         let mut handles = Vec::new();
@@ -74,8 +122,12 @@ impl WorkerScheduler {
         if let Some(ref consumer) = self.consumer {
             let consumer = consumer.clone();
             let clickhouse = self.clickhouse.clone();
+            let backpressure = self.backpressure.clone();
+            let sinks = self.sinks.clone();
             handles.push(tokio::spawn(async move {
-                let worker = ConsumerWorker::new(consumer, clickhouse);
+                let worker = ConsumerWorker::new(consumer, clickhouse)
+                    .with_backpressure(backpressure)
+                    .with_sinks(sinks);
                 if let Err(e) = worker.run().await {
                     error!("Consumer worker fatal error: {}", e);
                 }
@@ -83,6 +135,13 @@ impl WorkerScheduler {
             info!("Consumer worker started");
         }
 
+        // Forwarding worker (Redpanda → customer webhooks)
+        if let Some(ref forwarding) = self.forwarding {
+            let forwarding = forwarding.clone();
+            handles.push(tokio::spawn(forwarding.run()));
+            info!("Forwarding worker started");
+        }
+
         // Compression worker
         let scheduler = self.clone();
         handles.push(tokio::spawn(async move {
@@ -107,6 +166,20 @@ impl WorkerScheduler {
             scheduler.run_notification_worker().await;
         }));
 
+        // Erasure requests interrupted by a restart
+        let scheduler = self.clone();
+        handles.push(tokio::spawn(async move {
+            scheduler.run_erasure_resume().await;
+        }));
+
+        // Sink count comparison
+        if self.sinks.iter().any(|sink| sink.as_clickhouse().is_some()) {
+            let scheduler = self.clone();
+            handles.push(tokio::spawn(async move {
+                scheduler.run_sink_comparison().await;
+            }));
+        }
+
         info!("Background workers started");
         handles
     }
@@ -125,7 +198,9 @@ impl WorkerScheduler {
     }
 
     async fn run_retention_worker(&self) {
-        let worker = RetentionWorker::new(self.clickhouse.clone());
+        let worker = RetentionWorker::new(self.clickhouse.clone())
+            .with_archive(self.config.archive.clone())
+            .with_tiering(self.config.tiering.clone());
         let mut ticker = interval(self.config.retention_interval);
 
         loop {
@@ -153,13 +228,35 @@ impl WorkerScheduler {
                 error!("Failed to flush metrics: {}", e);
             }
 
-            // Collect and log ClickHouse operational metrics
-            match collect_ops_metrics(&self.clickhouse).await {
+            // Per-sink write metrics (only registered once a sink has written)
+            if !self.sinks.is_empty() {
+                for (name, sink) in metrics().sinks() {
+                    info!(
+                        sink = %name,
+                        inserts = sink.inserts.get(),
+                        insert_errors = sink.insert_errors.get(),
+                        events_inserted = sink.events_inserted.get(),
+                        mean_latency_ms = sink.latency_ms.mean(),
+                        "Sink metrics"
+                    );
+                }
+                for sink in &self.sinks {
+                    if !sink.health().await {
+                        warn!(sink = %sink.name(), "Sink unhealthy");
+                    }
+                }
+            }
+
+            // Collect and log ClickHouse operational metrics, and feed
+            // merge pressure back into the consumer
+            match collect_ops_metrics(&self.clickhouse, self.config.metrics_flush_interval).await {
                 Ok(ops_metrics) => {
                     log_ops_metrics(&ops_metrics);
+                    self.backpressure.update(&ops_metrics);
                 }
                 Err(e) => {
                     error!("Failed to collect ClickHouse ops metrics: {}", e);
+                    self.backpressure.reset();
                 }
             }
         }
@@ -180,7 +277,9 @@ impl WorkerScheduler {
             }
 
             // Check ClickHouse ops metrics for alerts
-            match collect_ops_metrics(&self.clickhouse).await {
+            match collect_ops_metrics(&self.clickhouse, self.config.notification_check_interval)
+                .await
+            {
                 Ok(ops_metrics) => {
                     if let Err(e) = worker.check_clickhouse_ops(&ops_metrics).await {
                         error!("ClickHouse ops notification error: {}", e);
@@ -192,4 +291,57 @@ impl WorkerScheduler {
             }
         }
     }
+
+    async fn run_erasure_resume(&self) {
+        use clickhouse_client::gdpr::resume_erasures;
+
+        let mut ticker = interval(self.config.erasure_resume_interval);
+
+        loop {
+            ticker.tick().await;
+
+            // Requests newer than two intervals may still be running in a handler
+            match resume_erasures(&self.clickhouse, 2 * self.config.erasure_resume_interval).await {
+                Ok(0) => {}
+                Ok(completed) => info!(completed = completed, "Resumed erasure requests"),
+                Err(e) => error!("Erasure resume error: {}", e),
+            }
+        }
+    }
+
+    async fn run_sink_comparison(&self) {
+        let worker = NotificationWorker::from_env();
+        let mut ticker = interval(self.config.sink_comparison_interval);
+
+        loop {
+            ticker.tick().await;
+
+            let mismatches = match compare_sinks(
+                &self.clickhouse,
+                &self.sinks,
+                self.config.sink_comparison_days,
+            )
+            .await
+            {
+                Ok(mismatches) => mismatches,
+                Err(e) => {
+                    error!("Sink comparison error: {}", e);
+                    continue;
+                }
+            };
+
+            for mismatch in mismatches {
+                let notification = Notification::SinkCountMismatch {
+                    sink: mismatch.sink,
+                    project_id: mismatch.project_id,
+                    day: mismatch.day,
+                    primary_events: mismatch.primary_events,
+                    sink_events: mismatch.sink_events,
+                };
+                if let Err(e) = worker.send(notification).await {
+                    error!("Sink comparison notification error: {}", e);
+                }
+            }
+        }
+    }
 }
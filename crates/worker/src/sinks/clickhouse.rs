@@ -0,0 +1,227 @@
+//! ClickHouse sink and cross-sink count comparison.
+//!
+//! [`compare_sinks`] checks per-project daily counts of every ClickHouse
+//! sink against the primary.
+
+use super::{Sink, SinkPolicy, PRIMARY_SINK};
+use async_trait::async_trait;
+use clickhouse_client::query::{daily_project_counts, DailyProjectCount};
+use clickhouse_client::{ClickHouseClient, ClickHouseConfig};
+use engine_core::{ClickHouseEvent, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Writes batches to a ClickHouse cluster's unified `events` table.
+#[derive(Clone)]
+pub struct ClickHouseSink {
+    name: String,
+    policy: SinkPolicy,
+    client: Arc<ClickHouseClient>,
+    /// Also maintain the property key catalog (primary only)
+    record_properties: bool,
+}
+
+impl ClickHouseSink {
+    pub fn new(name: impl Into<String>, policy: SinkPolicy, client: Arc<ClickHouseClient>) -> Self {
+        Self {
+            name: name.into(),
+            policy,
+            client,
+            record_properties: false,
+        }
+    }
+
+    /// The consumer's own client: required, and keeps the property catalog.
+    pub fn primary(client: Arc<ClickHouseClient>) -> Self {
+        Self {
+            record_properties: true,
+            ..Self::new(PRIMARY_SINK, SinkPolicy::Required, client)
+        }
+    }
+
+    /// Creates a sink with its own client.
+    pub fn from_config(name: &str, policy: SinkPolicy, config: &ClickHouseConfig) -> Result<Self> {
+        let client = ClickHouseClient::new(config.clone())?;
+        Ok(Self::new(name, policy, Arc::new(client)))
+    }
+
+    pub fn client(&self) -> &Arc<ClickHouseClient> {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl Sink for ClickHouseSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn policy(&self) -> SinkPolicy {
+        self.policy
+    }
+
+    /// Inserts all events into the unified events table.
+    ///
+    /// This follows the industry-standard analytics pattern (like Mixpanel/Amplitude):
+    /// - Single unified `events` table for all event types
+    /// - Event-specific data stored in JSON `data` field
+    /// - ClickHouse handles JSON queries efficiently
+    async fn write(&self, events: &[ClickHouseEvent]) -> Result<usize> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        // Parsed once for both the row maps and the catalog
+        let properties = clickhouse_client::properties::batch_properties(events);
+        let count = clickhouse_client::insert::insert_clickhouse_events_with_properties(
+            &self.client,
+            events.to_vec(),
+            &properties,
+        )
+        .await?;
+
+        // Keep the property key catalog current (best-effort, never fails the batch)
+        if self.record_properties {
+            if let Err(e) = clickhouse_client::properties::record_property_keys(
+                &self.client,
+                events,
+                &properties,
+            )
+            .await
+            {
+                warn!(error = %e, "Failed to update property key catalog");
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn health(&self) -> bool {
+        clickhouse_client::health::check_connection(&self.client).await
+    }
+
+    fn as_clickhouse(&self) -> Option<&ClickHouseSink> {
+        Some(self)
+    }
+}
+
+/// A project/day whose event count differs between two sinks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CountMismatch {
+    pub sink: String,
+    pub project_id: String,
+    pub day: String,
+    pub primary_events: u64,
+    pub sink_events: u64,
+}
+
+/// Diff per-project daily counts of a sink against the primary.
+///
+/// A project/day missing on one side counts as zero there.
+pub fn diff_counts(
+    sink: &str,
+    primary: &[DailyProjectCount],
+    other: &[DailyProjectCount],
+) -> Vec<CountMismatch> {
+    let index = |rows: &[DailyProjectCount]| -> BTreeMap<(String, String), u64> {
+        rows.iter()
+            .map(|r| ((r.project_id.clone(), r.day.clone()), r.events))
+            .collect()
+    };
+    let primary = index(primary);
+    let other = index(other);
+
+    let keys: BTreeSet<_> = primary.keys().chain(other.keys()).cloned().collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let primary_events = primary.get(&key).copied().unwrap_or(0);
+            let sink_events = other.get(&key).copied().unwrap_or(0);
+            (primary_events != sink_events).then(|| CountMismatch {
+                sink: sink.to_string(),
+                project_id: key.0,
+                day: key.1,
+                primary_events,
+                sink_events,
+            })
+        })
+        .collect()
+}
+
+/// Compare per-project daily counts over the last `days` completed days.
+///
+/// Sinks that are not backed by ClickHouse are skipped.
+pub async fn compare_sinks(
+    primary: &ClickHouseClient,
+    sinks: &[Arc<dyn Sink>],
+    days: u32,
+) -> Result<Vec<CountMismatch>> {
+    let primary_counts = daily_project_counts(primary, days).await?;
+
+    let mut mismatches = Vec::new();
+    for sink in sinks.iter().filter_map(|sink| sink.as_clickhouse()) {
+        let sink_counts = match daily_project_counts(sink.client(), days).await {
+            Ok(counts) => counts,
+            Err(e) => {
+                warn!(sink = %sink.name(), error = %e, "Failed to read sink counts");
+                continue;
+            }
+        };
+        let diff = diff_counts(sink.name(), &primary_counts, &sink_counts);
+        if diff.is_empty() {
+            info!(sink = %sink.name(), days = days, "Sink counts match primary");
+        }
+        mismatches.extend(diff);
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(project_id: &str, day: &str, events: u64) -> DailyProjectCount {
+        DailyProjectCount {
+            project_id: project_id.to_string(),
+            day: day.to_string(),
+            events,
+        }
+    }
+
+    #[test]
+    fn test_diff_counts() {
+        let primary = vec![
+            count("a", "2024-01-01", 100),
+            count("a", "2024-01-02", 50),
+            count("b", "2024-01-01", 10),
+        ];
+        let other = vec![
+            count("a", "2024-01-01", 100),
+            count("a", "2024-01-02", 49),
+            count("c", "2024-01-01", 5),
+        ];
+
+        let diff = diff_counts("managed", &primary, &other);
+        let keys: Vec<(&str, &str, u64, u64)> = diff
+            .iter()
+            .map(|m| {
+                (
+                    m.project_id.as_str(),
+                    m.day.as_str(),
+                    m.primary_events,
+                    m.sink_events,
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("a", "2024-01-02", 50, 49),
+                ("b", "2024-01-01", 10, 0),
+                ("c", "2024-01-01", 0, 5),
+            ]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Local JSONL file sink.
+//!
+//! Each event is one JSON line in `<path>/<name>-<timestamp>-<seq>.jsonl`.
+//! A new file is started once the current one would exceed
+//! `max_file_bytes`; when `max_files` is set, the oldest files beyond it
+//! are deleted after each rotation. Flushes and rotations `fsync` the
+//! file, so a flushed batch survives a crash.
+
+use super::{Sink, SinkPolicy};
+use async_trait::async_trait;
+use engine_core::{ClickHouseEvent, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Currently open output file.
+struct OpenFile {
+    writer: BufWriter<File>,
+    bytes: u64,
+}
+
+/// Appends batches to size-rotated JSONL files.
+pub struct JsonlFileSink {
+    name: String,
+    policy: SinkPolicy,
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    current: Mutex<Option<OpenFile>>,
+    /// Files opened by this process, keeps names unique within a millisecond
+    sequence: Mutex<u64>,
+    healthy: AtomicBool,
+}
+
+impl JsonlFileSink {
+    pub fn new(
+        name: impl Into<String>,
+        policy: SinkPolicy,
+        dir: impl Into<PathBuf>,
+        max_file_bytes: u64,
+        max_files: usize,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            policy,
+            dir: dir.into(),
+            max_file_bytes,
+            max_files,
+            current: Mutex::new(None),
+            sequence: Mutex::new(0),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    /// File name prefix shared by this sink's files.
+    fn prefix(&self) -> String {
+        format!("{}-", self.name)
+    }
+
+    /// Open a new output file.
+    async fn open_next(&self) -> Result<OpenFile> {
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Sink directory error: {}", e)))?;
+
+        let mut sequence = self.sequence.lock().await;
+        *sequence += 1;
+        let path = self.dir.join(format!(
+            "{}{}-{:06}.jsonl",
+            self.prefix(),
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            *sequence
+        ));
+
+        let file = File::create(&path)
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Sink file error: {}", e)))?;
+        info!(sink = %self.name, path = %path.display(), "Opened sink file");
+
+        Ok(OpenFile {
+            writer: BufWriter::new(file),
+            bytes: 0,
+        })
+    }
+
+    /// Delete the oldest files beyond `max_files`.
+    async fn prune(&self) -> Result<()> {
+        if self.max_files == 0 {
+            return Ok(());
+        }
+
+        let mut files = sink_files(&self.dir, &self.prefix()).await?;
+        if files.len() <= self.max_files {
+            return Ok(());
+        }
+
+        // Timestamped names sort oldest first
+        files.sort();
+        let excess = files.len() - self.max_files;
+        for path in files.into_iter().take(excess) {
+            if let Err(e) = fs::remove_file(&path).await {
+                warn!(
+                    sink = %self.name,
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to remove sink file"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_lines(&self, lines: &[u8]) -> Result<()> {
+        let mut current = self.current.lock().await;
+
+        let rotate = match current.as_ref() {
+            Some(open) => open.bytes > 0 && open.bytes + lines.len() as u64 > self.max_file_bytes,
+            None => true,
+        };
+        if rotate {
+            if let Some(mut open) = current.take() {
+                sync(&mut open).await?;
+            }
+            *current = Some(self.open_next().await?);
+            self.prune().await?;
+        }
+
+        let open = current.as_mut().expect("sink file opened above");
+        open.writer
+            .write_all(lines)
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Sink write error: {}", e)))?;
+        open.bytes += lines.len() as u64;
+        Ok(())
+    }
+}
+
+/// This sink's files in `dir`.
+async fn sink_files(dir: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Sink directory error: {}", e)))?;
+
+    let mut files = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Sink directory error: {}", e)))?
+    {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(prefix) && name.ends_with(".jsonl") {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Flush buffered lines and wait for them to reach the disk.
+async fn sync(open: &mut OpenFile) -> Result<()> {
+    open.writer
+        .flush()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Sink flush error: {}", e)))?;
+    open.writer
+        .get_ref()
+        .sync_data()
+        .await
+        .map_err(|e| engine_core::Error::internal(format!("Sink sync error: {}", e)))
+}
+
+#[async_trait]
+impl Sink for JsonlFileSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn policy(&self) -> SinkPolicy {
+        self.policy
+    }
+
+    async fn write(&self, events: &[ClickHouseEvent]) -> Result<usize> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let mut lines = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut lines, event)?;
+            lines.push(b'\n');
+        }
+
+        let result = self.write_lines(&lines).await;
+        self.healthy.store(result.is_ok(), Ordering::Relaxed);
+        result.map(|_| events.len())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let mut current = self.current.lock().await;
+        let Some(open) = current.as_mut() else {
+            return Ok(());
+        };
+
+        let result = sync(open).await;
+        self.healthy.store(result.is_ok(), Ordering::Relaxed);
+        result
+    }
+
+    async fn health(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_event(event_id: &str) -> ClickHouseEvent {
+        ClickHouseEvent {
+            event_id: event_id.to_string(),
+            project_id: "proj".to_string(),
+            session_id: "sess".to_string(),
+            user_id: None,
+            event_type: "pageview".to_string(),
+            custom_name: None,
+            timestamp: 1_700_000_000_000,
+            url: "https://example.com/".to_string(),
+            path: "/".to_string(),
+            referrer: String::new(),
+            user_agent: String::new(),
+            device_type: String::new(),
+            browser: String::new(),
+            browser_version: String::new(),
+            os: String::new(),
+            country: String::new(),
+            region: None,
+            city: None,
+            data: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_sink_rotates_and_prunes() {
+        let dir = std::env::temp_dir().join(format!("jsonl-sink-{}", uuid::Uuid::new_v4()));
+        // Small enough that every batch after the first starts a new file
+        let sink = JsonlFileSink::new("debug", SinkPolicy::BestEffort, &dir, 100, 2);
+
+        for i in 0..3 {
+            let batch = vec![test_event(&format!("e{}", i))];
+            assert_eq!(sink.write(&batch).await.unwrap(), 1);
+            sink.flush().await.unwrap();
+        }
+
+        let mut files = sink_files(&dir, "debug-").await.unwrap();
+        files.sort();
+        assert_eq!(files.len(), 2);
+
+        let newest = std::fs::read_to_string(&files[1]).unwrap();
+        let event: ClickHouseEvent = serde_json::from_str(newest.trim_end()).unwrap();
+        assert_eq!(event.event_id, "e2");
+        assert!(sink.health().await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//! Pluggable destinations for consumed batches.
+//!
+//! The consumer writes every batch to its primary ClickHouse sink (named
+//! `primary`, required) and to any configured extra sinks:
+//! - `required`: retried with the primary; a failure fails the batch, so
+//!   offsets are not committed until every required sink has the data.
+//!   Only a batch the primary alone failed may be skipped, per the
+//!   consumer's `skip_on_failure`
+//! - `best_effort`: tried once per batch; failures are logged and counted
+//!   but never hold up the pipeline
+//!
+//! Implementations:
+//! - [`ClickHouseSink`]: the unified `events` table of a ClickHouse cluster
+//! - [`JsonlFileSink`]: size-rotated local JSONL files
+//! - [`WebhookSink`]: JSON batches POSTed to an HTTP endpoint
+//!
+//! [`write_batch`] records inserts, errors, events and latency for each sink
+//! under its name in `metrics().sink(name)`.
+
+pub mod clickhouse;
+pub mod file;
+pub mod webhook;
+
+pub use self::clickhouse::{compare_sinks, diff_counts, ClickHouseSink, CountMismatch};
+pub use file::JsonlFileSink;
+pub use webhook::WebhookSink;
+
+use async_trait::async_trait;
+use clickhouse_client::ClickHouseConfig;
+use engine_core::{ClickHouseEvent, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use telemetry::metrics;
+
+/// Name of the consumer's own ClickHouse sink.
+pub const PRIMARY_SINK: &str = "primary";
+
+/// What a sink failure means for the batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkPolicy {
+    /// Batch fails (and is retried) if this sink fails
+    Required,
+    /// Failures are logged and counted only
+    #[default]
+    BestEffort,
+}
+
+/// A destination for consumed event batches.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Unique name, used in logs and metrics.
+    fn name(&self) -> &str;
+
+    /// Failure policy for this sink.
+    fn policy(&self) -> SinkPolicy;
+
+    /// Write a batch. Returns the number of events written.
+    async fn write(&self, events: &[ClickHouseEvent]) -> Result<usize>;
+
+    /// Make previously written batches durable.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the destination is currently reachable.
+    async fn health(&self) -> bool;
+
+    /// The ClickHouse sink behind this sink, if any (used for count comparison).
+    fn as_clickhouse(&self) -> Option<&ClickHouseSink> {
+        None
+    }
+}
+
+/// Write and flush a batch, recording the sink's metrics.
+pub async fn write_batch(sink: &dyn Sink, events: &[ClickHouseEvent]) -> Result<usize> {
+    let sink_metrics = metrics().sink(sink.name());
+    let start = Instant::now();
+
+    let result = match sink.write(events).await {
+        Ok(count) => sink.flush().await.map(|_| count),
+        Err(e) => Err(e),
+    };
+
+    match &result {
+        Ok(count) => {
+            sink_metrics.inserts.inc();
+            sink_metrics.events_inserted.inc_by(*count as u64);
+            sink_metrics
+                .latency_ms
+                .observe(start.elapsed().as_millis() as u64);
+        }
+        Err(_) => sink_metrics.insert_errors.inc(),
+    }
+
+    result
+}
+
+/// Configuration for an additional sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkConfig {
+    /// Unique name, used in logs and metrics
+    pub name: String,
+    #[serde(default)]
+    pub policy: SinkPolicy,
+    #[serde(flatten)]
+    pub target: SinkTarget,
+}
+
+/// Where a sink writes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkTarget {
+    /// Another ClickHouse cluster
+    #[serde(rename = "clickhouse")]
+    ClickHouse { clickhouse: ClickHouseConfig },
+    /// Rotating JSONL files in a local directory
+    Jsonl {
+        path: String,
+        /// Rotate once a file reaches this size
+        #[serde(default = "default_max_file_bytes")]
+        max_file_bytes: u64,
+        /// Rotated files to keep (0 keeps all)
+        #[serde(default)]
+        max_files: usize,
+    },
+    /// HTTP endpoint receiving JSON batches
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_timeout_secs")]
+        timeout_secs: u64,
+        /// Extra request headers, e.g. for authentication
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+fn default_max_file_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+/// Create the sink described by `config`.
+pub fn build_sink(config: &SinkConfig) -> Result<Arc<dyn Sink>> {
+    let sink: Arc<dyn Sink> = match &config.target {
+        SinkTarget::ClickHouse { clickhouse } => Arc::new(ClickHouseSink::from_config(
+            &config.name,
+            config.policy,
+            clickhouse,
+        )?),
+        SinkTarget::Jsonl {
+            path,
+            max_file_bytes,
+            max_files,
+        } => Arc::new(JsonlFileSink::new(
+            &config.name,
+            config.policy,
+            path,
+            *max_file_bytes,
+            *max_files,
+        )),
+        SinkTarget::Webhook {
+            url,
+            timeout_secs,
+            headers,
+        } => Arc::new(WebhookSink::new(
+            &config.name,
+            config.policy,
+            url,
+            *timeout_secs,
+            headers,
+        )?),
+    };
+    Ok(sink)
+}
+
+/// Create the sinks described by `configs`.
+///
+/// Names key metrics and JSONL file names, so they must be unique and may
+/// not reuse [`PRIMARY_SINK`].
+pub fn build_sinks(configs: &[SinkConfig]) -> Result<Vec<Arc<dyn Sink>>> {
+    let mut names = HashSet::from([PRIMARY_SINK]);
+    for config in configs {
+        if !names.insert(config.name.as_str()) {
+            return Err(engine_core::Error::validation(format!(
+                "Duplicate sink name: {}",
+                config.name
+            )));
+        }
+    }
+    configs
+        .iter()
+        .map(|config| {
+            build_sink(config)
+                .map_err(|e| engine_core::Error::internal(format!("Sink {}: {}", config.name, e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_config_defaults() {
+        let config: SinkConfig = serde_json::from_str(
+            r#"{"name":"managed","type":"clickhouse","clickhouse":{"url":"http://ch:8123"}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.policy, SinkPolicy::BestEffort);
+        assert!(matches!(config.target, SinkTarget::ClickHouse { .. }));
+
+        let config: SinkConfig = serde_json::from_str(
+            r#"{"name":"debug","type":"jsonl","policy":"required","path":"/tmp/events"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.policy, SinkPolicy::Required);
+        match config.target {
+            SinkTarget::Jsonl {
+                max_file_bytes,
+                max_files,
+                ..
+            } => {
+                assert_eq!(max_file_bytes, default_max_file_bytes());
+                assert_eq!(max_files, 0);
+            }
+            other => panic!("unexpected target: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_build_sinks_rejects_duplicate_names() {
+        let jsonl = |name: &str| -> SinkConfig {
+            serde_json::from_str(&format!(
+                r#"{{"name":"{}","type":"jsonl","path":"/tmp/events"}}"#,
+                name
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(build_sinks(&[jsonl("a"), jsonl("b")]).unwrap().len(), 2);
+        assert!(build_sinks(&[jsonl("a"), jsonl("a")]).is_err());
+        assert!(build_sinks(&[jsonl(PRIMARY_SINK)]).is_err());
+    }
+}
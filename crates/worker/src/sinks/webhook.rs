@@ -0,0 +1,131 @@
+//! HTTP webhook sink.
+//!
+//! Each batch is POSTed as `{"sink": "...", "events": [...]}`. Any non-2xx
+//! response fails the batch.
+
+use super::{Sink, SinkPolicy};
+use async_trait::async_trait;
+use engine_core::{ClickHouseEvent, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::debug;
+
+/// Request body sent to the webhook.
+#[derive(Debug, Serialize)]
+struct WebhookBatch<'a> {
+    sink: &'a str,
+    events: &'a [ClickHouseEvent],
+}
+
+/// POSTs batches to an HTTP endpoint.
+pub struct WebhookSink {
+    name: String,
+    policy: SinkPolicy,
+    url: String,
+    http_client: Client,
+    healthy: AtomicBool,
+}
+
+impl WebhookSink {
+    pub fn new(
+        name: impl Into<String>,
+        policy: SinkPolicy,
+        url: impl Into<String>,
+        timeout_secs: u64,
+        headers: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut default_headers = HeaderMap::new();
+        for (key, value) in headers {
+            let key = HeaderName::from_bytes(key.as_bytes()).map_err(|e| {
+                engine_core::Error::validation(format!("Invalid header name {}: {}", key, e))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|e| {
+                engine_core::Error::validation(format!("Invalid value for header {}: {}", key, e))
+            })?;
+            default_headers.insert(key, value);
+        }
+
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .default_headers(default_headers)
+            .build()
+            .map_err(|e| engine_core::Error::internal(format!("HTTP client error: {}", e)))?;
+
+        Ok(Self {
+            name: name.into(),
+            policy,
+            url: url.into(),
+            http_client,
+            healthy: AtomicBool::new(true),
+        })
+    }
+
+    async fn post(&self, events: &[ClickHouseEvent]) -> Result<()> {
+        let batch = WebhookBatch {
+            sink: &self.name,
+            events,
+        };
+
+        let resp = self
+            .http_client
+            .post(&self.url)
+            .json(&batch)
+            .send()
+            .await
+            .map_err(|e| engine_core::Error::internal(format!("Webhook error: {}", e)))?;
+
+        if resp.status().is_success() {
+            debug!(sink = %self.name, events = events.len(), "Webhook batch delivered");
+            Ok(())
+        } else {
+            Err(engine_core::Error::internal(format!(
+                "Webhook returned status {}",
+                resp.status()
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn policy(&self) -> SinkPolicy {
+        self.policy
+    }
+
+    async fn write(&self, events: &[ClickHouseEvent]) -> Result<usize> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let result = self.post(events).await;
+        self.healthy.store(result.is_ok(), Ordering::Relaxed);
+        result.map(|_| events.len())
+    }
+
+    /// Reflects the most recent delivery; endpoints are not probed.
+    async fn health(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_sink_rejects_invalid_headers() {
+        let headers = HashMap::from([("bad header".to_string(), "x".to_string())]);
+        assert!(WebhookSink::new("hook", SinkPolicy::BestEffort, "http://x", 5, &headers).is_err());
+
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer t".to_string())]);
+        assert!(WebhookSink::new("hook", SinkPolicy::BestEffort, "http://x", 5, &headers).is_ok());
+    }
+}
@@ -13,11 +13,19 @@ use anyhow::{Context, Result};
 use tokio::signal;
 use tracing::{error, info};
 
+use api::key_store::{KeyStoreConfig, LocalKeyStore};
+use api::middleware::{ClusterBackend, ClusterConfig};
 use api::{router, AppState};
+use clickhouse_client::archive::ArchiveConfig;
 use clickhouse_client::{ClickHouseClient, ClickHouseConfig};
+use engine_core::ip::default_trusted_proxies;
+use engine_core::{Cidr, MauConfig};
 use redpanda::{Consumer, Producer, RedpandaConfig};
 use telemetry::{health, init_tracing_from_env};
-use worker::{WorkerConfig, WorkerScheduler};
+use worker::{
+    build_sinks, ForwardingConfig, ForwardingWorker, RetentionWorker, SinkConfig, TieringPolicy,
+    WorkerConfig, WorkerScheduler,
+};
 
 /// Application configuration.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -31,11 +39,52 @@ struct Config {
     #[serde(default = "default_auth_url")]
     auth_url: String,
 
+    /// Validate keys from a local file or ClickHouse instead of `auth_url`
+    #[serde(default)]
+    key_store: Option<KeyStoreConfig>,
+
+    /// Proxies whose `X-Forwarded-For` entries are used to find the client IP
+    #[serde(default = "default_trusted_proxies")]
+    trusted_proxies: Vec<Cidr>,
+
+    /// Return 503 from ingestion once consumer lag exceeds this many events
+    #[serde(default)]
+    max_consumer_lag: Option<u64>,
+
     #[serde(default)]
     redpanda: RedpandaConfig,
 
+    /// Topic for events sent with test keys; empty to validate them without storing.
+    /// The topic must already exist (see `redpanda::topic::SANDBOX`).
+    #[serde(default)]
+    sandbox_topic: String,
+
     #[serde(default)]
     clickhouse: ClickHouseConfig,
+
+    /// Parquet export of partitions before retention drops them
+    #[serde(default)]
+    archive: Option<ArchiveConfig>,
+
+    /// Per-table moves of aging partitions to a cold volume
+    #[serde(default)]
+    tiering: Vec<TieringPolicy>,
+
+    /// Additional destinations written alongside `clickhouse`
+    #[serde(default)]
+    sinks: Vec<SinkConfig>,
+
+    /// Per-project forwarding of events to customer webhooks
+    #[serde(default)]
+    forwarding: ForwardingConfig,
+
+    /// Share rate limit usage between replicas
+    #[serde(default)]
+    rate_limit_cluster: Option<ClusterConfig>,
+
+    /// What ingestion does for projects over their MAU limit, per plan
+    #[serde(default)]
+    mau: MauConfig,
 }
 
 fn default_host() -> String {
@@ -56,8 +105,18 @@ impl Default for Config {
             host: default_host(),
             port: default_port(),
             auth_url: default_auth_url(),
+            key_store: None,
+            trusted_proxies: default_trusted_proxies(),
+            max_consumer_lag: None,
             redpanda: RedpandaConfig::default(),
+            sandbox_topic: String::new(),
             clickhouse: ClickHouseConfig::default(),
+            archive: None,
+            tiering: Vec::new(),
+            sinks: Vec::new(),
+            forwarding: ForwardingConfig::default(),
+            rate_limit_cluster: None,
+            mau: MauConfig::default(),
         }
     }
 }
@@ -83,6 +142,20 @@ async fn main() -> Result<()> {
 
     // Load configuration
     let config = load_config()?;
+    if let Some(archive) = &config.archive {
+        archive
+            .validate()
+            .map_err(anyhow::Error::msg)
+            .context("Invalid [archive] config")?;
+    }
+
+    // One-off commands run against ClickHouse and exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("restore") => return restore_command(&config, &args[1..]).await,
+        Some("retention-plan") => return retention_plan_command(&config).await,
+        _ => {}
+    }
 
     // Debug: log config to verify environment variables are being read
     info!(
@@ -129,16 +202,124 @@ async fn main() -> Result<()> {
         .context("Failed to create Redpanda consumer")?,
     );
 
+    // Initialize additional sinks
+    let sinks = build_sinks(&config.sinks).context("Failed to create sinks")?;
+    for sink in &sinks {
+        if let Some(clickhouse_sink) = sink.as_clickhouse() {
+            if let Err(e) = clickhouse_client::health::init_schema(clickhouse_sink.client()).await {
+                error!(sink = %sink.name(), "Failed to initialize sink schema: {}", e);
+            }
+        }
+        info!(sink = %sink.name(), policy = ?sink.policy(), "Sink configured");
+    }
+
     // Start background workers with consumer
-    let worker_scheduler = Arc::new(WorkerScheduler::with_consumer(
-        WorkerConfig::default(),
-        clickhouse.clone(),
-        consumer.clone(),
-    ));
+    let worker_config = WorkerConfig {
+        archive: config.archive.clone(),
+        tiering: config.tiering.clone(),
+        ..WorkerConfig::default()
+    };
+    let mut worker_scheduler =
+        WorkerScheduler::with_consumer(worker_config, clickhouse.clone(), consumer.clone())
+            .with_sinks(sinks);
+
+    // Customer event forwarding runs on its own consumer group
+    if !config.forwarding.rules.is_empty() {
+        let forwarding_consumer = Arc::new(
+            Consumer::new(
+                redpanda::ConsumerConfig {
+                    group_id: config.forwarding.group_id.clone(),
+                    ..config.redpanda.consumer.clone()
+                },
+                config.redpanda.brokers.clone(),
+                config.redpanda.sasl_username.clone(),
+                config.redpanda.sasl_password.clone(),
+            )
+            .await
+            .context("Failed to create forwarding consumer")?
+            .without_pipeline_metrics(),
+        );
+        let forwarding =
+            ForwardingWorker::new(forwarding_consumer, clickhouse.clone(), &config.forwarding)
+                .map_err(anyhow::Error::msg)
+                .context("Invalid forwarding configuration")?;
+        worker_scheduler = worker_scheduler.with_forwarding(forwarding);
+    }
+
+    let worker_scheduler = Arc::new(worker_scheduler);
     let _worker_handles = worker_scheduler.start();
 
     // Create application state
-    let state = AppState::new(producer.clone(), clickhouse.clone(), &config.auth_url);
+    let state = AppState::new(producer.clone(), clickhouse.clone(), &config.auth_url)
+        .with_max_consumer_lag(config.max_consumer_lag)
+        .with_mau(config.mau.clone())
+        .with_trusted_proxies(config.trusted_proxies.clone());
+    let _mau_flush = state.mau.clone().start_flush(clickhouse.clone());
+
+    // Test-key traffic goes to its own topic
+    let state = if config.sandbox_topic.is_empty() {
+        state
+    } else {
+        // The producer cannot create topics, so a missing one would fail every test batch
+        let missing =
+            redpanda::health::verify_topics(&config.redpanda, &[config.sandbox_topic.as_str()])
+                .await;
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Sandbox topic {} does not exist; create it or unset sandbox_topic",
+                config.sandbox_topic
+            );
+        }
+        let sandbox = Producer::new(RedpandaConfig {
+            topic: config.sandbox_topic.clone(),
+            ..config.redpanda.clone()
+        })
+        .await
+        .context("Failed to create sandbox producer")?;
+        info!(topic = %config.sandbox_topic, "Test keys routed to sandbox topic");
+        state.with_sandbox(Arc::new(sandbox))
+    };
+
+    // Validate keys locally instead of with the auth daemon
+    let state = match &config.key_store {
+        Some(key_store_config) => {
+            let key_store = Arc::new(
+                LocalKeyStore::load(key_store_config.clone(), clickhouse.clone())
+                    .await
+                    .context("Failed to load key store")?,
+            );
+            let (tenants, keys) = key_store.counts();
+            let _key_store_reload = key_store.clone().start_reload();
+            info!(
+                source = ?key_store_config.source,
+                tenants,
+                keys,
+                "Local key store enabled"
+            );
+            state.with_auth_provider(key_store)
+        }
+        None => state,
+    };
+
+    // Share rate limit usage with peer replicas
+    let state = match &config.rate_limit_cluster {
+        Some(cluster_config) => {
+            cluster_config
+                .validate()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid rate_limit_cluster config")?;
+            let cluster = Arc::new(ClusterBackend::new(cluster_config.clone()));
+            let _rate_limit_sync = cluster.clone().start_sync();
+            info!(
+                instance_id = %cluster.instance_id(),
+                peers = cluster_config.peers.len(),
+                peer_dns = ?cluster_config.peer_dns,
+                "Cluster rate limiting enabled"
+            );
+            state.with_rate_limit_cluster(cluster)
+        }
+        None => state,
+    };
 
     // Start rate limiter cleanup background task
     let _rate_limiter_cleanup = state.start_rate_limiter_cleanup();
@@ -159,10 +340,14 @@ async fn main() -> Result<()> {
         .context("Failed to bind to address")?;
 
     // Run server with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .context("Server error")?;
+    // Peer addresses are needed to resolve client IPs
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .context("Server error")?;
 
     // Cleanup
     info!("Shutting down...");
@@ -215,6 +400,9 @@ fn load_config() -> Result<Config> {
     if let Ok(topic) = std::env::var("INGESTION_REDPANDA_TOPIC") {
         config.redpanda.topic = topic;
     }
+    if let Ok(topic) = std::env::var("INGESTION_SANDBOX_TOPIC") {
+        config.sandbox_topic = topic;
+    }
 
     // Manual overrides for nested ClickHouse config
     if let Ok(url) = std::env::var("INGESTION_CLICKHOUSE_URL") {
@@ -229,15 +417,100 @@ fn load_config() -> Result<Config> {
     if let Ok(password) = std::env::var("INGESTION_CLICKHOUSE_PASSWORD") {
         config.clickhouse.password = Some(password);
     }
+    if let Ok(async_insert) = std::env::var("INGESTION_CLICKHOUSE_ASYNC_INSERT") {
+        config.clickhouse.async_insert = async_insert == "1" || async_insert == "true";
+    }
 
     // Auth URL override
     if let Ok(auth_url) = std::env::var("INGESTION_AUTH_URL") {
         config.auth_url = auth_url;
     }
+    if let Ok(path) = std::env::var("INGESTION_KEY_STORE_FILE") {
+        config.key_store = Some(KeyStoreConfig::file(path));
+    }
+    // Comma-separated CIDRs; empty trusts no proxy
+    if let Ok(proxies) = std::env::var("INGESTION_TRUSTED_PROXIES") {
+        config.trusted_proxies = proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(Cidr::parse)
+            .collect::<engine_core::Result<_>>()
+            .context("Invalid INGESTION_TRUSTED_PROXIES")?;
+    }
+    if let Ok(max_lag) = std::env::var("INGESTION_MAX_CONSUMER_LAG") {
+        config.max_consumer_lag = max_lag.parse().ok();
+    }
+
+    // Cluster rate limiting, e.g. INGESTION_RATE_LIMIT_PEER_DNS=<app>.internal on fly.io
+    if let Ok(secret) = std::env::var("INGESTION_RATE_LIMIT_CLUSTER_SECRET") {
+        let cluster = config.rate_limit_cluster.get_or_insert_with(|| {
+            let mut cluster = ClusterConfig::new(String::new());
+            cluster.peer_port = config.port;
+            cluster
+        });
+        cluster.secret = secret;
+        if let Ok(peer_dns) = std::env::var("INGESTION_RATE_LIMIT_PEER_DNS") {
+            cluster.peer_dns = Some(peer_dns);
+        }
+    }
 
     Ok(config)
 }
 
+/// `restore <table> <partition_id> [project_id]` - load a Parquet archive
+/// back into ClickHouse.
+async fn restore_command(config: &Config, args: &[String]) -> Result<()> {
+    let (table, partition_id, project_id) = match args {
+        [table, partition_id] => (table, partition_id, None),
+        [table, partition_id, project_id] => (table, partition_id, Some(project_id.as_str())),
+        _ => anyhow::bail!("Usage: ingestion-engine restore <table> <partition_id> [project_id]"),
+    };
+    let archive = config
+        .archive
+        .as_ref()
+        .context("No [archive] target configured")?;
+
+    let clickhouse = ClickHouseClient::new(config.clickhouse.clone())
+        .context("Failed to create ClickHouse client")?;
+    let rows = clickhouse_client::archive::restore_partition(
+        &clickhouse,
+        &archive.target,
+        table,
+        partition_id,
+        project_id,
+    )
+    .await
+    .context("Restore failed")?;
+
+    info!(table = %table, partition_id = %partition_id, rows, "Restore complete");
+    Ok(())
+}
+
+/// Retention worker with the configured archive and tiering policies.
+fn retention_worker(config: &Config, clickhouse: Arc<ClickHouseClient>) -> RetentionWorker {
+    RetentionWorker::new(clickhouse)
+        .with_archive(config.archive.clone())
+        .with_tiering(config.tiering.clone())
+}
+
+/// `retention-plan` - print what the next retention run would drop or move,
+/// as JSON, without changing anything.
+async fn retention_plan_command(config: &Config) -> Result<()> {
+    let clickhouse = Arc::new(
+        ClickHouseClient::new(config.clickhouse.clone())
+            .context("Failed to create ClickHouse client")?,
+    );
+    let plan = retention_worker(config, clickhouse)
+        .plan()
+        .await
+        .map_err(anyhow::Error::msg)
+        .context("Failed to build retention plan")?;
+
+    println!("{}", serde_json::to_string_pretty(&plan)?);
+    Ok(())
+}
+
 /// Check component health on startup.
 async fn check_health(config: &Config, clickhouse: &ClickHouseClient) {
     // Check Redpanda
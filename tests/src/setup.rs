@@ -39,6 +39,7 @@ impl TestContext {
             password: containers.clickhouse_password.clone(),
             pool_size: 5,
             timeout_secs: 30,
+            ..ClickHouseConfig::default()
         };
         let clickhouse =
             Arc::new(ClickHouseClient::new(ch_config).expect("Failed to create ClickHouse client"));
@@ -51,12 +52,13 @@ impl TestContext {
         // Use mock producer instead of real Redpanda
         let mock_producer = Arc::new(MockProducer::new());
 
-        // Create router with mock producer
+        // Create router with mock producer; test keys go to the same mock as sandbox
         let state = AppState::new(
             mock_producer.clone() as Arc<dyn EventProducer>,
             clickhouse.clone(),
             "mock",
-        );
+        )
+        .with_sandbox(mock_producer.clone() as Arc<dyn EventProducer>);
         let router = router(state);
 
         Self {